@@ -1,17 +1,25 @@
 #version 450 core
 
+// Set by `Material::apply` from the MTL material of each submesh.
+struct Material {
+    vec3 ambient;
+    vec3 diffuse;
+    bool has_diffuse_map;
+    sampler2D diffuse_map;
+};
+
+in vec3 v_normal;
 in vec2 v_texcoord;
 
 out vec4 frag_color;
 
-uniform sampler2D color_map0;
-uniform sampler2D color_map1;
+uniform Material u_material;
 
 void main()
 {
-    frag_color = mix(
-        texture(color_map0, v_texcoord), 
-        texture(color_map1, v_texcoord),
-        0.25
-    );
+    vec3 albedo = u_material.has_diffuse_map
+        ? texture(u_material.diffuse_map, v_texcoord).rgb
+        : vec3(1.0);
+    float light = max(dot(normalize(v_normal), normalize(vec3(0.4, 1.0, 0.7))), 0.0);
+    frag_color = vec4(albedo * (u_material.ambient + u_material.diffuse * light), 1.0);
 }
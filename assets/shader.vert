@@ -5,14 +5,20 @@ layout (location = 1) in vec3 a_color;
 layout (location = 2) in vec3 a_normal;
 layout (location = 3) in vec2 a_texcoord;
 
+out vec3 v_normal;
 out vec2 v_texcoord;
 
+layout (std140) uniform Camera
+{
+    mat4 view;
+    mat4 projection;
+} u_camera;
+
 uniform mat4 u_model;
-uniform mat4 u_view;
-uniform mat4 u_projection;
 
 void main()
 {
+    v_normal = mat3(u_model) * a_normal;
     v_texcoord = a_texcoord;
-    gl_Position = u_projection * u_view * u_model * vec4(a_pos, 1.0);
+    gl_Position = u_camera.projection * u_camera.view * u_model * vec4(a_pos, 1.0);
 }
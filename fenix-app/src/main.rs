@@ -1,18 +1,236 @@
 use fenix_core::logging;
 use fenix_utils::math;
 use fenix_renderer::{
-    buffer::{IndexBuffer, VertexBuffer},
-    shader::ShaderProgram, texture::Texture2D,
+    asset::{AssetManager, Handle},
+    block_layout::BlockLayout,
+    buffer::UniformBuffer,
+    framebuffer::{AttachmentFormat, AttachmentSpec, Framebuffer, FramebufferSpec},
+    mesh::Mesh,
+    shader::{cache::ProgramCache, ShaderProgram, ShaderType},
+    vertex_array::VertexArray,
+    Error,
 };
 use glam::{Mat4, Vec3};
 use glfw::{Action, Context, CursorMode, Key, OpenGlProfileHint, WindowHint};
 use log::{error, info, trace, warn, LevelFilter};
-use std::mem::size_of;
-use std::{ffi::c_void, ptr};
+use std::{
+    env,
+    ffi::c_void,
+    mem,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+#[cfg(feature = "headless")]
+use {fenix_renderer::headless::HeadlessContext, std::fs};
+
+/// Contents of the `Camera` uniform block shared by all the shaders.
+#[derive(BlockLayout)]
+struct Camera {
+    view: Mat4,
+    projection: Mat4,
+}
+
+const CAMERA_BINDING: u32 = 0;
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+
+/// Command line options.
+struct Options {
+    /// Render without a window and save the frames as PNG files.
+    #[cfg(feature = "headless")]
+    headless: bool,
+    /// Number of frames rendered in headless mode.
+    #[cfg(feature = "headless")]
+    frames: u32,
+    /// Directory where the frames of headless mode are saved.
+    #[cfg(feature = "headless")]
+    output: PathBuf,
+    /// Directories where the assets are looked up, in order.
+    assets: Vec<PathBuf>,
+}
+
+impl Options {
+    fn parse() -> Result<Self, String> {
+        let mut options = Self {
+            #[cfg(feature = "headless")]
+            headless: false,
+            #[cfg(feature = "headless")]
+            frames: 1,
+            #[cfg(feature = "headless")]
+            output: PathBuf::from("frames"),
+            assets: Vec::new(),
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                #[cfg(feature = "headless")]
+                "--headless" => options.headless = true,
+                #[cfg(feature = "headless")]
+                "--frames" => {
+                    options.frames = args
+                        .next()
+                        .and_then(|frames| frames.parse().ok())
+                        .ok_or("--frames expects a number")?;
+                },
+                #[cfg(feature = "headless")]
+                "--output" => {
+                    options.output = args.next().ok_or("--output expects a directory")?.into();
+                },
+                #[cfg(not(feature = "headless"))]
+                "--headless" | "--frames" | "--output" => {
+                    return Err(format!("{} needs the 'headless' feature of fenix-app", arg));
+                },
+                "--assets" => {
+                    options.assets.push(args.next().ok_or("--assets expects a directory")?.into());
+                },
+                _ => return Err(format!("unknown argument '{}'", arg)),
+            }
+        }
+        if options.assets.is_empty() {
+            options
+                .assets
+                .push(Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets"));
+        }
+        Ok(options)
+    }
+}
+
+/// The resources needed to render the demo scene.
+struct Scene {
+    /// Drawn with the materials of its MTL library.
+    cube: Handle<Mesh>,
+    /// Loads the meshes and shaders and rebuilds them when their files change.
+    assets: AssetManager,
+    program: Handle<ShaderProgram>,
+    camera_buffer: UniformBuffer<Camera>,
+    /// The scene is rendered with 4x MSAA here instead of directly to the window.
+    framebuffer: Framebuffer,
+}
+
+impl Scene {
+    fn new(options: &Options) -> Result<Self, Error> {
+        let mut assets = options
+            .assets
+            .iter()
+            .fold(AssetManager::new(), |assets, root| assets.root(root))
+            .program_cache(ProgramCache::new("cache/shaders"));
+
+        let cube = assets.load_mesh("cube.obj")?;
+
+        let program = assets.load_program(&[
+            (ShaderType::Vertex, "shader.vert"),
+            (ShaderType::Fragment, "shader.frag"),
+        ])?;
+        bind_camera_block(&mut program.borrow_mut());
+
+        let camera_buffer = UniformBuffer::new(&Camera {
+            view: Mat4::IDENTITY,
+            projection: Mat4::IDENTITY,
+        });
+        camera_buffer.bind(CAMERA_BINDING);
+
+        let framebuffer = Framebuffer::new(
+            FramebufferSpec::new(WIDTH, HEIGHT)
+                .samples(4)
+                .color(AttachmentSpec::renderbuffer(AttachmentFormat::Rgba8))
+                .depth(AttachmentSpec::renderbuffer(
+                    AttachmentFormat::Depth24Stencil8,
+                )),
+        )?;
+
+        unsafe {
+            gl::ClearColor(0.2, 0.3, 0.3, 1.0);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+
+        Ok(Self {
+            cube,
+            assets,
+            program,
+            camera_buffer,
+            framebuffer,
+        })
+    }
+
+    /// Renders a frame of `width` by `height` pixels into `self.framebuffer`.
+    fn draw(&mut self, width: u32, height: u32) {
+        for program in self.assets.poll() {
+            bind_camera_block(&mut program.borrow_mut());
+        }
+        if let Err(err) = self.framebuffer.resize(width.max(1), height.max(1)) {
+            error!("{}", err);
+        }
+
+        let model = Mat4::IDENTITY;
+        let view = Mat4::from_translation(Vec3::new(0.0, 0.0, -3.0))
+            * Mat4::from_axis_angle(
+                Vec3::new(-1.0, 1.0, -1.0).try_normalize().unwrap(),
+                math::radians(30.0),
+            );
+        let projection = Mat4::perspective_rh(45.0, 16.0 / 9.0, 0.1, 10.0);
+        self.camera_buffer.set(&Camera { view, projection });
+
+        let mut program = self.program.borrow_mut();
+        program.bind();
+        if let Err(err) = program.set("u_model", &model) {
+            error!("{}", err);
+        }
+
+        // Draw frame to buffer
+        self.framebuffer.bind();
+        unsafe {
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
-fn main() {
+            let cube = self.cube.borrow();
+            cube.get_vertex_array().bind();
+            for submesh in cube.get_submeshes() {
+                if let Some(material) = submesh.material {
+                    if let Err(err) = cube.get_materials()[material].apply(&mut program) {
+                        error!("{}", err);
+                    }
+                }
+                gl::DrawElements(
+                    gl::TRIANGLES,
+                    submesh.count as i32,
+                    gl::UNSIGNED_INT,
+                    (submesh.offset * mem::size_of::<u32>()) as *const c_void,
+                );
+            }
+            VertexArray::unbind();
+        }
+        Framebuffer::unbind();
+    }
+}
+
+fn main() -> ExitCode {
     logging::setup("fenix.log", LevelFilter::Trace).expect("failed to initialize logging");
 
+    let options = match Options::parse() {
+        Ok(options) => options,
+        Err(err) => {
+            error!("{}", err);
+            return ExitCode::FAILURE;
+        },
+    };
+    #[cfg(feature = "headless")]
+    let result = if options.headless {
+        run_headless(&options)
+    } else {
+        run_windowed(&options)
+    };
+    #[cfg(not(feature = "headless"))]
+    let result = run_windowed(&options);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            error!("{}", err);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run_windowed(options: &Options) -> Result<(), Error> {
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
 
     info!("GLFW version: {:?}", glfw::get_version_string());
@@ -30,7 +248,7 @@ fn main() {
     }
 
     let (mut window, events) = glfw
-        .create_window(1280, 720, "Fenix - Editor", glfw::WindowMode::Windowed)
+        .create_window(WIDTH, HEIGHT, "Fenix - Editor", glfw::WindowMode::Windowed)
         .expect("Failed to create GLFW window.");
 
     window.make_current();
@@ -40,114 +258,73 @@ fn main() {
     glfw.set_swap_interval(glfw::SwapInterval::Sync(1));
 
     // Load OpenGL function pointers
-    gl::load_with(|s| window.get_proc_address(s) as *const c_void);
-
-    let container_tex = Texture2D::from_file("assets/image/container.jpg").unwrap();
-    let awesome_tex = Texture2D::from_file("assets/image/awesomeface.png").unwrap();
-
-    #[rustfmt::skip]
-    const VERTICES: [f32; 5 * 4] = [
-        0.0, 0.0, 0.0, 0.0, 0.0,
-        1.0, 0.0, 0.0, 1.0, 0.0,
-        1.0, 1.0, 0.0, 1.0, 1.0,
-        0.0, 1.0, 0.0, 0.0, 1.0,
-    ];
-
-    #[rustfmt::skip]
-    const INDICES: [u32; 3 * 2] = [
-        0, 1, 2,
-        0, 2, 3,
-    ];
-
-    let mut vao = 0;
-
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
-    }
-
-    let vertex_buff = VertexBuffer::from(&VERTICES);
-    vertex_buff.bind();
-
-    let index_buff = IndexBuffer::from(&INDICES);
-    index_buff.bind();
-
-    unsafe {
-        gl::EnableVertexAttribArray(0);
-        gl::VertexAttribPointer(
-            0,
-            3,
-            gl::FLOAT,
-            gl::FALSE,
-            5 * size_of::<f32>() as i32,
-            0 as *const c_void,
-        );
-
-        gl::EnableVertexAttribArray(3);
-        gl::VertexAttribPointer(
-            3,
-            2,
-            gl::FLOAT,
-            gl::FALSE,
-            5 * size_of::<f32>() as i32,
-            (3 * size_of::<f32>()) as *const c_void,
-        );
-    }
-
-    let mut program = ShaderProgram::from_file("assets/shader.vert", "assets/shader.frag", None)
-        .unwrap_or_else(|err| {
-            error!("{}", err);
-            warn!("Using empty ShaderProgram");
-            ShaderProgram::new().unwrap()
-        });
-    program.bind();
+    fenix_renderer::load_with(|s| window.get_proc_address(s) as *const c_void);
 
-    unsafe {
-        gl::Viewport(0, 0, 1280, 720);
-        gl::ClearColor(0.2, 0.3, 0.3, 1.0);
-    }
+    let mut scene = Scene::new(options)?;
 
     while !window.should_close() {
         // Poll for and process events
         glfw.poll_events();
+        let mut screenshot = false;
         for (_, event) in glfw::flush_messages(&events) {
+            if let glfw::WindowEvent::Key(Key::F12, _, Action::Press, _) = event {
+                screenshot = true;
+            }
             handle_window_event(&mut window, event);
         }
 
-        let model = Mat4::from_translation(Vec3::new(-0.5, -0.5, -0.5));
-        let view = Mat4::from_translation(Vec3::new(0.0, 0.0, -3.0))
-            * Mat4::from_axis_angle(
-                Vec3::new(-1.0, 1.0, -1.0).try_normalize().unwrap(),
-                math::radians(30.0),
-            );
-        let projection = Mat4::perspective_rh(45.0, 16.0 / 9.0, 0.1, 10.0);
+        let (width, height) = window.get_framebuffer_size();
+        scene.draw(width as u32, height as u32);
+        scene
+            .framebuffer
+            .blit_to_default(width as u32, height as u32);
+        if screenshot {
+            save_screenshot(width as u32, height as u32);
+        }
 
-        program.set_uniform_mat4("u_model", &model);
-        program.set_uniform_mat4("u_view", &view);
-        program.set_uniform_mat4("u_projection", &projection);
+        // Swap front and back buffers
+        window.swap_buffers();
+    }
+    Ok(())
+}
 
-        container_tex.bind(0);
-        awesome_tex.bind(1);
+/// Renders `options.frames` frames without a window and saves them as PNG files.
+#[cfg(feature = "headless")]
+fn run_headless(options: &Options) -> Result<(), Error> {
+    let context = HeadlessContext::new()?;
+    info!("Rendering headless with {}", context.get_renderer());
 
-        program.set_uniform_1i("color_map0", 0);
-        program.set_uniform_1i("color_map1", 1);
+    let mut scene = Scene::new(options)?;
+    // Multisampled framebuffers can't be read back directly, so they are resolved here first.
+    let resolved = Framebuffer::new(
+        FramebufferSpec::new(WIDTH, HEIGHT).color(AttachmentSpec::texture(AttachmentFormat::Rgba8)),
+    )?;
+    fs::create_dir_all(&options.output).map_err(|source| Error::Write {
+        path: options.output.clone(),
+        source,
+    })?;
 
-        // Draw frame to buffer
-        unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT);
-
-            gl::BindVertexArray(vao);
-            gl::DrawElements(
-                gl::TRIANGLES,
-                index_buff.get_count() as i32,
-                gl::UNSIGNED_INT,
-                ptr::null(),
-            );
-            gl::BindVertexArray(0);
-        }
+    for frame in 0..options.frames {
+        scene.draw(WIDTH, HEIGHT);
+        scene.framebuffer.resolve_to(&resolved);
+        let path = options.output.join(format!("frame_{:04}.png", frame));
+        resolved.save_png(0, &path)?;
+        info!("Saved frame {}", path.display());
+    }
+    Ok(())
+}
 
-        // Swap front and back buffers
-        window.swap_buffers();
+/// Saves the frame drawn to the window in the `screenshots` directory.
+fn save_screenshot(width: u32, height: u32) {
+    match Framebuffer::save_screenshot(width, height, "screenshots") {
+        Ok(path) => info!("Saved screenshot {}", path.display()),
+        Err(err) => error!("{}", err),
+    }
+}
+
+fn bind_camera_block(program: &mut ShaderProgram) {
+    if let Err(err) = program.bind_uniform_block("Camera", CAMERA_BINDING) {
+        error!("{}", err);
     }
 }
 
@@ -0,0 +1,273 @@
+//! Derive macros for `fenix-renderer` traits.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitInt};
+
+/// Derives `fenix_renderer::vertex_array::Vertex` for a struct.
+///
+/// The struct must be `#[repr(C)]` and implement `bytemuck::Pod`. Every field needs a
+/// `#[location(N)]` attribute with the location of the attribute in the vertex shader, and
+/// may be marked as `#[normalized]`. Attributes are laid out in declaration order.
+///
+/// ```ignore
+/// #[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+/// #[repr(C)]
+/// struct MyVertex {
+///     #[location(0)]
+///     pos: Vec3,
+///     #[location(3)]
+///     uv: Vec2,
+/// }
+/// ```
+#[proc_macro_derive(Vertex, attributes(location, normalized))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_vertex(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_vertex(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    if !has_repr_c(input) {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Vertex can only be derived for #[repr(C)] structs",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "Vertex can only be derived for structs with named fields",
+                ))
+            },
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Vertex can only be derived for structs",
+            ))
+        },
+    };
+
+    let mut attributes = Vec::new();
+    for field in fields {
+        let mut location = None;
+        let mut normalized = false;
+        for attr in &field.attrs {
+            if attr.path.is_ident("location") {
+                let lit: LitInt = attr.parse_args()?;
+                location = Some(lit.base10_parse::<u32>()?);
+            } else if attr.path.is_ident("normalized") {
+                normalized = true;
+            }
+        }
+
+        let ident = field.ident.as_ref().unwrap();
+        let location = location
+            .ok_or_else(|| syn::Error::new_spanned(ident, "missing #[location(N)] attribute"))?;
+        let name = ident.to_string();
+        let ty = &field.ty;
+        let normalized = if normalized {
+            quote!(.normalized())
+        } else {
+            quote!()
+        };
+        attributes.push(quote! {
+            ::fenix_renderer::vertex_array::VertexAttribute::of::<#ty>(#name, #location)
+                #normalized
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::fenix_renderer::vertex_array::Vertex for #ident #ty_generics
+        #where_clause
+        {
+            fn layout() -> ::fenix_renderer::vertex_array::BufferLayout {
+                ::fenix_renderer::vertex_array::BufferLayout::new(::std::vec![
+                    #(#attributes),*
+                ])
+            }
+        }
+    })
+}
+
+/// Derives `fenix_renderer::block_layout::BlockLayout` for a struct.
+///
+/// The fields are laid out in declaration order following the `std140` or `std430` rules,
+/// just like the members of a GLSL struct. Every field must implement `BlockLayout` too.
+///
+/// ```ignore
+/// #[derive(BlockLayout)]
+/// struct Camera {
+///     view: Mat4,
+///     projection: Mat4,
+/// }
+/// ```
+#[proc_macro_derive(BlockLayout)]
+pub fn derive_block_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_block_layout(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_block_layout(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "BlockLayout can only be derived for structs with named fields",
+                ))
+            },
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "BlockLayout can only be derived for structs",
+            ))
+        },
+    };
+
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let idents: Vec<_> = fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::fenix_renderer::block_layout::BlockLayout for #ident #ty_generics
+        #where_clause
+        {
+            fn alignment(rules: ::fenix_renderer::block_layout::LayoutRules) -> usize {
+                ::fenix_renderer::block_layout::struct_alignment(rules, &[
+                    #(<#types as ::fenix_renderer::block_layout::BlockLayout>::alignment(rules)),*
+                ])
+            }
+
+            fn size(rules: ::fenix_renderer::block_layout::LayoutRules) -> usize {
+                let mut offset = 0;
+                #(
+                    offset = ::fenix_renderer::block_layout::align_to(
+                        offset,
+                        <#types as ::fenix_renderer::block_layout::BlockLayout>::alignment(rules),
+                    ) + <#types as ::fenix_renderer::block_layout::BlockLayout>::size(rules);
+                )*
+                ::fenix_renderer::block_layout::align_to(offset, Self::alignment(rules))
+            }
+
+            fn write(
+                &self,
+                rules: ::fenix_renderer::block_layout::LayoutRules,
+                out: &mut [u8],
+            ) {
+                let mut offset = 0;
+                #(
+                    offset = ::fenix_renderer::block_layout::align_to(
+                        offset,
+                        <#types as ::fenix_renderer::block_layout::BlockLayout>::alignment(rules),
+                    );
+                    ::fenix_renderer::block_layout::BlockLayout::write(
+                        &self.#idents,
+                        rules,
+                        &mut out[offset..],
+                    );
+                    offset += <#types as ::fenix_renderer::block_layout::BlockLayout>::size(rules);
+                )*
+                let _ = offset;
+            }
+        }
+    })
+}
+
+fn has_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| {
+        attr.path.is_ident("repr")
+            && attr
+                .parse_args_with(
+                    syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated,
+                )
+                .is_ok_and(|reprs| {
+                    reprs.iter().any(|repr| repr.path().is_ident("C"))
+                })
+    })
+}
+
+/// Layouts `#[derive(Vertex)]` rejects at compile time.
+///
+/// Fields need a `#[location(N)]`:
+///
+/// ```compile_fail
+/// # use fenix_renderer::vertex_array::Vertex;
+/// #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+/// #[repr(C)]
+/// struct MyVertex {
+///     pos: glam::Vec3,
+/// }
+/// ```
+///
+/// The struct must be `#[repr(C)]`:
+///
+/// ```compile_fail
+/// # use fenix_renderer::vertex_array::Vertex;
+/// #[derive(Clone, Copy, Vertex)]
+/// struct MyVertex {
+///     #[location(0)]
+///     pos: glam::Vec3,
+/// }
+/// # unsafe impl bytemuck::Zeroable for MyVertex {}
+/// # unsafe impl bytemuck::Pod for MyVertex {}
+/// ```
+///
+/// Field types must implement `AttributeFormat`, so there are no `bool` attributes:
+///
+/// ```compile_fail,E0277
+/// # use fenix_renderer::vertex_array::Vertex;
+/// #[derive(Clone, Copy, Vertex)]
+/// #[repr(C)]
+/// struct MyVertex {
+///     #[location(0)]
+///     visible: bool,
+/// }
+/// # unsafe impl bytemuck::Zeroable for MyVertex {}
+/// # unsafe impl bytemuck::Pod for MyVertex {}
+/// ```
+///
+/// and arrays of scalars are vectors of at most 4 components:
+///
+/// ```compile_fail,E0277
+/// # use fenix_renderer::vertex_array::Vertex;
+/// #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+/// #[repr(C)]
+/// struct MyVertex {
+///     #[location(0)]
+///     weights: [f32; 5],
+/// }
+/// ```
+///
+/// While the same structs with supported fields compile:
+///
+/// ```
+/// # use fenix_renderer::vertex_array::Vertex;
+/// #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Vertex)]
+/// #[repr(C)]
+/// struct MyVertex {
+///     #[location(0)]
+///     pos: glam::Vec3,
+///     #[location(1)]
+///     weights: [f32; 4],
+///     #[location(2)]
+///     uvs: [glam::Vec2; 2],
+/// }
+/// ```
+#[cfg(doctest)]
+#[allow(dead_code)]
+struct CompileFail;
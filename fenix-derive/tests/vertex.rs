@@ -0,0 +1,67 @@
+//! Layouts generated by `#[derive(Vertex)]`.
+
+use bytemuck::{Pod, Zeroable};
+use fenix_renderer::vertex_array::{ComponentType, Vertex, VertexAttribute};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+#[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+#[repr(C)]
+struct MeshVertex {
+    #[location(0)]
+    position: Vec3,
+    #[location(1)]
+    #[normalized]
+    color: [u8; 4],
+    #[location(3)]
+    uv: Vec2,
+}
+
+#[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+#[repr(C)]
+struct Instance {
+    #[location(4)]
+    model: Mat4,
+    #[location(8)]
+    tint: Vec4,
+}
+
+fn describe(attribute: &VertexAttribute) -> (&str, u32, ComponentType, u32, u32, bool, u32) {
+    (
+        attribute.name.as_str(),
+        attribute.location,
+        attribute.component_type,
+        attribute.count,
+        attribute.columns,
+        attribute.normalized,
+        attribute.get_offset(),
+    )
+}
+
+#[test]
+fn fields_become_attributes_in_declaration_order() {
+    let layout = MeshVertex::layout();
+    let attributes: Vec<_> = layout.get_attributes().iter().map(describe).collect();
+    assert_eq!(
+        attributes,
+        [
+            ("position", 0, ComponentType::Float, 3, 1, false, 0),
+            ("color", 1, ComponentType::UnsignedByte, 4, 1, true, 12),
+            ("uv", 3, ComponentType::Float, 2, 1, false, 16),
+        ]
+    );
+    assert_eq!(layout.get_stride() as usize, std::mem::size_of::<MeshVertex>());
+}
+
+#[test]
+fn matrices_take_a_location_per_column() {
+    let layout = Instance::layout();
+    let attributes: Vec<_> = layout.get_attributes().iter().map(describe).collect();
+    assert_eq!(
+        attributes,
+        [
+            ("model", 4, ComponentType::Float, 4, 4, false, 0),
+            ("tint", 8, ComponentType::Float, 4, 1, false, 64),
+        ]
+    );
+    assert_eq!(layout.get_stride() as usize, std::mem::size_of::<Instance>());
+}
@@ -0,0 +1,353 @@
+//! Loading of textures, meshes and shader programs shared through reference-counted
+//! handles.
+//!
+//! ```ignore
+//! let mut assets = AssetManager::new().root("assets");
+//! let texture = assets.load_texture("image/container.jpg", &TextureSpec::default())?;
+//! let same = assets.load_texture("./image/../image/container.jpg", &TextureSpec::default())?;
+//! assert!(texture == same);
+//! ```
+
+use crate::{
+    error::Error,
+    mesh::Mesh,
+    shader::{
+        cache::ProgramCache, preprocessor::Preprocessor, reload::SourceTimes, ShaderProgram,
+        ShaderType,
+    },
+    texture::{Texture, Texture2D, TextureSpec},
+};
+use log::{error, info};
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    collections::HashMap,
+    hash::Hash,
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+};
+
+/// A shared reference to an asset loaded by an [`AssetManager`]. Clones refer to the same
+/// asset, which is freed when the last of them is dropped.
+pub struct Handle<T> {
+    asset: Rc<RefCell<T>>,
+}
+
+impl<T> Handle<T> {
+    /// Borrows the asset.
+    ///
+    /// # Panics
+    ///
+    /// If the asset is mutably borrowed, see [`RefCell::borrow`].
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.asset.borrow()
+    }
+
+    /// Borrows the asset mutably, e.g. to set the uniforms of a program.
+    ///
+    /// # Panics
+    ///
+    /// If the asset is already borrowed, see [`RefCell::borrow_mut`].
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.asset.borrow_mut()
+    }
+
+    /// Returns the number of handles to the asset.
+    pub fn get_count(&self) -> usize {
+        Rc::strong_count(&self.asset)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            asset: Rc::clone(&self.asset),
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.asset, &other.asset)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T: Texture> Texture for Handle<T> {
+    const TARGET: u32 = T::TARGET;
+
+    fn get_id(&self) -> u32 {
+        self.borrow().get_id()
+    }
+
+    fn get_size(&self) -> [u32; 3] {
+        self.borrow().get_size()
+    }
+}
+
+/// Assets of one type indexed by the canonical paths they were loaded from. Only weak
+/// references are kept, so the cache doesn't keep them alive.
+struct Cache<K, T> {
+    assets: HashMap<K, Weak<RefCell<T>>>,
+}
+
+impl<K: Eq + Hash, T> Cache<K, T> {
+    fn new() -> Self {
+        Self {
+            assets: HashMap::new(),
+        }
+    }
+
+    /// Returns the asset loaded with `key`, if it's still alive.
+    fn get(&self, key: &K) -> Option<Handle<T>> {
+        let asset = self.assets.get(key)?.upgrade()?;
+        Some(Handle { asset })
+    }
+
+    fn insert(&mut self, key: K, asset: T) -> Handle<T> {
+        // Entries of dropped assets are only removed here, which is enough to keep the map
+        // from growing with every asset ever loaded.
+        self.assets.retain(|_, asset| asset.strong_count() > 0);
+        let asset = Rc::new(RefCell::new(asset));
+        self.assets.insert(key, Rc::downgrade(&asset));
+        Handle { asset }
+    }
+
+    /// Returns the number of assets that are still alive.
+    fn len(&self) -> usize {
+        self.assets
+            .values()
+            .filter(|asset| asset.strong_count() > 0)
+            .count()
+    }
+}
+
+/// The stages of a program with the canonical paths of their files.
+type ProgramKey = Vec<(ShaderType, PathBuf)>;
+
+/// Loads assets from files found in a list of root directories, and loads each file only
+/// once while any [`Handle`] to it is alive.
+///
+/// Files are identified by their canonical path, so different relative paths to the same
+/// file share the asset. GPU resources are freed as soon as the last handle to the asset
+/// is dropped, and loading the file again afterwards reads it from disk.
+///
+/// Programs are also rebuilt when their files change on disk, see
+/// [`AssetManager::poll`].
+pub struct AssetManager {
+    roots: Vec<PathBuf>,
+    preprocessor: Preprocessor,
+    program_cache: Option<ProgramCache>,
+    textures: Cache<PathBuf, Texture2D>,
+    meshes: Cache<PathBuf, Mesh>,
+    programs: Cache<ProgramKey, ShaderProgram>,
+    program_times: HashMap<ProgramKey, SourceTimes>,
+}
+
+impl Default for AssetManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AssetManager {
+    /// Creates a manager without roots, which resolves relative paths against the working
+    /// directory.
+    pub fn new() -> Self {
+        Self {
+            roots: Vec::new(),
+            preprocessor: Preprocessor::new(),
+            program_cache: None,
+            textures: Cache::new(),
+            meshes: Cache::new(),
+            programs: Cache::new(),
+            program_times: HashMap::new(),
+        }
+    }
+
+    /// Adds a directory where relative paths are looked up. Roots are tried in the order
+    /// they were added and the first one containing the file is used.
+    pub fn root(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.roots.push(dir.into());
+        self
+    }
+
+    /// Sets the preprocessor the shaders of programs are loaded with.
+    pub fn preprocessor(mut self, preprocessor: Preprocessor) -> Self {
+        self.preprocessor = preprocessor;
+        self
+    }
+
+    /// Stores the binaries of linked programs in `cache`, see
+    /// [`ShaderProgram::from_stage_files_cached`].
+    pub fn program_cache(mut self, cache: ProgramCache) -> Self {
+        self.program_cache = Some(cache);
+        self
+    }
+
+    /// Returns the roots where relative paths are looked up.
+    pub fn get_roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Returns the canonical path of an asset file. Absolute paths are only canonicalized,
+    /// relative ones are looked up in the roots.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> Result<PathBuf, Error> {
+        let path = path.as_ref();
+        let resolved = if path.is_absolute() || self.roots.is_empty() {
+            path.canonicalize().ok()
+        } else {
+            self.roots
+                .iter()
+                .find_map(|root| root.join(path).canonicalize().ok())
+        };
+        resolved.ok_or_else(|| Error::MissingAsset {
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Loads an image as a texture created as described by `spec`, see
+    /// [`Texture2D::from_file_with`].
+    ///
+    /// If the texture is already loaded it's returned as is, even if it was created with
+    /// a different spec.
+    pub fn load_texture(
+        &mut self,
+        path: impl AsRef<Path>,
+        spec: &TextureSpec,
+    ) -> Result<Handle<Texture2D>, Error> {
+        let path = self.resolve(path)?;
+        if let Some(texture) = self.textures.get(&path) {
+            return Ok(texture);
+        }
+        let texture = Texture2D::from_file_with(&path.to_string_lossy(), spec)?;
+        Ok(self.textures.insert(path, texture))
+    }
+
+    /// Loads an OBJ file and its materials, see [`Mesh::from_file`].
+    pub fn load_mesh(&mut self, path: impl AsRef<Path>) -> Result<Handle<Mesh>, Error> {
+        let path = self.resolve(path)?;
+        if let Some(mesh) = self.meshes.get(&path) {
+            return Ok(mesh);
+        }
+        let mesh = Mesh::from_file(&path.to_string_lossy())?;
+        Ok(self.meshes.insert(path, mesh))
+    }
+
+    /// Builds a program from any combination of stages, given as `(type, path)` pairs, see
+    /// [`ShaderProgram::from_stage_files`]. Programs made of the same files are shared.
+    pub fn load_program(
+        &mut self,
+        stages: &[(ShaderType, &str)],
+    ) -> Result<Handle<ShaderProgram>, Error> {
+        let key = stages
+            .iter()
+            .map(|&(st, path)| Ok((st, self.resolve(path)?)))
+            .collect::<Result<ProgramKey, Error>>()?;
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(program);
+        }
+
+        let paths: Vec<_> = key.iter().map(|(_, path)| path.to_string_lossy()).collect();
+        let stages: Vec<_> = key
+            .iter()
+            .zip(&paths)
+            .map(|(&(st, _), path)| (st, path.as_ref()))
+            .collect();
+        let program = match &self.program_cache {
+            Some(cache) => {
+                ShaderProgram::from_stage_files_cached(&self.preprocessor, &stages, cache)?
+            },
+            None => ShaderProgram::from_stage_files(&self.preprocessor, &stages)?,
+        };
+
+        self.program_times
+            .insert(key.clone(), SourceTimes::new(&program));
+        let program = self.programs.insert(key, program);
+        let programs = &self.programs;
+        self.program_times
+            .retain(|key, _| programs.get(key).is_some());
+        Ok(program)
+    }
+
+    /// Rebuilds the programs whose files (including the `#include`d ones) changed since
+    /// the last poll, and returns the ones that were successfully replaced.
+    ///
+    /// Like [`ShaderReloader::poll`](crate::shader::reload::ShaderReloader::poll), the
+    /// program is replaced in place so every handle sees the new one, and when it fails
+    /// to rebuild the error is logged and the last working program is kept.
+    pub fn poll(&mut self) -> Vec<Handle<ShaderProgram>> {
+        let mut reloaded = Vec::new();
+        for (key, times) in &mut self.program_times {
+            let program = match self.programs.get(key) {
+                Some(program) => program,
+                None => continue,
+            };
+            if !times.is_outdated() {
+                continue;
+            }
+            let rebuilt = program.borrow().rebuild();
+            match rebuilt {
+                Some(Ok(new_program)) => {
+                    let files: Vec<_> = key.iter().map(|(_, path)| path.display()).collect();
+                    info!("Reloaded shader program {:?}", files);
+                    *times = SourceTimes::new(&new_program);
+                    *program.borrow_mut() = new_program;
+                    reloaded.push(program);
+                },
+                Some(Err(err)) => {
+                    error!("{}", err);
+                    times.update(&program.borrow());
+                },
+                None => {},
+            }
+        }
+        reloaded
+    }
+
+    /// Returns the number of assets that are loaded, i.e. that have any handle alive.
+    pub fn get_loaded(&self) -> usize {
+        self.textures.len() + self.meshes.len() + self.programs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+    }
+
+    #[test]
+    fn resolve_tries_the_roots_in_order() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets");
+        let manager = AssetManager::new().root(&assets).root(fixtures());
+
+        let expected = fixtures().join("bc1_8x8.dds").canonicalize().unwrap();
+        assert_eq!(manager.resolve("bc1_8x8.dds").unwrap(), expected);
+        assert_eq!(
+            manager.resolve("image/../cube.obj").unwrap(),
+            assets.join("cube.obj").canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn resolve_keeps_absolute_paths() {
+        let manager = AssetManager::new().root("does/not/exist");
+        let path = fixtures().join("./bc1_8x8.ktx2");
+        assert_eq!(
+            manager.resolve(&path).unwrap(),
+            path.canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_assets_report_the_requested_path() {
+        let manager = AssetManager::new().root(fixtures());
+        match manager.resolve("image/missing.png") {
+            Err(Error::MissingAsset { path }) => assert_eq!(path, Path::new("image/missing.png")),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
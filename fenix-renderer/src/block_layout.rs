@@ -0,0 +1,281 @@
+//! Memory layout of the data inside uniform and shader storage blocks.
+//!
+//! GLSL interface blocks declared as `std140` or `std430` have well defined padding rules
+//! (see section 7.6.2.2 of the OpenGL 4.5 specification) that usually don't match how
+//! Rust lays out the same struct. Types implementing [`BlockLayout`] know how to write
+//! themselves into a buffer following those rules.
+
+use glam::{
+    DMat2, DMat3, DMat4, DVec2, DVec3, DVec4, IVec2, IVec3, IVec4, Mat2, Mat3, Mat4, UVec2, UVec3,
+    UVec4, Vec2, Vec3, Vec4,
+};
+
+pub use fenix_derive::BlockLayout;
+
+/// The packing rules of an interface block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutRules {
+    /// `layout (std140)`, required for uniform blocks.
+    Std140,
+    /// `layout (std430)`, only available for shader storage blocks. Arrays and structs
+    /// aren't padded to 16 bytes.
+    Std430,
+}
+
+/// A type that can be stored in a `std140` or `std430` block.
+///
+/// Usually implemented with `#[derive(BlockLayout)]`, which lays out the fields of a struct
+/// in declaration order like a GLSL struct.
+///
+/// ```ignore
+/// #[derive(BlockLayout)]
+/// struct Camera {
+///     view: Mat4,
+///     projection: Mat4,
+///     position: Vec3,
+/// }
+/// ```
+pub trait BlockLayout {
+    /// Returns the base alignment in bytes of the type.
+    fn alignment(rules: LayoutRules) -> usize;
+
+    /// Returns the size in bytes the type occupies.
+    fn size(rules: LayoutRules) -> usize;
+
+    /// Writes the value at the start of `out`, which is at least [`BlockLayout::size`]
+    /// bytes long. Padding bytes are left untouched.
+    fn write(&self, rules: LayoutRules, out: &mut [u8]);
+
+    /// Returns the value laid out in a new buffer, with zeroed padding.
+    fn to_bytes(&self, rules: LayoutRules) -> Vec<u8> {
+        let mut bytes = vec![0; Self::size(rules)];
+        self.write(rules, &mut bytes);
+        bytes
+    }
+}
+
+/// Rounds `offset` up to the next multiple of `alignment`.
+pub fn align_to(offset: usize, alignment: usize) -> usize {
+    offset.next_multiple_of(alignment)
+}
+
+/// Returns the base alignment of a struct whose members have the given alignments.
+pub fn struct_alignment(rules: LayoutRules, members: &[usize]) -> usize {
+    let alignment = members.iter().copied().max().unwrap_or(1);
+    match rules {
+        LayoutRules::Std140 => align_to(alignment, 16),
+        LayoutRules::Std430 => alignment,
+    }
+}
+
+/// Returns the distance in bytes between the elements of an array of `T`.
+pub fn array_stride<T: BlockLayout>(rules: LayoutRules) -> usize {
+    align_to(T::size(rules), array_alignment::<T>(rules))
+}
+
+fn array_alignment<T: BlockLayout>(rules: LayoutRules) -> usize {
+    match rules {
+        LayoutRules::Std140 => align_to(T::alignment(rules), 16),
+        LayoutRules::Std430 => T::alignment(rules),
+    }
+}
+
+macro_rules! impl_block_layout {
+    ($($ty:ty => ($alignment:literal, $size:literal)),* $(,)?) => {
+        $(
+            impl BlockLayout for $ty {
+                fn alignment(_: LayoutRules) -> usize {
+                    $alignment
+                }
+
+                fn size(_: LayoutRules) -> usize {
+                    $size
+                }
+
+                fn write(&self, _: LayoutRules, out: &mut [u8]) {
+                    out[..$size].copy_from_slice(bytemuck::bytes_of(self));
+                }
+            }
+        )*
+    };
+}
+
+impl_block_layout! {
+    f32 => (4, 4), i32 => (4, 4), u32 => (4, 4), f64 => (8, 8),
+    Vec2 => (8, 8), Vec3 => (16, 12), Vec4 => (16, 16),
+    IVec2 => (8, 8), IVec3 => (16, 12), IVec4 => (16, 16),
+    UVec2 => (8, 8), UVec3 => (16, 12), UVec4 => (16, 16),
+    DVec2 => (16, 16), DVec3 => (32, 24), DVec4 => (32, 32),
+}
+
+impl BlockLayout for bool {
+    fn alignment(_: LayoutRules) -> usize {
+        4
+    }
+
+    fn size(_: LayoutRules) -> usize {
+        4
+    }
+
+    fn write(&self, _: LayoutRules, out: &mut [u8]) {
+        out[..4].copy_from_slice(&(*self as u32).to_ne_bytes());
+    }
+}
+
+impl<T: BlockLayout, const N: usize> BlockLayout for [T; N] {
+    fn alignment(rules: LayoutRules) -> usize {
+        array_alignment::<T>(rules)
+    }
+
+    fn size(rules: LayoutRules) -> usize {
+        array_stride::<T>(rules) * N
+    }
+
+    fn write(&self, rules: LayoutRules, out: &mut [u8]) {
+        let stride = array_stride::<T>(rules);
+        for (i, element) in self.iter().enumerate() {
+            element.write(rules, &mut out[i * stride..]);
+        }
+    }
+}
+
+// A column-major matrix is laid out exactly like an array of its columns.
+macro_rules! impl_block_layout_matrix {
+    ($($ty:ty => [$column:ty; $count:literal]),* $(,)?) => {
+        $(
+            impl BlockLayout for $ty {
+                fn alignment(rules: LayoutRules) -> usize {
+                    <[$column; $count]>::alignment(rules)
+                }
+
+                fn size(rules: LayoutRules) -> usize {
+                    <[$column; $count]>::size(rules)
+                }
+
+                fn write(&self, rules: LayoutRules, out: &mut [u8]) {
+                    let columns: [$column; $count] =
+                        std::array::from_fn(|i| self.col(i));
+                    columns.write(rules, out);
+                }
+            }
+        )*
+    };
+}
+
+impl_block_layout_matrix! {
+    Mat2 => [Vec2; 2], Mat3 => [Vec3; 3], Mat4 => [Vec4; 4],
+    DMat2 => [DVec2; 2], DMat3 => [DVec3; 3], DMat4 => [DVec4; 4],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(BlockLayout)]
+    struct Light {
+        position: Vec3,
+        intensity: f32,
+        color: Vec3,
+    }
+
+    #[derive(BlockLayout)]
+    struct Scene {
+        ambient: f32,
+        lights: [Light; 2],
+        weights: [f32; 3],
+        normal_matrix: Mat3,
+        enabled: bool,
+    }
+
+    #[test]
+    fn vec3_is_followed_by_a_scalar() {
+        // `intensity` fills the padding after `position`, `color` starts a new 16 bytes.
+        assert_eq!(Light::alignment(LayoutRules::Std140), 16);
+        assert_eq!(Light::size(LayoutRules::Std140), 32);
+        assert_eq!(Light::size(LayoutRules::Std430), 32);
+
+        let light = Light {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            intensity: 4.0,
+            color: Vec3::new(5.0, 6.0, 7.0),
+        };
+        let bytes = light.to_bytes(LayoutRules::Std140);
+        let floats: &[f32] = bytemuck::cast_slice(&bytes);
+        assert_eq!(floats, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 0.0]);
+    }
+
+    #[test]
+    fn std140_pads_array_elements_to_16_bytes() {
+        assert_eq!(array_stride::<f32>(LayoutRules::Std140), 16);
+        assert_eq!(<[f32; 3]>::size(LayoutRules::Std140), 48);
+        assert_eq!(array_stride::<Vec2>(LayoutRules::Std140), 16);
+        assert_eq!(array_stride::<Vec3>(LayoutRules::Std140), 16);
+    }
+
+    #[test]
+    fn std430_packs_arrays() {
+        assert_eq!(array_stride::<f32>(LayoutRules::Std430), 4);
+        assert_eq!(<[f32; 3]>::size(LayoutRules::Std430), 12);
+        assert_eq!(array_stride::<Vec2>(LayoutRules::Std430), 8);
+        // A vec3 is still aligned like a vec4.
+        assert_eq!(array_stride::<Vec3>(LayoutRules::Std430), 16);
+    }
+
+    #[test]
+    fn matrices_are_arrays_of_columns() {
+        assert_eq!(Mat4::size(LayoutRules::Std140), 64);
+        assert_eq!(Mat3::size(LayoutRules::Std140), 48);
+        assert_eq!(Mat2::size(LayoutRules::Std140), 32);
+        assert_eq!(Mat2::size(LayoutRules::Std430), 16);
+        assert_eq!(DMat3::size(LayoutRules::Std140), 96);
+
+        let bytes = Mat3::from_cols_array(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0])
+            .to_bytes(LayoutRules::Std140);
+        let floats: &[f32] = bytemuck::cast_slice(&bytes);
+        assert_eq!(
+            floats,
+            [1.0, 2.0, 3.0, 0.0, 4.0, 5.0, 6.0, 0.0, 7.0, 8.0, 9.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn nested_struct_layout() {
+        // ambient: 0, lights: 16 (2 * 32), weights: 80 (3 * 16), normal_matrix: 128 (48),
+        // enabled: 176, rounded up to the 16 bytes alignment of the struct.
+        assert_eq!(Scene::size(LayoutRules::Std140), 192);
+        // ambient: 0, lights: 16 (2 * 32), weights: 80 (3 * 4), normal_matrix: 96 (48),
+        // enabled: 144.
+        assert_eq!(Scene::size(LayoutRules::Std430), 160);
+
+        let scene = Scene {
+            ambient: 0.5,
+            lights: [
+                Light {
+                    position: Vec3::ZERO,
+                    intensity: 1.0,
+                    color: Vec3::ONE,
+                },
+                Light {
+                    position: Vec3::ONE,
+                    intensity: 2.0,
+                    color: Vec3::ZERO,
+                },
+            ],
+            weights: [0.25, 0.5, 0.75],
+            normal_matrix: Mat3::IDENTITY,
+            enabled: true,
+        };
+        let bytes = scene.to_bytes(LayoutRules::Std140);
+        let read =
+            |offset: usize| f32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(read(0), 0.5);
+        assert_eq!(read(16 + 12), 1.0);
+        assert_eq!(read(48 + 12), 2.0);
+        assert_eq!(read(80), 0.25);
+        assert_eq!(read(96), 0.5);
+        assert_eq!(read(112), 0.75);
+        assert_eq!(read(128), 1.0);
+        assert_eq!(read(128 + 20), 1.0);
+        assert_eq!(u32::from_ne_bytes(bytes[176..180].try_into().unwrap()), 1);
+    }
+}
@@ -1,17 +1,108 @@
 //! TODO: Add module documentation when this project grows.
 
+use crate::block_layout::{self, BlockLayout, LayoutRules};
+use crate::vertex_array::Vertex;
 use std::ops::Drop;
-use std::{ffi::c_void, mem, ptr};
+use std::{ffi::c_void, marker::PhantomData, mem, ops::BitOr, ptr};
 
 /// Struct representing a [Buffer Object](https://www.khronos.org/opengl/wiki/Buffer_Object)
 /// that stores vertex data.
 pub struct VertexBuffer {
     id: u32,
+    size: usize,
+    ring: Option<RingBuffer>,
+}
+
+/// State of a buffer in streaming mode.
+///
+/// The buffer is split in sections that are written in turns. Each section is protected
+/// by a fence so the CPU never overwrites data the GPU is still reading.
+struct RingBuffer {
+    ptr: *mut u8,
+    section_size: usize,
+    fences: Vec<gl::types::GLsync>,
+    current: usize,
+}
+
+impl RingBuffer {
+    /// Creates a persistently mapped buffer of `section_count` sections of `section_size`
+    /// bytes each, returning its id.
+    fn new(section_size: u32, section_count: u32) -> (u32, Self) {
+        assert!(section_count > 0, "A streaming buffer needs at least one section");
+        let size = section_size as usize * section_count as usize;
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+        let mut id = 0;
+        let ptr = unsafe {
+            gl::CreateBuffers(1, &mut id);
+            gl::NamedBufferStorage(id, size as isize, ptr::null(), flags);
+            gl::MapNamedBufferRange(id, 0, size as isize, flags) as *mut u8
+        };
+        assert!(!ptr.is_null(), "Failed to map streaming buffer");
+        let ring = Self {
+            ptr,
+            section_size: section_size as usize,
+            fences: vec![ptr::null(); section_count as usize],
+            current: 0,
+        };
+        (id, ring)
+    }
+
+    /// Copies `bytes` to the next section, waiting for the GPU to finish reading it first,
+    /// and returns the offset of the section.
+    fn write(&mut self, bytes: &[u8]) -> usize {
+        assert!(
+            bytes.len() <= self.section_size,
+            "Streaming {} bytes overflows a section of {} bytes",
+            bytes.len(),
+            self.section_size
+        );
+
+        self.current = (self.current + 1) % self.fences.len();
+        let fence = mem::replace(&mut self.fences[self.current], ptr::null());
+        if !fence.is_null() {
+            unsafe {
+                let mut flags = 0;
+                while gl::ClientWaitSync(fence, flags, 1_000_000) == gl::TIMEOUT_EXPIRED {
+                    flags = gl::SYNC_FLUSH_COMMANDS_BIT;
+                }
+                gl::DeleteSync(fence);
+            }
+        }
+
+        let offset = self.current * self.section_size;
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.ptr.add(offset), bytes.len());
+        }
+        offset
+    }
+
+    /// Protects the section last written until the commands issued so far complete.
+    fn fence(&mut self) {
+        let fence = &mut self.fences[self.current];
+        unsafe {
+            if !fence.is_null() {
+                gl::DeleteSync(*fence);
+            }
+            *fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+        }
+    }
+
+    /// Deletes the fences and unmaps the buffer `id`, which must be the one it was created
+    /// with.
+    fn release(&mut self, id: u32) {
+        unsafe {
+            for fence in self.fences.drain(..).filter(|fence| !fence.is_null()) {
+                gl::DeleteSync(fence);
+            }
+            gl::UnmapNamedBuffer(id);
+        }
+    }
 }
 
 impl VertexBuffer {
-    /// Creates a new vertex buffer and fills it with the given data.
-    pub fn from(data: &[f32]) -> Self {
+    /// Creates a new vertex buffer and fills it with the given vertices.
+    pub fn from<T: Vertex>(data: &[T]) -> Self {
+        let bytes = T::as_bytes(data);
         let mut id = 0;
         unsafe {
             // NOTE(Miguel): We use DSA https://www.khronos.org/opengl/wiki/Direct_State_Access
@@ -19,33 +110,108 @@ impl VertexBuffer {
             gl::CreateBuffers(1, &mut id);
             gl::NamedBufferData(
                 id,
-                mem::size_of_val(data) as isize,
-                data.as_ptr() as *const c_void,
+                bytes.len() as isize,
+                bytes.as_ptr() as *const c_void,
                 gl::STATIC_DRAW,
             );
         }
-        Self { id }
+        Self {
+            id,
+            size: bytes.len(),
+            ring: None,
+        }
     }
 
     /// Creates a new vertex buffer of the specified size (in bytes).
+    ///
+    /// The contents of the buffer are undefined until they are written with
+    /// [`VertexBuffer::set_data`].
     pub fn with_size(size: u32) -> Self {
         let mut id = 0;
         unsafe {
-            gl::GenBuffers(1, &mut id);
-            gl::BindBuffer(gl::ARRAY_BUFFER, id);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                size as isize,
-                ptr::null(),
-                gl::DYNAMIC_DRAW,
+            gl::CreateBuffers(1, &mut id);
+            gl::NamedBufferData(id, size as isize, ptr::null(), gl::DYNAMIC_DRAW);
+        }
+        Self {
+            id,
+            size: size as usize,
+            ring: None,
+        }
+    }
+
+    /// Creates a vertex buffer in streaming mode, meant for geometry that changes every
+    /// frame.
+    ///
+    /// The buffer holds `section_count` sections of `section_size` bytes each and stays
+    /// persistently mapped, see [`VertexBuffer::stream`]. Three sections are usually
+    /// enough to never wait on the GPU.
+    pub fn streaming(section_size: u32, section_count: u32) -> Self {
+        let (id, ring) = RingBuffer::new(section_size, section_count);
+        Self {
+            id,
+            size: section_size as usize * section_count as usize,
+            ring: Some(ring),
+        }
+    }
+
+    /// Overwrites part of the buffer with the given vertices, starting `offset` bytes from
+    /// the beginning of the buffer.
+    ///
+    /// Panics if the data doesn't fit in the buffer or if the buffer is in streaming mode.
+    pub fn set_data<T: Vertex>(&mut self, offset: usize, data: &[T]) {
+        assert!(
+            self.ring.is_none(),
+            "Streaming vertex buffers must be updated with `stream`"
+        );
+        let bytes = T::as_bytes(data);
+        assert!(
+            offset + bytes.len() <= self.size,
+            "Writing {} bytes at offset {} overflows a vertex buffer of {} bytes",
+            bytes.len(),
+            offset,
+            self.size
+        );
+        unsafe {
+            gl::NamedBufferSubData(
+                self.id,
+                offset as isize,
+                bytes.len() as isize,
+                bytes.as_ptr() as *const c_void,
             );
         }
-        Self { id }
     }
 
-    /// Fills the buffer with vertex data.
-    pub fn set_data() {
-        todo!();
+    /// Writes the given vertices to the next section of a streaming buffer and returns the
+    /// offset (in bytes) of that section.
+    ///
+    /// If the GPU hasn't finished reading the section yet this call blocks until it does.
+    /// Call [`VertexBuffer::fence`] after issuing the draw calls that read the vertices.
+    ///
+    /// Panics if the buffer isn't in streaming mode or if the data doesn't fit in a section.
+    pub fn stream<T: Vertex>(&mut self, data: &[T]) -> usize {
+        let ring = self
+            .ring
+            .as_mut()
+            .expect("Only streaming vertex buffers can be updated with `stream`");
+        ring.write(T::as_bytes(data))
+    }
+
+    /// Protects the section last written with [`VertexBuffer::stream`] until the commands
+    /// issued so far complete.
+    pub fn fence(&mut self) {
+        if let Some(ring) = self.ring.as_mut() {
+            ring.fence();
+        }
+    }
+
+    /// Returns the size of the buffer in bytes.
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the internal id of the buffer used by OpenGL.
+    pub fn get_id(&self) -> u32 {
+        self.id
     }
 
     /// Binds this buffer to the target `ARRAY_BUFFER`.
@@ -61,6 +227,9 @@ impl VertexBuffer {
 
 impl Drop for VertexBuffer {
     fn drop(&mut self) {
+        if let Some(ring) = self.ring.as_mut() {
+            ring.release(self.id);
+        }
         unsafe { gl::DeleteBuffers(1, &self.id) };
     }
 }
@@ -93,6 +262,11 @@ impl IndexBuffer {
         self.count
     }
 
+    /// Returns the internal id of the buffer used by OpenGL.
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
     /// Binds this buffer to the target `ELEMENT_ARRAY_BUFFER`.
     pub fn bind(&self) {
         unsafe { gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.id) };
@@ -108,4 +282,291 @@ impl Drop for IndexBuffer {
     fn drop(&mut self) {
         unsafe { gl::DeleteBuffers(1, &self.id) };
     }
-}
\ No newline at end of file
+}
+
+/// Struct representing a [Buffer Object](https://www.khronos.org/opengl/wiki/Buffer_Object)
+/// that backs a `std140` uniform block holding a `T`.
+///
+/// The same buffer can be bound to a binding point shared by several programs, see
+/// [`ShaderProgram::bind_uniform_block`](crate::shader::ShaderProgram::bind_uniform_block).
+pub struct UniformBuffer<T: BlockLayout> {
+    id: u32,
+    data: Vec<u8>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: BlockLayout> UniformBuffer<T> {
+    /// Creates a new uniform buffer holding `value`.
+    pub fn new(value: &T) -> Self {
+        let data = value.to_bytes(LayoutRules::Std140);
+        let mut id = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut id);
+            gl::NamedBufferData(
+                id,
+                data.len() as isize,
+                data.as_ptr() as *const c_void,
+                gl::DYNAMIC_DRAW,
+            );
+        }
+        Self {
+            id,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Replaces the contents of the buffer with `value`.
+    pub fn set(&mut self, value: &T) {
+        value.write(LayoutRules::Std140, &mut self.data);
+        unsafe {
+            gl::NamedBufferSubData(
+                self.id,
+                0,
+                self.data.len() as isize,
+                self.data.as_ptr() as *const c_void,
+            );
+        }
+    }
+
+    /// Binds the buffer to the uniform buffer binding point `binding`.
+    pub fn bind(&self, binding: u32) {
+        unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.id) };
+    }
+
+    /// Returns the size of the buffer in bytes.
+    pub fn get_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the internal id of the buffer used by OpenGL.
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl<T: BlockLayout> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.id) };
+    }
+}
+
+/// Struct representing a [Buffer Object](https://www.khronos.org/opengl/wiki/Buffer_Object)
+/// that backs a `std430` shader storage block holding an array of `T`.
+///
+/// Meant for data written by shaders, e.g. particles simulated by a
+/// [`ComputeProgram`](crate::shader::compute::ComputeProgram). Call [`memory_barrier`]
+/// between a dispatch that writes the buffer and the commands that read it.
+pub struct StorageBuffer<T: BlockLayout> {
+    id: u32,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: BlockLayout> StorageBuffer<T> {
+    /// Creates a new storage buffer holding `data`.
+    pub fn from(data: &[T]) -> Self {
+        let mut buffer = Self::with_len(data.len());
+        buffer.set_data(0, data);
+        buffer
+    }
+
+    /// Creates a new storage buffer with room for `len` elements, all zeroed.
+    pub fn with_len(len: usize) -> Self {
+        let size = Self::get_stride() * len;
+        let zeroes = vec![0_u8; size];
+        let mut id = 0;
+        unsafe {
+            gl::CreateBuffers(1, &mut id);
+            gl::NamedBufferData(
+                id,
+                size as isize,
+                zeroes.as_ptr() as *const c_void,
+                gl::DYNAMIC_COPY,
+            );
+        }
+        Self {
+            id,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Overwrites the elements starting at `index` with `data`.
+    ///
+    /// Panics if the data doesn't fit in the buffer.
+    pub fn set_data(&mut self, index: usize, data: &[T]) {
+        assert!(
+            index + data.len() <= self.len,
+            "Writing {} elements at index {} overflows a storage buffer of {} elements",
+            data.len(),
+            index,
+            self.len
+        );
+        let stride = Self::get_stride();
+        let mut bytes = vec![0; stride * data.len()];
+        for (i, element) in data.iter().enumerate() {
+            element.write(LayoutRules::Std430, &mut bytes[i * stride..]);
+        }
+        unsafe {
+            gl::NamedBufferSubData(
+                self.id,
+                (index * stride) as isize,
+                bytes.len() as isize,
+                bytes.as_ptr() as *const c_void,
+            );
+        }
+    }
+
+    /// Reads the whole contents of the buffer back to the CPU, laid out following the
+    /// `std430` rules.
+    ///
+    /// This stalls until the GPU finishes writing the buffer, so use it sparingly.
+    pub fn read_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0_u8; self.get_size()];
+        unsafe {
+            gl::GetNamedBufferSubData(
+                self.id,
+                0,
+                bytes.len() as isize,
+                bytes.as_mut_ptr() as *mut c_void,
+            );
+        }
+        bytes
+    }
+
+    /// Binds the buffer to the shader storage buffer binding point `binding`.
+    pub fn bind(&self, binding: u32) {
+        unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.id) };
+    }
+
+    /// Returns the number of elements in the buffer.
+    pub fn get_len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the distance in bytes between two elements of the buffer.
+    pub fn get_stride() -> usize {
+        block_layout::array_stride::<T>(LayoutRules::Std430)
+    }
+
+    /// Returns the size of the buffer in bytes.
+    pub fn get_size(&self) -> usize {
+        Self::get_stride() * self.len
+    }
+
+    /// Returns the internal id of the buffer used by OpenGL.
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl<T: BlockLayout> Drop for StorageBuffer<T> {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.id) };
+    }
+}
+
+/// Struct representing a persistently mapped [Pixel Buffer Object](https://www.khronos.org/opengl/wiki/Pixel_Buffer_Object)
+/// used to stream pixels to textures.
+///
+/// Like streaming vertex buffers, it's split in sections that are written in turns, so
+/// copying pixels to it doesn't wait for earlier uploads to finish.
+pub struct PixelUnpackBuffer {
+    id: u32,
+    ring: RingBuffer,
+}
+
+impl PixelUnpackBuffer {
+    /// Creates a buffer of `section_count` sections of `section_size` bytes each.
+    pub fn new(section_size: u32, section_count: u32) -> Self {
+        let (id, ring) = RingBuffer::new(section_size, section_count);
+        Self { id, ring }
+    }
+
+    /// Writes the given pixels to the next section and returns the offset (in bytes) of
+    /// that section, to be used as the pixel pointer of `glTextureSubImage*` while the
+    /// buffer is bound.
+    ///
+    /// If the GPU hasn't finished reading the section yet this call blocks until it does.
+    /// Call [`PixelUnpackBuffer::fence`] after issuing the uploads that read the pixels.
+    ///
+    /// Panics if the data doesn't fit in a section.
+    pub fn stream(&mut self, data: &[u8]) -> usize {
+        self.ring.write(data)
+    }
+
+    /// Protects the section last written with [`PixelUnpackBuffer::stream`] until the
+    /// commands issued so far complete.
+    pub fn fence(&mut self) {
+        self.ring.fence();
+    }
+
+    /// Returns the size of each section in bytes.
+    pub fn get_section_size(&self) -> usize {
+        self.ring.section_size
+    }
+
+    /// Returns the internal id of the buffer used by OpenGL.
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// Binds this buffer to the target `PIXEL_UNPACK_BUFFER`.
+    pub fn bind(&self) {
+        unsafe { gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, self.id) };
+    }
+
+    /// Unbinds any buffer bound to the `PIXEL_UNPACK_BUFFER` target.
+    pub fn unbind() {
+        unsafe { gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0) };
+    }
+}
+
+impl Drop for PixelUnpackBuffer {
+    fn drop(&mut self) {
+        self.ring.release(self.id);
+        unsafe { gl::DeleteBuffers(1, &self.id) };
+    }
+}
+
+/// The ways memory written by shaders can be read afterwards, see [`memory_barrier`].
+///
+/// Barriers can be combined with `|`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Barrier(u32);
+
+impl Barrier {
+    /// Vertex data read from vertex buffers.
+    pub const VERTEX_ATTRIB_ARRAY: Self = Self(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    /// Indices read from index buffers.
+    pub const ELEMENT_ARRAY: Self = Self(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    /// Data read from uniform buffers.
+    pub const UNIFORM: Self = Self(gl::UNIFORM_BARRIER_BIT);
+    /// Textures sampled by shaders.
+    pub const TEXTURE_FETCH: Self = Self(gl::TEXTURE_FETCH_BARRIER_BIT);
+    /// Images loaded and stored by shaders.
+    pub const SHADER_IMAGE_ACCESS: Self = Self(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    /// Draw and dispatch parameters read from indirect buffers.
+    pub const COMMAND: Self = Self(gl::COMMAND_BARRIER_BIT);
+    /// Buffers read back or copied by the CPU.
+    pub const BUFFER_UPDATE: Self = Self(gl::BUFFER_UPDATE_BARRIER_BIT);
+    /// Shader storage buffers accessed by shaders.
+    pub const SHADER_STORAGE: Self = Self(gl::SHADER_STORAGE_BARRIER_BIT);
+    /// Every kind of access.
+    pub const ALL: Self = Self(gl::ALL_BARRIER_BITS);
+}
+
+impl BitOr for Barrier {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Makes the memory written by previous shader invocations visible to later accesses of
+/// the given kinds.
+pub fn memory_barrier(barrier: Barrier) {
+    unsafe { gl::MemoryBarrier(barrier.0) };
+}
@@ -0,0 +1,140 @@
+//! Error type shared by the whole renderer.
+
+use crate::{
+    mesh::ParseError,
+    shader::{diagnostic::Diagnostic, ShaderType},
+};
+use image::{error::ImageError, ColorType};
+use std::{error, fmt, io, path::PathBuf};
+
+/// The errors that can happen while creating renderer resources.
+#[derive(Debug)]
+pub enum Error {
+    /// A file couldn't be read.
+    Io { path: PathBuf, source: io::Error },
+    /// A file or directory couldn't be created or written.
+    Write { path: PathBuf, source: io::Error },
+    /// A shader couldn't be preprocessed, e.g. because of an include cycle. `line` is the
+    /// line of `path` with the offending directive.
+    Preprocess {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// A shader failed to compile. `diagnostics` is the parsed info log reported by the
+    /// driver and `report` those same diagnostics rendered next to the offending source
+    /// lines.
+    Compile {
+        stage: ShaderType,
+        diagnostics: Vec<Diagnostic>,
+        report: String,
+    },
+    /// A SPIR-V module was rejected before reaching the driver.
+    InvalidSpirv { stage: ShaderType, message: String },
+    /// A program failed to link. `log` is the info log reported by the driver.
+    Link { log: String },
+    /// A program isn't linked or lacks a shader stage it's expected to have, e.g. a
+    /// compute shader.
+    MissingStage { stage: ShaderType },
+    /// A uniform isn't active in the program, either because it doesn't exist or because
+    /// the compiler optimized it away.
+    MissingUniform { name: String },
+    /// A uniform or shader storage block isn't active in the program.
+    MissingBlock { name: String },
+    /// An image was decoded but its color format can't be uploaded to a texture.
+    UnsupportedImageFormat {
+        path: PathBuf,
+        color_type: ColorType,
+    },
+    /// A texture file is malformed (e.g. a truncated KTX2 container) or the images of a
+    /// texture with several of them (e.g. the faces of a cube map) don't fit together.
+    Texture { path: PathBuf, message: String },
+    /// An image couldn't be decoded.
+    Image { path: PathBuf, source: ImageError },
+    /// An image couldn't be encoded or written.
+    SaveImage { path: PathBuf, source: ImageError },
+    /// An OBJ or MTL file has a syntax error.
+    Parse { path: PathBuf, source: ParseError },
+    /// An asset file wasn't found in any of the roots of an
+    /// [`AssetManager`](crate::asset::AssetManager).
+    MissingAsset { path: PathBuf },
+    /// An OpenGL context couldn't be created.
+    Context { message: String },
+    /// A framebuffer spec exceeds the limits of the driver or the resulting framebuffer
+    /// isn't complete.
+    Framebuffer { message: String },
+    /// OpenGL failed to create an object.
+    Allocation { object: &'static str },
+    /// The OpenGL driver lacks a feature, e.g. an extension that isn't available.
+    Unsupported { feature: &'static str },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => {
+                write!(f, "Failed to read file {}: {}", path.display(), source)
+            },
+            Error::Write { path, source } => {
+                write!(f, "Failed to write {}: {}", path.display(), source)
+            },
+            Error::Preprocess { path, line, message } => {
+                write!(f, "{}:{}: {}", path.display(), line, message)
+            },
+            Error::Compile { stage, report, .. } => {
+                write!(f, "Failed to compile {} shader:\n{}", stage, report)
+            },
+            Error::InvalidSpirv { stage, message } => {
+                write!(f, "Invalid SPIR-V module for {} shader: {}", stage, message)
+            },
+            Error::Link { log } => write!(f, "Failed to link PROGRAM:\n{}", log),
+            Error::MissingStage { stage } => {
+                write!(f, "Program isn't linked with a {} shader", stage)
+            },
+            Error::MissingUniform { name } => {
+                write!(f, "Failed to get location of uniform '{}'", name)
+            },
+            Error::MissingBlock { name } => write!(f, "Failed to find block '{}'", name),
+            Error::UnsupportedImageFormat { path, color_type } => write!(
+                f,
+                "Image {} has an unsupported color format ({:?})",
+                path.display(),
+                color_type
+            ),
+            Error::Texture { path, message } => {
+                write!(f, "Invalid texture image {}: {}", path.display(), message)
+            },
+            Error::Image { path, source } => {
+                write!(f, "Failed to load image {}: {}", path.display(), source)
+            },
+            Error::SaveImage { path, source } => {
+                write!(f, "Failed to save image {}: {}", path.display(), source)
+            },
+            Error::Parse { path, source } => write!(f, "{}:{}", path.display(), source),
+            Error::MissingAsset { path } => {
+                write!(f, "Asset {} wasn't found in any asset root", path.display())
+            },
+            Error::Context { message } => {
+                write!(f, "Failed to create OpenGL context: {}", message)
+            },
+            Error::Framebuffer { message } => write!(f, "Invalid framebuffer: {}", message),
+            Error::Allocation { object } => write!(f, "Couldn't allocate a new {}", object),
+            Error::Unsupported { feature } => {
+                write!(f, "{} aren't supported by the OpenGL driver", feature)
+            },
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Write { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::SaveImage { source, .. } => Some(source),
+            Error::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,654 @@
+//! Offscreen render targets.
+
+use crate::{error::Error, texture::flip_rows};
+use image::RgbaImage;
+use std::{
+    ffi::c_void,
+    fs,
+    ops::BitOr,
+    path::{Path, PathBuf},
+};
+
+/// Storage format of a framebuffer attachment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum AttachmentFormat {
+    Rgba8 = gl::RGBA8,
+    Rgba16F = gl::RGBA16F,
+    Rgba32F = gl::RGBA32F,
+    Rg16F = gl::RG16F,
+    /// Single integer channel, e.g. for object ids used in mouse picking.
+    R32I = gl::R32I,
+    Depth24 = gl::DEPTH_COMPONENT24,
+    Depth32F = gl::DEPTH_COMPONENT32F,
+    Depth24Stencil8 = gl::DEPTH24_STENCIL8,
+}
+
+impl AttachmentFormat {
+    /// Checks if the format stores depth (and maybe stencil) instead of color.
+    pub fn is_depth(self) -> bool {
+        matches!(
+            self,
+            AttachmentFormat::Depth24
+                | AttachmentFormat::Depth32F
+                | AttachmentFormat::Depth24Stencil8
+        )
+    }
+
+    /// Checks if the format stores integers, which are never filtered.
+    pub fn is_integer(self) -> bool {
+        self == AttachmentFormat::R32I
+    }
+
+    fn attachment_point(self) -> u32 {
+        match self {
+            AttachmentFormat::Depth24Stencil8 => gl::DEPTH_STENCIL_ATTACHMENT,
+            AttachmentFormat::Depth24 | AttachmentFormat::Depth32F => gl::DEPTH_ATTACHMENT,
+            _ => gl::COLOR_ATTACHMENT0,
+        }
+    }
+}
+
+/// Description of a single attachment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttachmentSpec {
+    pub format: AttachmentFormat,
+    /// Whether the attachment is a texture that can be sampled by shaders or a write-only
+    /// renderbuffer, which some drivers render to faster.
+    pub sampled: bool,
+}
+
+impl AttachmentSpec {
+    /// An attachment backed by a texture.
+    pub fn texture(format: AttachmentFormat) -> Self {
+        Self {
+            format,
+            sampled: true,
+        }
+    }
+
+    /// An attachment backed by a renderbuffer.
+    pub fn renderbuffer(format: AttachmentFormat) -> Self {
+        Self {
+            format,
+            sampled: false,
+        }
+    }
+}
+
+/// Description of a [`Framebuffer`].
+///
+/// ```ignore
+/// let spec = FramebufferSpec::new(1280, 720)
+///     .samples(4)
+///     .color(AttachmentSpec::texture(AttachmentFormat::Rgba16F))
+///     .depth(AttachmentSpec::renderbuffer(AttachmentFormat::Depth24Stencil8));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FramebufferSpec {
+    pub width: u32,
+    pub height: u32,
+    /// Number of MSAA samples, 1 disables multisampling.
+    pub samples: u32,
+    /// Color attachments, in `layout (location = N) out` order.
+    pub color: Vec<AttachmentSpec>,
+    pub depth: Option<AttachmentSpec>,
+}
+
+impl FramebufferSpec {
+    /// Creates a spec without attachments nor multisampling.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            samples: 1,
+            color: Vec::new(),
+            depth: None,
+        }
+    }
+
+    /// Sets the number of MSAA samples.
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Adds a color attachment.
+    pub fn color(mut self, attachment: AttachmentSpec) -> Self {
+        self.color.push(attachment);
+        self
+    }
+
+    /// Sets the depth (or depth-stencil) attachment.
+    pub fn depth(mut self, attachment: AttachmentSpec) -> Self {
+        self.depth = Some(attachment);
+        self
+    }
+}
+
+/// The buffers copied by [`Framebuffer::blit_to`]. Can be combined with `|`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlitMask(u32);
+
+impl BlitMask {
+    pub const COLOR: Self = Self(gl::COLOR_BUFFER_BIT);
+    pub const DEPTH: Self = Self(gl::DEPTH_BUFFER_BIT);
+    pub const STENCIL: Self = Self(gl::STENCIL_BUFFER_BIT);
+}
+
+impl BitOr for BlitMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Attachment {
+    Texture(u32),
+    Renderbuffer(u32),
+}
+
+impl Attachment {
+    fn create(spec: &AttachmentSpec, width: u32, height: u32, samples: u32) -> Self {
+        let (width, height) = (width as i32, height as i32);
+        let format = spec.format as u32;
+        let mut id = 0;
+        unsafe {
+            if spec.sampled {
+                if samples > 1 {
+                    gl::CreateTextures(gl::TEXTURE_2D_MULTISAMPLE, 1, &mut id);
+                    gl::TextureStorage2DMultisample(
+                        id,
+                        samples as i32,
+                        format,
+                        width,
+                        height,
+                        gl::TRUE,
+                    );
+                } else {
+                    gl::CreateTextures(gl::TEXTURE_2D, 1, &mut id);
+                    gl::TextureStorage2D(id, 1, format, width, height);
+                    let filter = if spec.format == AttachmentFormat::R32I {
+                        gl::NEAREST
+                    } else {
+                        gl::LINEAR
+                    };
+                    gl::TextureParameteri(id, gl::TEXTURE_MIN_FILTER, filter as i32);
+                    gl::TextureParameteri(id, gl::TEXTURE_MAG_FILTER, filter as i32);
+                    gl::TextureParameteri(id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                    gl::TextureParameteri(id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                }
+                Attachment::Texture(id)
+            } else {
+                gl::CreateRenderbuffers(1, &mut id);
+                if samples > 1 {
+                    gl::NamedRenderbufferStorageMultisample(
+                        id,
+                        samples as i32,
+                        format,
+                        width,
+                        height,
+                    );
+                } else {
+                    gl::NamedRenderbufferStorage(id, format, width, height);
+                }
+                Attachment::Renderbuffer(id)
+            }
+        }
+    }
+
+    fn attach(self, framebuffer: u32, point: u32) {
+        unsafe {
+            match self {
+                Attachment::Texture(id) => gl::NamedFramebufferTexture(framebuffer, point, id, 0),
+                Attachment::Renderbuffer(id) => {
+                    gl::NamedFramebufferRenderbuffer(framebuffer, point, gl::RENDERBUFFER, id)
+                },
+            }
+        }
+    }
+
+    fn delete(self) {
+        unsafe {
+            match self {
+                Attachment::Texture(id) => gl::DeleteTextures(1, &id),
+                Attachment::Renderbuffer(id) => gl::DeleteRenderbuffers(1, &id),
+            }
+        }
+    }
+}
+
+/// Wrapper for a [Framebuffer Object](https://www.khronos.org/opengl/wiki/Framebuffer_Object)
+/// that owns its attachments.
+pub struct Framebuffer {
+    id: u32,
+    spec: FramebufferSpec,
+    color_attachments: Vec<Attachment>,
+    depth_attachment: Option<Attachment>,
+}
+
+impl Framebuffer {
+    /// Creates a framebuffer and its attachments as described by `spec`.
+    pub fn new(spec: FramebufferSpec) -> Result<Self, Error> {
+        validate(&spec)?;
+        let mut id = 0;
+        unsafe { gl::CreateFramebuffers(1, &mut id) };
+        if id == 0 {
+            return Err(Error::Allocation {
+                object: "framebuffer",
+            });
+        }
+        let mut framebuffer = Self {
+            id,
+            spec,
+            color_attachments: Vec::new(),
+            depth_attachment: None,
+        };
+        framebuffer.create_attachments()?;
+        Ok(framebuffer)
+    }
+
+    /// Recreates the attachments with a new size. Their contents are lost.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if (width, height) == (self.spec.width, self.spec.height) {
+            return Ok(());
+        }
+        let mut spec = self.spec.clone();
+        spec.width = width;
+        spec.height = height;
+        validate(&spec)?;
+        self.spec = spec;
+        self.delete_attachments();
+        self.create_attachments()
+    }
+
+    fn create_attachments(&mut self) -> Result<(), Error> {
+        let FramebufferSpec {
+            width,
+            height,
+            samples,
+            ..
+        } = self.spec;
+        for (i, spec) in self.spec.color.iter().enumerate() {
+            let attachment = Attachment::create(spec, width, height, samples);
+            attachment.attach(self.id, gl::COLOR_ATTACHMENT0 + i as u32);
+            self.color_attachments.push(attachment);
+        }
+        if let Some(spec) = &self.spec.depth {
+            let attachment = Attachment::create(spec, width, height, samples);
+            attachment.attach(self.id, spec.format.attachment_point());
+            self.depth_attachment = Some(attachment);
+        }
+
+        unsafe {
+            if self.color_attachments.is_empty() {
+                gl::NamedFramebufferDrawBuffer(self.id, gl::NONE);
+                gl::NamedFramebufferReadBuffer(self.id, gl::NONE);
+            } else {
+                let draw_buffers: Vec<u32> = (0..self.color_attachments.len() as u32)
+                    .map(|i| gl::COLOR_ATTACHMENT0 + i)
+                    .collect();
+                gl::NamedFramebufferDrawBuffers(
+                    self.id,
+                    draw_buffers.len() as i32,
+                    draw_buffers.as_ptr(),
+                );
+                gl::NamedFramebufferReadBuffer(self.id, gl::COLOR_ATTACHMENT0);
+            }
+        }
+
+        let status = unsafe { gl::CheckNamedFramebufferStatus(self.id, gl::FRAMEBUFFER) };
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(Error::Framebuffer {
+                message: incomplete_reason(status).to_string(),
+            })
+        }
+    }
+
+    fn delete_attachments(&mut self) {
+        self.color_attachments
+            .drain(..)
+            .for_each(Attachment::delete);
+        if let Some(attachment) = self.depth_attachment.take() {
+            attachment.delete();
+        }
+    }
+
+    /// Binds the framebuffer as the target of draw calls and sets the viewport to cover it.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.spec.width as i32, self.spec.height as i32);
+        }
+    }
+
+    /// Binds the default framebuffer (the window) back. The viewport isn't restored.
+    pub fn unbind() {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) };
+    }
+
+    /// Copies the given buffers to `target`, scaling them if the sizes differ. Color is
+    /// read from the first color attachment and written to every color attachment of
+    /// `target`.
+    ///
+    /// Blitting from a multisampled framebuffer to a single-sampled one resolves it, in
+    /// that case both must have the same size.
+    pub fn blit_to(&self, target: &Framebuffer, mask: BlitMask) {
+        self.blit(target.id, target.spec.width, target.spec.height, mask, 0);
+    }
+
+    /// Copies the first color attachment to the default framebuffer (the window), which is
+    /// `width` by `height` pixels. A multisampled framebuffer must have the same size.
+    pub fn blit_to_default(&self, width: u32, height: u32) {
+        self.blit(0, width, height, BlitMask::COLOR, 0);
+    }
+
+    /// Resolves every color attachment and the depth attachment of a multisampled
+    /// framebuffer into the matching attachments of `target`. The depth attachment is only
+    /// copied if both have the same depth format.
+    pub fn resolve_to(&self, target: &Framebuffer) {
+        let count = self
+            .color_attachments
+            .len()
+            .min(target.color_attachments.len()) as u32;
+        for i in 0..count {
+            unsafe {
+                gl::NamedFramebufferReadBuffer(self.id, gl::COLOR_ATTACHMENT0 + i);
+                gl::NamedFramebufferDrawBuffer(target.id, gl::COLOR_ATTACHMENT0 + i);
+            }
+            self.blit(
+                target.id,
+                target.spec.width,
+                target.spec.height,
+                BlitMask::COLOR,
+                i as usize,
+            );
+        }
+        if count > 0 {
+            // Restore the read and draw buffers changed above.
+            unsafe { gl::NamedFramebufferReadBuffer(self.id, gl::COLOR_ATTACHMENT0) };
+            let draw_buffers: Vec<u32> = (0..target.color_attachments.len() as u32)
+                .map(|i| gl::COLOR_ATTACHMENT0 + i)
+                .collect();
+            unsafe {
+                gl::NamedFramebufferDrawBuffers(
+                    target.id,
+                    draw_buffers.len() as i32,
+                    draw_buffers.as_ptr(),
+                );
+            }
+        }
+        let depth_format = |spec: &FramebufferSpec| spec.depth.map(|depth| depth.format);
+        if depth_format(&self.spec).is_some()
+            && depth_format(&self.spec) == depth_format(&target.spec)
+        {
+            self.blit(
+                target.id,
+                target.spec.width,
+                target.spec.height,
+                BlitMask::DEPTH,
+                0,
+            );
+        }
+    }
+
+    /// Blits `mask` to `target`, reading color from the color attachment `color`.
+    fn blit(&self, target: u32, width: u32, height: u32, mask: BlitMask, color: usize) {
+        let (src_width, src_height) = (self.spec.width as i32, self.spec.height as i32);
+        // Depth, stencil and integer colors can only be blitted with nearest filtering.
+        let integer = self
+            .spec
+            .color
+            .get(color)
+            .is_some_and(|attachment| attachment.format.is_integer());
+        let filter = if mask == BlitMask::COLOR
+            && !integer
+            && (width, height) != (self.spec.width, self.spec.height)
+        {
+            gl::LINEAR
+        } else {
+            gl::NEAREST
+        };
+        unsafe {
+            gl::BlitNamedFramebuffer(
+                self.id,
+                target,
+                0,
+                0,
+                src_width,
+                src_height,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                mask.0,
+                filter,
+            );
+        }
+    }
+
+    /// Reads back the color attachment `index` as 8-bit RGBA, with the first row at the top
+    /// of the image. Multisampled framebuffers must be resolved first, see
+    /// [`Framebuffer::resolve_to`].
+    ///
+    /// Only [`AttachmentFormat::Rgba8`] attachments can be read, other formats return an
+    /// [`Error::Framebuffer`]. Panics if the attachment doesn't exist.
+    pub fn read_pixels(&self, index: usize) -> Result<RgbaImage, Error> {
+        assert!(
+            index < self.color_attachments.len(),
+            "Color attachment {} doesn't exist",
+            index
+        );
+        let format = self.spec.color[index].format;
+        if format != AttachmentFormat::Rgba8 {
+            return Err(Error::Framebuffer {
+                message: format!(
+                    "color attachment {} is {:?}, only Rgba8 attachments can be read back",
+                    index, format
+                ),
+            });
+        }
+        let image = read_rgba(
+            self.id,
+            gl::COLOR_ATTACHMENT0 + index as u32,
+            self.spec.width,
+            self.spec.height,
+        );
+        unsafe { gl::NamedFramebufferReadBuffer(self.id, gl::COLOR_ATTACHMENT0) };
+        Ok(image)
+    }
+
+    /// Reads back the color attachment `index` and saves it as a PNG file at `path`.
+    pub fn save_png(&self, index: usize, path: impl AsRef<Path>) -> Result<(), Error> {
+        save_png(&self.read_pixels(index)?, path.as_ref())
+    }
+
+    /// Reads back the back buffer of the default framebuffer (the window), which is
+    /// `width` by `height` pixels, as 8-bit RGBA with the first row at the top. Call it
+    /// after drawing the frame and before swapping the buffers.
+    pub fn read_default_pixels(width: u32, height: u32) -> RgbaImage {
+        read_rgba(0, gl::BACK, width, height)
+    }
+
+    /// Saves the back buffer of the default framebuffer as a PNG file in `dir`, named
+    /// after the current local time (e.g. `screenshot_2023-01-31_18-04-05.123.png`), and
+    /// returns its path. `dir` is created if it doesn't exist.
+    pub fn save_screenshot(
+        width: u32,
+        height: u32,
+        dir: impl AsRef<Path>,
+    ) -> Result<PathBuf, Error> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(|source| Error::Write {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+        let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S%.3f");
+        let path = dir.join(format!("screenshot_{}.png", timestamp));
+        save_png(&Self::read_default_pixels(width, height), &path)?;
+        Ok(path)
+    }
+
+    /// Binds the color attachment `index` to the texture unit `unit` so it can be sampled.
+    ///
+    /// Panics if the attachment doesn't exist or is a renderbuffer.
+    pub fn bind_color_attachment(&self, index: usize, unit: u32) {
+        let id = self
+            .get_color_attachment_id(index)
+            .expect("Only texture attachments can be sampled");
+        unsafe { gl::BindTextureUnit(unit, id) };
+    }
+
+    /// Returns the id of the texture of the color attachment `index`, or `None` if it
+    /// doesn't exist or is a renderbuffer.
+    pub fn get_color_attachment_id(&self, index: usize) -> Option<u32> {
+        match self.color_attachments.get(index)? {
+            Attachment::Texture(id) => Some(*id),
+            Attachment::Renderbuffer(_) => None,
+        }
+    }
+
+    /// Returns the id of the texture of the depth attachment, or `None` if there isn't one
+    /// or it is a renderbuffer.
+    pub fn get_depth_attachment_id(&self) -> Option<u32> {
+        match self.depth_attachment? {
+            Attachment::Texture(id) => Some(id),
+            Attachment::Renderbuffer(_) => None,
+        }
+    }
+
+    /// Returns the spec the framebuffer was created with, with the current size.
+    pub fn get_spec(&self) -> &FramebufferSpec {
+        &self.spec
+    }
+
+    /// Returns the width of the framebuffer.
+    pub fn get_width(&self) -> u32 {
+        self.spec.width
+    }
+
+    /// Returns the height of the framebuffer.
+    pub fn get_height(&self) -> u32 {
+        self.spec.height
+    }
+
+    /// Returns the internal id of the framebuffer used by OpenGL.
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        self.delete_attachments();
+        unsafe { gl::DeleteFramebuffers(1, &self.id) };
+    }
+}
+
+/// Checks the spec against the limits of the driver before creating anything.
+fn validate(spec: &FramebufferSpec) -> Result<(), Error> {
+    let error = |message: String| Err(Error::Framebuffer { message });
+    let (mut max_size, mut max_samples, mut max_color) = (0, 0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAX_FRAMEBUFFER_WIDTH, &mut max_size);
+        gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+        gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_color);
+    }
+
+    if spec.width == 0 || spec.height == 0 {
+        return error(format!("size {}x{} is empty", spec.width, spec.height));
+    }
+    if spec.width.max(spec.height) > max_size as u32 {
+        return error(format!(
+            "size {}x{} exceeds the maximum of {}",
+            spec.width, spec.height, max_size
+        ));
+    }
+    if spec.samples == 0 || spec.samples > max_samples as u32 {
+        return error(format!(
+            "{} samples requested, the driver supports between 1 and {}",
+            spec.samples, max_samples
+        ));
+    }
+    if spec.color.len() > max_color as u32 as usize {
+        return error(format!(
+            "{} color attachments requested, the driver supports {}",
+            spec.color.len(),
+            max_color
+        ));
+    }
+    if let Some(attachment) = spec.color.iter().find(|a| a.format.is_depth()) {
+        return error(format!(
+            "{:?} can't be a color attachment",
+            attachment.format
+        ));
+    }
+    if let Some(attachment) = spec.depth.filter(|a| !a.format.is_depth()) {
+        return error(format!(
+            "{:?} can't be a depth attachment",
+            attachment.format
+        ));
+    }
+    if spec.color.is_empty() && spec.depth.is_none() {
+        return error("no attachments".to_string());
+    }
+    Ok(())
+}
+
+/// Describes the status returned by `glCheckFramebufferStatus`.
+fn incomplete_reason(status: u32) -> &'static str {
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => "the default framebuffer doesn't exist",
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is incomplete",
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "there are no attachments",
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer has no attachment",
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer has no attachment",
+        gl::FRAMEBUFFER_UNSUPPORTED => {
+            "the combination of attachment formats isn't supported by the driver"
+        },
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => {
+            "the attachments don't have the same number of samples"
+        },
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "the attachments aren't all layered",
+        _ => "unknown framebuffer status",
+    }
+}
+
+/// Reads the color buffer `read_buffer` of the framebuffer `id` as 8-bit RGBA, flipping it
+/// so the first row is at the top.
+fn read_rgba(id: u32, read_buffer: u32, width: u32, height: u32) -> RgbaImage {
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    unsafe {
+        gl::NamedFramebufferReadBuffer(id, read_buffer);
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, id);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadnPixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.len() as i32,
+            pixels.as_mut_ptr() as *mut c_void,
+        );
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+    }
+
+    let mut image = RgbaImage::from_raw(width, height, pixels).unwrap();
+    flip_rows(&mut image);
+    image
+}
+
+fn save_png(image: &RgbaImage, path: &Path) -> Result<(), Error> {
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|source| Error::SaveImage {
+            path: path.to_path_buf(),
+            source,
+        })
+}
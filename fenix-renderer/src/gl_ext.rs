@@ -0,0 +1,131 @@
+//! OpenGL functions and constants missing from the bindings of the `gl` crate, which only
+//! cover core OpenGL 4.5 without extensions.
+
+use std::{
+    ffi::{c_void, CStr},
+    mem, ptr,
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+
+/// `GL_SHADER_BINARY_FORMAT_SPIR_V` (GL 4.6 / `ARB_gl_spirv`).
+pub const SHADER_BINARY_FORMAT_SPIR_V: u32 = 0x9551;
+/// `GL_TEXTURE_MAX_ANISOTROPY` (GL 4.6 / `EXT_texture_filter_anisotropic`).
+pub const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+/// `GL_MAX_TEXTURE_MAX_ANISOTROPY` (GL 4.6 / `EXT_texture_filter_anisotropic`).
+pub const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+/// `GL_COMPRESSED_RGB_S3TC_DXT1_EXT` (`EXT_texture_compression_s3tc`), i.e. BC1.
+pub const COMPRESSED_RGB_S3TC_DXT1: u32 = 0x83F0;
+/// `GL_COMPRESSED_RGBA_S3TC_DXT1_EXT` (`EXT_texture_compression_s3tc`), i.e. BC1 with
+/// 1-bit alpha.
+pub const COMPRESSED_RGBA_S3TC_DXT1: u32 = 0x83F1;
+/// `GL_COMPRESSED_RGBA_S3TC_DXT3_EXT` (`EXT_texture_compression_s3tc`), i.e. BC2.
+pub const COMPRESSED_RGBA_S3TC_DXT3: u32 = 0x83F2;
+/// `GL_COMPRESSED_RGBA_S3TC_DXT5_EXT` (`EXT_texture_compression_s3tc`), i.e. BC3.
+pub const COMPRESSED_RGBA_S3TC_DXT5: u32 = 0x83F3;
+/// `GL_COMPRESSED_SRGB_S3TC_DXT1_EXT` (`EXT_texture_sRGB`).
+pub const COMPRESSED_SRGB_S3TC_DXT1: u32 = 0x8C4C;
+/// `GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT` (`EXT_texture_sRGB`).
+pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: u32 = 0x8C4D;
+/// `GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT` (`EXT_texture_sRGB`).
+pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: u32 = 0x8C4E;
+/// `GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT` (`EXT_texture_sRGB`).
+pub const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: u32 = 0x8C4F;
+
+/// `GL_COMPRESSED_RGBA_ASTC_4x4_KHR` (`KHR_texture_compression_astc_ldr`). The other
+/// block sizes follow it in the order of [`ASTC_BLOCK_SIZES`].
+pub const COMPRESSED_RGBA_ASTC_4X4: u32 = 0x93B0;
+/// `GL_COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR` (`KHR_texture_compression_astc_ldr`). The
+/// other block sizes follow it in the order of [`ASTC_BLOCK_SIZES`].
+pub const COMPRESSED_SRGB8_ALPHA8_ASTC_4X4: u32 = 0x93D0;
+/// Block sizes of the 2D ASTC formats, in the order of their enums.
+#[rustfmt::skip]
+pub const ASTC_BLOCK_SIZES: [[u32; 2]; 14] = [
+    [4, 4], [5, 4], [5, 5], [6, 5], [6, 6], [8, 5], [8, 6],
+    [8, 8], [10, 5], [10, 6], [10, 8], [10, 10], [12, 10], [12, 12],
+];
+
+type SpecializeShaderFn = extern "system" fn(u32, *const i8, u32, *const u32, *const u32);
+
+static SPECIALIZE_SHADER: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+/// Bits of the maximum anisotropy as a `f32`, 0 if anisotropic filtering isn't supported.
+static MAX_ANISOTROPY: AtomicU32 = AtomicU32::new(0);
+
+/// Loads the extra functions with the same loader given to `gl::load_with`.
+pub(crate) fn load_with<F>(mut loader: F)
+where
+    F: FnMut(&str) -> *const c_void,
+{
+    // Drivers may return stubs for functions they don't implement, so the pointer is only
+    // loaded when the version or extension promises the function is there.
+    let specialize_shader = if has_version(4, 6) {
+        loader("glSpecializeShader")
+    } else if has_extension("GL_ARB_gl_spirv") {
+        loader("glSpecializeShaderARB")
+    } else {
+        ptr::null()
+    };
+    SPECIALIZE_SHADER.store(specialize_shader as *mut c_void, Ordering::Release);
+
+    let mut max_anisotropy = 0.0;
+    if has_extension("GL_EXT_texture_filter_anisotropic")
+        || has_extension("GL_ARB_texture_filter_anisotropic")
+    {
+        unsafe { gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy) };
+    }
+    MAX_ANISOTROPY.store(f32::to_bits(max_anisotropy), Ordering::Release);
+}
+
+/// Checks if the version of the current context is at least `major.minor`.
+fn has_version(major: i32, minor: i32) -> bool {
+    let mut version = [0; 2];
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut version[0]);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut version[1]);
+    }
+    version >= [major, minor]
+}
+
+/// Checks if the current context supports an extension.
+fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count) };
+    (0..count as u32).any(|i| {
+        let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+        !extension.is_null()
+            && unsafe { CStr::from_ptr(extension as *const _) }.to_bytes() == name.as_bytes()
+    })
+}
+
+/// Returns the maximum anisotropy supported by the driver, or `None` if anisotropic
+/// filtering isn't available.
+pub fn max_anisotropy() -> Option<f32> {
+    let max_anisotropy = f32::from_bits(MAX_ANISOTROPY.load(Ordering::Acquire));
+    (max_anisotropy > 0.0).then_some(max_anisotropy)
+}
+
+/// Checks if `glSpecializeShader` (or `glSpecializeShaderARB`) was loaded, i.e. if the
+/// context is OpenGL 4.6 or supports `ARB_gl_spirv`.
+pub fn has_specialize_shader() -> bool {
+    !SPECIALIZE_SHADER.load(Ordering::Acquire).is_null()
+}
+
+/// Calls `glSpecializeShader`.
+///
+/// # Safety
+///
+/// The function must have been loaded (see [`has_specialize_shader`]) and the arguments
+/// must be valid for it: `entry_point` a nul terminated string and `indices` and `values`
+/// pointing to `count` elements.
+pub unsafe fn specialize_shader(
+    shader: u32,
+    entry_point: *const i8,
+    count: u32,
+    indices: *const u32,
+    values: *const u32,
+) {
+    let function = SPECIALIZE_SHADER.load(Ordering::Acquire);
+    debug_assert!(!function.is_null(), "glSpecializeShader isn't loaded");
+    let function: SpecializeShaderFn = mem::transmute(function);
+    function(shader, entry_point, count, indices, values);
+}
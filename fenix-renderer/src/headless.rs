@@ -0,0 +1,144 @@
+//! OpenGL contexts without a window, for rendering on machines without a display (e.g.
+//! CI runners with Mesa's llvmpipe).
+//!
+//! The context is created through EGL. It is surfaceless, so there is no default
+//! framebuffer and everything must be rendered into a [`Framebuffer`](crate::framebuffer::Framebuffer).
+
+use crate::error::Error;
+use khronos_egl as egl;
+use std::{ffi::CStr, ptr};
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`, a platform without any native display.
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+/// An OpenGL 4.5 core context that is current on the thread that created it.
+///
+/// Like a window context, it must outlive every object created while it's current.
+pub struct HeadlessContext {
+    egl: egl::DynamicInstance<egl::EGL1_4>,
+    display: egl::Display,
+    context: egl::Context,
+}
+
+impl HeadlessContext {
+    /// Creates a context, makes it current and loads the OpenGL functions with
+    /// [`load_with`](crate::load_with).
+    pub fn new() -> Result<Self, Error> {
+        let error = |message: String| Error::Context { message };
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() }
+            .map_err(|err| error(format!("couldn't load libEGL: {}", err)))?;
+
+        let display = open_display(&egl).ok_or_else(|| error("no EGL display".into()))?;
+        egl.initialize(display)
+            .map_err(|err| error(format!("couldn't initialize EGL: {}", err)))?;
+
+        let context = create_context(&egl, display).map_err(|message| {
+            let _ = egl.terminate(display);
+            error(message)
+        })?;
+        crate::load_with(|name| {
+            egl.get_proc_address(name)
+                .map_or(ptr::null(), |function| function as *const _)
+        });
+
+        Ok(Self {
+            egl,
+            display,
+            context,
+        })
+    }
+
+    /// Returns the `GL_RENDERER` string of the context, e.g. `llvmpipe (LLVM 15.0.7, 256 bits)`.
+    pub fn get_renderer(&self) -> String {
+        unsafe {
+            let renderer = gl::GetString(gl::RENDERER);
+            if renderer.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(renderer as *const _)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        }
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
+
+/// Opens the surfaceless platform if the driver supports it, otherwise the default display.
+fn open_display(egl: &egl::DynamicInstance<egl::EGL1_4>) -> Option<egl::Display> {
+    let client_extensions = egl
+        .query_string(None, egl::EXTENSIONS)
+        .map(|extensions| extensions.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if client_extensions
+        .split(' ')
+        .any(|extension| extension == "EGL_MESA_platform_surfaceless")
+    {
+        if let Some(egl) = egl.upcast::<egl::EGL1_5>() {
+            let display = unsafe {
+                egl.get_platform_display(
+                    PLATFORM_SURFACELESS_MESA,
+                    egl::DEFAULT_DISPLAY,
+                    &[egl::ATTRIB_NONE],
+                )
+            };
+            if let Ok(display) = display {
+                return Some(display);
+            }
+        }
+    }
+    unsafe { egl.get_display(egl::DEFAULT_DISPLAY) }
+}
+
+fn create_context(
+    egl: &egl::DynamicInstance<egl::EGL1_4>,
+    display: egl::Display,
+) -> Result<egl::Context, String> {
+    let extensions = egl
+        .query_string(Some(display), egl::EXTENSIONS)
+        .map_err(|err| err.to_string())?
+        .to_string_lossy();
+    if !extensions
+        .split(' ')
+        .any(|extension| extension == "EGL_KHR_surfaceless_context")
+    {
+        return Err("EGL_KHR_surfaceless_context isn't supported".into());
+    }
+
+    egl.bind_api(egl::OPENGL_API)
+        .map_err(|err| format!("OpenGL isn't supported by EGL: {}", err))?;
+    // The surface type defaults to windows, which surfaceless displays lack.
+    #[rustfmt::skip]
+    let config_attributes = [
+        egl::SURFACE_TYPE, 0,
+        egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+        egl::NONE,
+    ];
+    let config = egl
+        .choose_first_config(display, &config_attributes)
+        .map_err(|err| err.to_string())?
+        .ok_or("no EGL config supports OpenGL")?;
+
+    #[rustfmt::skip]
+    let attributes = [
+        egl::CONTEXT_MAJOR_VERSION, 4,
+        egl::CONTEXT_MINOR_VERSION, 5,
+        egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+        egl::NONE,
+    ];
+    let context = egl
+        .create_context(display, config, None, &attributes)
+        .map_err(|err| format!("no OpenGL 4.5 core context: {}", err))?;
+    if let Err(err) = egl.make_current(display, None, None, Some(context)) {
+        let _ = egl.destroy_context(display, context);
+        return Err(format!("couldn't make the context current: {}", err));
+    }
+    Ok(context)
+}
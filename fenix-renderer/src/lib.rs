@@ -1,3 +1,32 @@
+// Lets the code generated by `fenix-derive` refer to this crate as `::fenix_renderer` from
+// inside the crate itself.
+extern crate self as fenix_renderer;
+
+pub mod asset;
+pub mod block_layout;
 pub mod buffer;
+pub mod error;
+pub mod framebuffer;
+pub mod gl_ext;
+#[cfg(feature = "headless")]
+pub mod headless;
+pub mod material;
+pub mod mesh;
 pub mod shader;
 pub mod texture;
+pub mod vertex_array;
+
+pub use error::Error;
+
+use std::ffi::c_void;
+
+/// Loads the OpenGL function pointers, use it instead of `gl::load_with`.
+///
+/// Besides the functions of the `gl` crate it loads the ones in [`gl_ext`].
+pub fn load_with<F>(mut loader: F)
+where
+    F: FnMut(&str) -> *const c_void,
+{
+    gl::load_with(&mut loader);
+    gl_ext::load_with(loader);
+}
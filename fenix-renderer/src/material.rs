@@ -0,0 +1,367 @@
+//! Materials described by [MTL](https://paulbourke.net/dataformats/mtl/) material libraries,
+//! the companion format of OBJ files.
+
+use crate::{error::Error, mesh::ParseError, shader::ShaderProgram, texture::Texture2D};
+use glam::Vec3;
+use std::path::{Path, PathBuf};
+
+/// Texture unit the diffuse map is bound to by [`Material::apply`].
+pub const DIFFUSE_MAP_SLOT: u32 = 0;
+/// Texture unit the bump map is bound to by [`Material::apply`].
+pub const BUMP_MAP_SLOT: u32 = 1;
+/// Texture unit the specular map is bound to by [`Material::apply`].
+pub const SPECULAR_MAP_SLOT: u32 = 2;
+
+/// A material as it's described in an MTL file.
+///
+/// Texture paths are stored as they appear in the file, that is, relative to the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialData {
+    pub name: String,
+    /// `Ka`
+    pub ambient: Vec3,
+    /// `Kd`
+    pub diffuse: Vec3,
+    /// `Ks`
+    pub specular: Vec3,
+    /// `Ns`
+    pub shininess: f32,
+    /// `d`, or `1 - Tr`
+    pub dissolve: f32,
+    /// `illum`
+    pub illumination: u32,
+    /// `map_Kd`
+    pub diffuse_map: Option<PathBuf>,
+    /// `map_Bump` or `bump`
+    pub bump_map: Option<PathBuf>,
+    /// `map_Ks`
+    pub specular_map: Option<PathBuf>,
+}
+
+impl MaterialData {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: Vec3::splat(0.2),
+            diffuse: Vec3::splat(0.8),
+            specular: Vec3::splat(1.0),
+            shininess: 0.0,
+            dissolve: 1.0,
+            illumination: 2,
+            diffuse_map: None,
+            bump_map: None,
+            specular_map: None,
+        }
+    }
+}
+
+/// The materials declared in an MTL file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaterialLibrary {
+    pub materials: Vec<MaterialData>,
+}
+
+impl MaterialLibrary {
+    /// Parses the contents of an MTL file.
+    ///
+    /// Supports the `Ka`, `Kd`, `Ks`, `Ns`, `d`, `Tr` and `illum` statements plus the
+    /// `map_Kd`, `map_Bump` (or `bump`) and `map_Ks` texture slots. Options given to texture
+    /// maps (`-s`, `-bm`, ...) are skipped. Any other statement is ignored.
+    pub fn parse_mtl(src: &str) -> Result<Self, ParseError> {
+        let mut library = Self::default();
+
+        for (i, line) in src.lines().enumerate() {
+            let line_num = i + 1;
+            let line = match line.find('#') {
+                Some(start) => &line[..start],
+                None => line,
+            };
+            let mut tokens = line.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+
+            if keyword == "newmtl" {
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| ParseError::new(line_num, "missing material name"))?;
+                library.materials.push(MaterialData::new(name));
+                continue;
+            }
+
+            let material = match library.materials.last_mut() {
+                Some(material) => material,
+                None => {
+                    return Err(ParseError::new(
+                        line_num,
+                        format!("'{}' found before any 'newmtl'", keyword),
+                    ))
+                },
+            };
+
+            match keyword {
+                "Ka" => material.ambient = parse_color(tokens, line_num)?,
+                "Kd" => material.diffuse = parse_color(tokens, line_num)?,
+                "Ks" => material.specular = parse_color(tokens, line_num)?,
+                "Ns" => material.shininess = parse_float(tokens, line_num)?,
+                "d" => material.dissolve = parse_float(tokens.filter(|&t| t != "-halo"), line_num)?,
+                "Tr" => material.dissolve = 1.0 - parse_float(tokens, line_num)?,
+                "illum" => {
+                    let token = tokens.next().unwrap_or_default();
+                    material.illumination = token.parse().map_err(|_| {
+                        ParseError::new(line_num, format!("invalid illumination model '{}'", token))
+                    })?;
+                },
+                "map_Kd" => material.diffuse_map = Some(parse_map(tokens, line_num)?),
+                "map_Bump" | "map_bump" | "bump" => {
+                    material.bump_map = Some(parse_map(tokens, line_num)?)
+                },
+                "map_Ks" => material.specular_map = Some(parse_map(tokens, line_num)?),
+                _ => {},
+            }
+        }
+
+        Ok(library)
+    }
+
+    /// Returns the material with the given name, if any.
+    pub fn get(&self, name: &str) -> Option<&MaterialData> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+}
+
+fn parse_float<'a>(
+    mut tokens: impl Iterator<Item = &'a str>,
+    line: usize,
+) -> Result<f32, ParseError> {
+    let token = tokens.next().unwrap_or_default();
+    token
+        .parse()
+        .map_err(|_| ParseError::new(line, format!("invalid number '{}'", token)))
+}
+
+/// Parses an `r [g b]` color, if only `r` is given it's used for all the channels.
+fn parse_color<'a>(tokens: impl Iterator<Item = &'a str>, line: usize) -> Result<Vec3, ParseError> {
+    let values = tokens
+        .map(|token| {
+            token
+                .parse::<f32>()
+                .map_err(|_| ParseError::new(line, format!("invalid color value '{}'", token)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match values[..] {
+        [r] => Ok(Vec3::splat(r)),
+        [r, g, b] => Ok(Vec3::new(r, g, b)),
+        _ => Err(ParseError::new(line, "expected an 'r g b' color")),
+    }
+}
+
+/// Parses the arguments of a texture map statement skipping its options.
+fn parse_map<'a>(
+    tokens: impl Iterator<Item = &'a str>,
+    line: usize,
+) -> Result<PathBuf, ParseError> {
+    let mut tokens = tokens.peekable();
+    while let Some(option) = tokens.next_if(|t| t.starts_with('-')) {
+        // Options take a fixed number of arguments except for -o, -s and -t which take
+        // between 1 and 3, so skip anything that looks like an argument.
+        if option == "-imfchan" {
+            tokens.next();
+        }
+        while tokens
+            .next_if(|t| t.parse::<f32>().is_ok() || *t == "on" || *t == "off")
+            .is_some()
+        {}
+    }
+    let path: Vec<&str> = tokens.collect();
+    if path.is_empty() {
+        return Err(ParseError::new(line, "missing texture path"));
+    }
+    Ok(PathBuf::from(path.join(" ")))
+}
+
+/// A material with its textures loaded to the GPU.
+pub struct Material {
+    name: String,
+    ambient: Vec3,
+    diffuse: Vec3,
+    specular: Vec3,
+    shininess: f32,
+    dissolve: f32,
+    diffuse_map: Option<Texture2D>,
+    bump_map: Option<Texture2D>,
+    specular_map: Option<Texture2D>,
+}
+
+impl Material {
+    /// Loads the textures referenced by `data`, resolving their paths relative to
+    /// `base_dir` (usually the directory of the MTL file).
+    pub fn from_data(data: &MaterialData, base_dir: &Path) -> Result<Self, Error> {
+        let load = |path: &Option<PathBuf>| -> Result<Option<Texture2D>, Error> {
+            match path {
+                Some(path) => {
+                    let path = base_dir.join(path);
+                    Texture2D::from_file(&path.to_string_lossy()).map(Some)
+                },
+                None => Ok(None),
+            }
+        };
+
+        Ok(Self {
+            name: data.name.clone(),
+            ambient: data.ambient,
+            diffuse: data.diffuse,
+            specular: data.specular,
+            shininess: data.shininess,
+            dissolve: data.dissolve,
+            diffuse_map: load(&data.diffuse_map)?,
+            bump_map: load(&data.bump_map)?,
+            specular_map: load(&data.specular_map)?,
+        })
+    }
+
+    /// Returns the name of the material.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Binds the textures of the material and uploads its properties to the given program.
+    ///
+    /// The program is expected to declare a `u_material` uniform of the following type,
+    /// members that aren't present in the program are skipped.
+    ///
+    /// ```glsl
+    /// struct Material {
+    ///     vec3 ambient;
+    ///     vec3 diffuse;
+    ///     vec3 specular;
+    ///     float shininess;
+    ///     float dissolve;
+    ///     bool has_diffuse_map;
+    ///     bool has_bump_map;
+    ///     bool has_specular_map;
+    ///     sampler2D diffuse_map;
+    ///     sampler2D bump_map;
+    ///     sampler2D specular_map;
+    /// };
+    /// ```
+    pub fn apply(&self, program: &mut ShaderProgram) -> Result<(), Error> {
+        let vectors = [
+            ("u_material.ambient", self.ambient),
+            ("u_material.diffuse", self.diffuse),
+            ("u_material.specular", self.specular),
+        ];
+        for (name, value) in vectors {
+            if program.has_uniform(name) {
+                program.set_uniform_3f(name, value)?;
+            }
+        }
+
+        let scalars = [
+            ("u_material.shininess", self.shininess),
+            ("u_material.dissolve", self.dissolve),
+        ];
+        for (name, value) in scalars {
+            if program.has_uniform(name) {
+                program.set_uniform_1f(name, value)?;
+            }
+        }
+
+        let maps = [
+            ("diffuse_map", &self.diffuse_map, DIFFUSE_MAP_SLOT),
+            ("bump_map", &self.bump_map, BUMP_MAP_SLOT),
+            ("specular_map", &self.specular_map, SPECULAR_MAP_SLOT),
+        ];
+        for (name, texture, slot) in maps {
+            let sampler = format!("u_material.{}", name);
+            let flag = format!("u_material.has_{}", name);
+            if let Some(texture) = texture {
+                texture.bind(slot);
+                if program.has_uniform(&sampler) {
+                    program.set_uniform_1i(&sampler, slot as i32)?;
+                }
+            }
+            if program.has_uniform(&flag) {
+                program.set_uniform_1i(&flag, texture.is_some() as i32)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_material_values() {
+        let src = "
+# Two materials
+newmtl red
+Ka 0.1
+Kd 1.0 0.0 0.0
+Ks 0.5 0.5 0.5
+Ns 96.0
+d 0.75
+illum 1
+
+newmtl glass
+Tr 0.9
+";
+        let library = MaterialLibrary::parse_mtl(src).unwrap();
+        let red = library.get("red").unwrap();
+        assert_eq!(red.ambient, Vec3::splat(0.1));
+        assert_eq!(red.diffuse, Vec3::X);
+        assert_eq!(red.specular, Vec3::splat(0.5));
+        assert_eq!(red.shininess, 96.0);
+        assert_eq!(red.dissolve, 0.75);
+        assert_eq!(red.illumination, 1);
+
+        let glass = library.get("glass").unwrap();
+        assert!((glass.dissolve - 0.1).abs() < 1e-6);
+        assert_eq!(glass.diffuse, Vec3::splat(0.8), "unset values keep their defaults");
+        assert!(library.get("blue").is_none());
+    }
+
+    #[test]
+    fn map_options_are_skipped() {
+        let src = "
+newmtl textured
+map_Kd -blendu off -s 2 2 1 -o 0.5 image/diffuse map.png
+map_Bump -bm 0.3 -imfchan l bump.png
+bump -clamp on alt_bump.png
+map_Ks specular.png
+";
+        let library = MaterialLibrary::parse_mtl(src).unwrap();
+        let material = &library.materials[0];
+        assert_eq!(material.diffuse_map, Some(PathBuf::from("image/diffuse map.png")));
+        assert_eq!(material.bump_map, Some(PathBuf::from("alt_bump.png")));
+        assert_eq!(material.specular_map, Some(PathBuf::from("specular.png")));
+        assert_eq!(
+            parse_map("-bm 0.3 -imfchan l bump.png".split_whitespace(), 1),
+            Ok(PathBuf::from("bump.png"))
+        );
+    }
+
+    #[test]
+    fn errors_report_the_line() {
+        let error = |src: &str| MaterialLibrary::parse_mtl(src).unwrap_err();
+        assert_eq!(
+            error("Kd 1 1 1\n"),
+            ParseError::new(1, "'Kd' found before any 'newmtl'")
+        );
+        assert_eq!(
+            error("newmtl a\nKd 1 1\n"),
+            ParseError::new(2, "expected an 'r g b' color")
+        );
+        assert_eq!(
+            error("newmtl a\n\nNs high\n"),
+            ParseError::new(3, "invalid number 'high'")
+        );
+        assert_eq!(
+            error("newmtl a\nmap_Kd -s 1 1 1\n"),
+            ParseError::new(2, "missing texture path")
+        );
+    }
+}
@@ -0,0 +1,603 @@
+//! Triangle meshes loaded from [Wavefront OBJ](https://en.wikipedia.org/wiki/Wavefront_.obj_file)
+//! files.
+//!
+//! Parsing is done entirely on the CPU by [`MeshData::parse_obj`], the GPU buffers are only
+//! created when a [`Mesh`] is built from the parsed data.
+
+use crate::{
+    buffer::{IndexBuffer, VertexBuffer},
+    error::Error,
+    material::{Material, MaterialLibrary},
+    vertex_array::{Vertex, VertexArray},
+};
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec2, Vec3};
+use log::warn;
+use std::{
+    collections::HashMap,
+    error, fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// A single vertex of a mesh as it's laid out in the vertex buffer.
+///
+/// Attribute locations match the ones used by `assets/shader.vert`.
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable, Vertex)]
+#[repr(C)]
+pub struct MeshVertex {
+    #[location(0)]
+    pub position: Vec3,
+    #[location(2)]
+    pub normal: Vec3,
+    #[location(3)]
+    pub tex_coord: Vec2,
+}
+
+/// Error produced when an OBJ file can't be parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// Line (starting at 1) where the error was found.
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for ParseError {}
+
+/// A range of indices of a mesh drawn with the same material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubMeshData {
+    /// Name of the material selected with `usemtl`.
+    pub material: Option<String>,
+    /// Position of the first index of the submesh in the index list.
+    pub offset: usize,
+    /// Number of indices in the submesh.
+    pub count: usize,
+}
+
+/// Deduplicated, triangulated mesh data living in CPU memory.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    /// Index ranges split at every `usemtl` statement. Covers all the indices.
+    pub submeshes: Vec<SubMeshData>,
+    /// Material library files referenced with `mtllib`, relative to the OBJ file.
+    pub material_libs: Vec<String>,
+}
+
+/// Indices of the attributes of a face corner, already resolved to be 0-based.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct VertexKey {
+    position: usize,
+    tex_coord: Option<usize>,
+    normal: Option<usize>,
+}
+
+impl MeshData {
+    /// Parses the contents of an OBJ file.
+    ///
+    /// Supports `v`, `vt`, `vn`, `f`, `mtllib` and `usemtl` records. Faces can use any of
+    /// the `v`, `v/vt`, `v//vn` and `v/vt/vn` index formats as well as negative (relative)
+    /// indices. Polygons with more than three vertices are triangulated as a fan, so they
+    /// are expected to be convex. The optional `w` components of positions and texture
+    /// coordinates are accepted but ignored, and so are vertex colors written after the
+    /// position (`v x y z r g b`). Any other record is skipped.
+    ///
+    /// Face corners that reference the same attributes share a single vertex. Missing
+    /// normals or texture coordinates are filled with zeros. Every `usemtl` starts a new
+    /// submesh.
+    pub fn parse_obj(src: &str) -> Result<Self, ParseError> {
+        let mut positions: Vec<Vec3> = Vec::new();
+        let mut tex_coords: Vec<Vec2> = Vec::new();
+        let mut normals: Vec<Vec3> = Vec::new();
+
+        let mut data = Self::default();
+        let mut lookup: HashMap<VertexKey, u32> = HashMap::new();
+        let mut polygon: Vec<u32> = Vec::new();
+
+        for (i, line) in src.lines().enumerate() {
+            let line_num = i + 1;
+            let line = match line.find('#') {
+                Some(start) => &line[..start],
+                None => line,
+            };
+            let mut tokens = line.split_whitespace();
+            let keyword = match tokens.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+
+            match keyword {
+                "v" => {
+                    let values = parse_floats(tokens, 3, 7, line_num)?;
+                    if values.len() == 5 {
+                        return Err(ParseError::new(
+                            line_num,
+                            "expected 'x y z [w]' or 'x y z [w] r g b' values, found 5",
+                        ));
+                    }
+                    positions.push(Vec3::new(values[0], values[1], values[2]));
+                },
+                "vt" => {
+                    let values = parse_floats(tokens, 1, 3, line_num)?;
+                    let v = values.get(1).copied().unwrap_or(0.0);
+                    tex_coords.push(Vec2::new(values[0], v));
+                },
+                "vn" => {
+                    let values = parse_floats(tokens, 3, 3, line_num)?;
+                    normals.push(Vec3::new(values[0], values[1], values[2]));
+                },
+                "f" => {
+                    polygon.clear();
+                    for token in tokens {
+                        let key = parse_face_vertex(
+                            token,
+                            positions.len(),
+                            tex_coords.len(),
+                            normals.len(),
+                            line_num,
+                        )?;
+                        let index = *lookup.entry(key).or_insert_with(|| {
+                            data.vertices.push(MeshVertex {
+                                position: positions[key.position],
+                                normal: key.normal.map_or(Vec3::ZERO, |n| normals[n]),
+                                tex_coord: key.tex_coord.map_or(Vec2::ZERO, |t| tex_coords[t]),
+                            });
+                            (data.vertices.len() - 1) as u32
+                        });
+                        polygon.push(index);
+                    }
+                    if polygon.len() < 3 {
+                        return Err(ParseError::new(
+                            line_num,
+                            "a face needs at least 3 vertices",
+                        ));
+                    }
+                    for k in 1..polygon.len() - 1 {
+                        data.indices
+                            .extend_from_slice(&[polygon[0], polygon[k], polygon[k + 1]]);
+                    }
+                },
+                "mtllib" => data.material_libs.extend(tokens.map(String::from)),
+                "usemtl" => {
+                    let name = tokens
+                        .next()
+                        .ok_or_else(|| ParseError::new(line_num, "missing material name"))?;
+                    data.close_submesh();
+                    data.submeshes.push(SubMeshData {
+                        material: Some(name.to_string()),
+                        offset: data.indices.len(),
+                        count: 0,
+                    });
+                },
+                _ => {},
+            }
+        }
+
+        data.close_submesh();
+        Ok(data)
+    }
+
+    /// Updates the index count of the last submesh and drops it if it turns out to be
+    /// empty. Faces found before the first `usemtl` get a submesh without material.
+    fn close_submesh(&mut self) {
+        match self.submeshes.last_mut() {
+            Some(last) => last.count = self.indices.len() - last.offset,
+            None if !self.indices.is_empty() => self.submeshes.push(SubMeshData {
+                material: None,
+                offset: 0,
+                count: self.indices.len(),
+            }),
+            None => {},
+        }
+        if self.submeshes.last().is_some_and(|last| last.count == 0) {
+            self.submeshes.pop();
+        }
+    }
+}
+
+/// Parses between `min` and `max` floating point values.
+fn parse_floats<'a>(
+    tokens: impl Iterator<Item = &'a str>,
+    min: usize,
+    max: usize,
+    line: usize,
+) -> Result<Vec<f32>, ParseError> {
+    let values = tokens
+        .map(|token| {
+            token
+                .parse::<f32>()
+                .map_err(|_| ParseError::new(line, format!("invalid number '{}'", token)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.len() < min || values.len() > max {
+        return Err(ParseError::new(
+            line,
+            format!(
+                "expected between {} and {} values, found {}",
+                min,
+                max,
+                values.len()
+            ),
+        ));
+    }
+    Ok(values)
+}
+
+/// Parses a face corner in any of the `v`, `v/vt`, `v//vn` or `v/vt/vn` formats.
+fn parse_face_vertex(
+    token: &str,
+    position_count: usize,
+    tex_coord_count: usize,
+    normal_count: usize,
+    line: usize,
+) -> Result<VertexKey, ParseError> {
+    let mut parts = token.split('/');
+    let position = match parts.next() {
+        Some(s) if !s.is_empty() => resolve_index(s, position_count, line)?,
+        _ => {
+            return Err(ParseError::new(
+                line,
+                format!("missing position index in '{}'", token),
+            ))
+        },
+    };
+    let tex_coord = match parts.next() {
+        Some(s) if !s.is_empty() => Some(resolve_index(s, tex_coord_count, line)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(s) if !s.is_empty() => Some(resolve_index(s, normal_count, line)?),
+        _ => None,
+    };
+    if parts.next().is_some() {
+        return Err(ParseError::new(
+            line,
+            format!("malformed face vertex '{}'", token),
+        ));
+    }
+    Ok(VertexKey {
+        position,
+        tex_coord,
+        normal,
+    })
+}
+
+/// Converts a 1-based (or negative, relative to the end) OBJ index into a 0-based index.
+fn resolve_index(s: &str, count: usize, line: usize) -> Result<usize, ParseError> {
+    let index: i64 = s
+        .parse()
+        .map_err(|_| ParseError::new(line, format!("invalid index '{}'", s)))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(ParseError::new(
+            line,
+            format!("index {} is out of range", index),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+/// A range of indices of a [`Mesh`] and the material used to draw it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubMesh {
+    pub offset: usize,
+    pub count: usize,
+    /// Index of the material in [`Mesh::get_materials`].
+    pub material: Option<usize>,
+}
+
+/// A mesh whose vertices and indices have been uploaded to the GPU.
+///
+/// Vertices are laid out as described by [`MeshVertex`].
+pub struct Mesh {
+    vertex_array: VertexArray,
+    submeshes: Vec<SubMesh>,
+    materials: Vec<Material>,
+}
+
+impl Mesh {
+    /// Uploads the given mesh data to the GPU.
+    ///
+    /// Materials aren't loaded, so none of the submeshes will have one. Use
+    /// [`Mesh::from_file`] to also load the material libraries referenced by the mesh.
+    pub fn from_data(data: &MeshData) -> Result<Self, Error> {
+        let submeshes = data
+            .submeshes
+            .iter()
+            .map(|submesh| SubMesh {
+                offset: submesh.offset,
+                count: submesh.count,
+                material: None,
+            })
+            .collect();
+        let mut vertex_array = VertexArray::new().ok_or(Error::Allocation {
+            object: "vertex array",
+        })?;
+        vertex_array.add_vertex_buffer(VertexBuffer::from(&data.vertices), &MeshVertex::layout());
+        vertex_array.set_index_buffer(IndexBuffer::from(&data.indices));
+        Ok(Self {
+            vertex_array,
+            submeshes,
+            materials: Vec::new(),
+        })
+    }
+
+    /// Loads an OBJ file and uploads its contents to the GPU.
+    ///
+    /// Material libraries referenced with `mtllib` are loaded from the directory of the
+    /// OBJ file, together with the textures of the materials that are used.
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let src = read_file(Path::new(path))?;
+        let data = MeshData::parse_obj(&src).map_err(|source| Error::Parse {
+            path: PathBuf::from(path),
+            source,
+        })?;
+        let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+
+        let mut libraries = Vec::new();
+        for lib in &data.material_libs {
+            let lib_path = base_dir.join(lib);
+            let src = read_file(&lib_path)?;
+            let library = MaterialLibrary::parse_mtl(&src).map_err(|source| Error::Parse {
+                path: lib_path.clone(),
+                source,
+            })?;
+            libraries.push((lib_path, library));
+        }
+
+        let mut mesh = Self::from_data(&data)?;
+        let mut loaded: HashMap<&str, usize> = HashMap::new();
+        for (submesh, submesh_data) in mesh.submeshes.iter_mut().zip(&data.submeshes) {
+            let name = match &submesh_data.material {
+                Some(name) => name.as_str(),
+                None => continue,
+            };
+            if let Some(&index) = loaded.get(name) {
+                submesh.material = Some(index);
+                continue;
+            }
+            let found = libraries
+                .iter()
+                .find_map(|(lib_path, library)| library.get(name).map(|m| (lib_path, m)));
+            match found {
+                Some((lib_path, material_data)) => {
+                    let lib_dir = lib_path.parent().unwrap_or_else(|| Path::new(""));
+                    let material = Material::from_data(material_data, lib_dir)?;
+                    mesh.materials.push(material);
+                    loaded.insert(name, mesh.materials.len() - 1);
+                    submesh.material = Some(mesh.materials.len() - 1);
+                },
+                None => warn!("{}: material '{}' not found", path, name),
+            }
+        }
+
+        Ok(mesh)
+    }
+
+    /// Returns the vertex array holding the vertex and index buffers of the mesh.
+    pub fn get_vertex_array(&self) -> &VertexArray {
+        &self.vertex_array
+    }
+
+    /// Returns the parts of the mesh drawn with different materials.
+    pub fn get_submeshes(&self) -> &[SubMesh] {
+        &self.submeshes
+    }
+
+    /// Returns the materials used by the submeshes.
+    pub fn get_materials(&self) -> &[Material] {
+        &self.materials
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3], normal: [f32; 3], tex_coord: [f32; 2]) -> MeshVertex {
+        MeshVertex {
+            position: Vec3::from_array(position),
+            normal: Vec3::from_array(normal),
+            tex_coord: Vec2::from_array(tex_coord),
+        }
+    }
+
+    #[test]
+    fn parses_full_index_triplets() {
+        let src = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1
+";
+        let data = MeshData::parse_obj(src).unwrap();
+        assert_eq!(
+            data.vertices,
+            [
+                vertex([0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0]),
+                vertex([1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0]),
+                vertex([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0]),
+            ]
+        );
+        assert_eq!(data.indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn missing_tex_coords_and_normals_are_zero() {
+        let src = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vn 0 0 1
+f 1//1 2//1 3//1
+f 1 2 3
+";
+        let data = MeshData::parse_obj(src).unwrap();
+        assert_eq!(data.vertices.len(), 6);
+        assert_eq!(data.vertices[1], vertex([1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0]));
+        assert_eq!(data.vertices[4], vertex([1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0]));
+    }
+
+    #[test]
+    fn negative_indices_are_relative_to_the_end() {
+        let src = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f -3 -2 -1
+v 5 5 5
+f 1 -2 -1
+";
+        let data = MeshData::parse_obj(src).unwrap();
+        assert_eq!(data.indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(data.vertices[3].position, Vec3::splat(5.0));
+    }
+
+    #[test]
+    fn optional_components_are_ignored() {
+        let src = "
+v 0 0 0 1
+v 1 0 0 0.5 1 0 0
+v 0 1 0 0 1 0
+vt 0.5
+vt 0.25 0.75 1
+f 1/1 2/2 3/2
+";
+        let data = MeshData::parse_obj(src).unwrap();
+        assert_eq!(data.vertices[0].position, Vec3::ZERO);
+        assert_eq!(data.vertices[1].position, Vec3::X);
+        assert_eq!(data.vertices[2].position, Vec3::Y);
+        assert_eq!(data.vertices[0].tex_coord, Vec2::new(0.5, 0.0));
+        assert_eq!(data.vertices[1].tex_coord, Vec2::new(0.25, 0.75));
+    }
+
+    #[test]
+    fn polygons_are_triangulated_as_fans() {
+        let src = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v -1 1 0
+f 1 2 3 4
+f 1 2 3 4 5
+";
+        let data = MeshData::parse_obj(src).unwrap();
+        assert_eq!(
+            data.indices,
+            [0, 1, 2, 0, 2, 3, 0, 1, 2, 0, 2, 3, 0, 3, 4]
+        );
+    }
+
+    #[test]
+    fn shared_corners_are_deduplicated() {
+        let data = MeshData::parse_obj(include_str!("../../assets/cube.obj")).unwrap();
+        // 6 faces with 4 distinct corners each, since the normals differ between faces.
+        assert_eq!(data.vertices.len(), 24);
+        assert_eq!(data.indices.len(), 36);
+
+        let src = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vn 0 0 1
+vn 0 0 -1
+f 1//1 2//1 3//1
+f 3//1 2//1 1//2
+";
+        let data = MeshData::parse_obj(src).unwrap();
+        assert_eq!(data.vertices.len(), 4);
+        assert_eq!(data.indices, [0, 1, 2, 2, 1, 3]);
+    }
+
+    #[test]
+    fn usemtl_splits_submeshes() {
+        let src = "
+mtllib a.mtl b.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 1 1 0
+f 1 2 3
+usemtl red
+f 1 2 3 4
+usemtl unused
+usemtl blue
+f 2 4 3
+";
+        let data = MeshData::parse_obj(src).unwrap();
+        assert_eq!(data.material_libs, ["a.mtl", "b.mtl"]);
+        let submesh = |material: Option<&str>, offset, count| SubMeshData {
+            material: material.map(String::from),
+            offset,
+            count,
+        };
+        assert_eq!(
+            data.submeshes,
+            [
+                submesh(None, 0, 3),
+                submesh(Some("red"), 3, 6),
+                submesh(Some("blue"), 9, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_report_the_line() {
+        let error = |src: &str| MeshData::parse_obj(src).unwrap_err();
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 zero\n"),
+            ParseError::new(2, "invalid number 'zero'")
+        );
+        assert_eq!(error("\n\nv 0 0\n").line, 3);
+        assert_eq!(error("v 0 0 0 1 0\n").line, 1);
+        assert_eq!(
+            error("v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            ParseError::new(3, "a face needs at least 3 vertices")
+        );
+        assert_eq!(
+            error("v 0 0 0\n# comment\nf 1 1 4\n"),
+            ParseError::new(3, "index 4 is out of range")
+        );
+        assert_eq!(
+            error("v 0 0 0\nf 1 1 0\n"),
+            ParseError::new(2, "index 0 is out of range")
+        );
+        assert_eq!(
+            error("v 0 0 0\nf 1/// 1 1\n"),
+            ParseError::new(2, "malformed face vertex '1///'")
+        );
+        assert_eq!(error("v 0 0 0\nf 1/x 1 1\n").line, 2);
+    }
+}
@@ -1,16 +1,55 @@
 //! TODO: Add module documentation when this project grows.
 
+pub mod cache;
+pub mod compute;
+pub mod diagnostic;
+pub mod preprocessor;
+pub mod reflection;
+pub mod reload;
+pub mod spirv;
+pub mod uniform;
+
+use crate::{error::Error, gl_ext};
 use gl;
 use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
-use std::{collections::HashMap, fs, ffi::CString};
+use cache::ProgramCache;
+use log::{debug, warn};
+use preprocessor::{Preprocessor, Source};
+use reflection::{DataType, ProgramReflection};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{c_void, CString},
+    fmt,
+    fs,
+    path::PathBuf,
+    ptr,
+};
+use uniform::Uniform;
 
 /// The types of shader.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum ShaderType {
     Vertex = gl::VERTEX_SHADER,
     Fragment = gl::FRAGMENT_SHADER,
     Geometry = gl::GEOMETRY_SHADER,
+    TessControl = gl::TESS_CONTROL_SHADER,
+    TessEvaluation = gl::TESS_EVALUATION_SHADER,
+    Compute = gl::COMPUTE_SHADER,
+}
+
+impl fmt::Display for ShaderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ShaderType::Vertex => "VERTEX",
+            ShaderType::Fragment => "FRAGMENT",
+            ShaderType::Geometry => "GEOMETRY",
+            ShaderType::TessControl => "TESS_CONTROL",
+            ShaderType::TessEvaluation => "TESS_EVALUATION",
+            ShaderType::Compute => "COMPUTE",
+        };
+        f.write_str(name)
+    }
 }
 
 /// A handle to a [Shader Object](https://www.khronos.org/opengl/wiki/GLSL_Object#Shader_objects).
@@ -90,22 +129,148 @@ impl Shader {
     }
 
     /// Creates and compiles a shader of the given type from a source string.
-    pub fn from_source(st: ShaderType, source: &str) -> Result<Self, String> {
-        let shader = Self::new(st).ok_or_else(|| String::from("Couldn't allocate new shader"))?;
+    pub fn from_source(st: ShaderType, source: &str) -> Result<Self, Error> {
+        Self::from_named_source(st, &format!("<{}>", st), source)
+    }
+
+    /// Same as [`Shader::from_source`], `name` is the name given to the source (usually
+    /// its file path) in the diagnostics reported when compilation fails.
+    pub fn from_named_source(st: ShaderType, name: &str, source: &str) -> Result<Self, Error> {
+        Self::compile_source(st, source, |diagnostic| diagnostic.render(name, source))
+    }
+
+    /// Creates and compiles a shader from the output of the [`Preprocessor`].
+    ///
+    /// Diagnostics are reported against the original file (root or included) they come
+    /// from.
+    pub fn from_preprocessed(st: ShaderType, source: &Source) -> Result<Self, Error> {
+        Self::compile_source(st, &source.code, |diagnostic| {
+            let file = diagnostic
+                .file
+                .and_then(|file| source.files.get(file as usize));
+            match file {
+                Some(file) => diagnostic.render(&file.path.display().to_string(), &file.contents),
+                None => diagnostic.render(&format!("<{}>", st), &source.code),
+            }
+        })
+    }
+
+    fn compile_source<F>(st: ShaderType, source: &str, render: F) -> Result<Self, Error>
+    where
+        F: Fn(&diagnostic::Diagnostic) -> String,
+    {
+        let shader = Self::new(st).ok_or(Error::Allocation { object: "shader" })?;
         shader.set_source(source);
         shader.compile();
-        if shader.is_successfully_compiled() {
-            Ok(shader)
-        } else {
-            let log = shader.get_info_log();
-            shader.delete();
-            let st: &str = match st {
-                ShaderType::Vertex => "VERTEX",
-                ShaderType::Fragment => "FRAGMENT",
-                ShaderType::Geometry => "GEOMETRY",
-            };
-            Err(format!("Failed to compile {} shader:\n{}", st, log))
+        shader.check_compiled(st, render)
+    }
+
+    /// Creates a shader from a SPIR-V module, specializing the entry point `entry_point`.
+    ///
+    /// `specialization_constants` are `(constant_id, value)` pairs, the value being the
+    /// raw bits of the constant (e.g. `f32::to_bits` for floats). The module is validated
+    /// on the CPU before being given to the driver, see [`spirv::validate`].
+    ///
+    /// Requires OpenGL 4.6 or the `ARB_gl_spirv` extension, and the function pointers to
+    /// be loaded with [`crate::load_with`].
+    pub fn from_spirv(
+        st: ShaderType,
+        bytes: &[u8],
+        entry_point: &str,
+        specialization_constants: &[(u32, u32)],
+    ) -> Result<Self, Error> {
+        spirv::validate(bytes, st, entry_point)
+            .map_err(|message| Error::InvalidSpirv { stage: st, message })?;
+        if !gl_ext::has_specialize_shader() {
+            return Err(Error::Unsupported { feature: "SPIR-V shaders" });
+        }
+        let entry_point = CString::new(entry_point).map_err(|_| Error::InvalidSpirv {
+            stage: st,
+            message: format!("entry point {:?} contains a NUL byte", entry_point),
+        })?;
+
+        let shader = Self::new(st).ok_or(Error::Allocation { object: "shader" })?;
+        let (indices, values): (Vec<u32>, Vec<u32>) =
+            specialization_constants.iter().copied().unzip();
+        unsafe {
+            gl::ShaderBinary(
+                1,
+                &shader.id,
+                gl_ext::SHADER_BINARY_FORMAT_SPIR_V,
+                bytes.as_ptr() as *const c_void,
+                bytes.len() as i32,
+            );
+            gl_ext::specialize_shader(
+                shader.id,
+                entry_point.as_ptr(),
+                indices.len() as u32,
+                indices.as_ptr(),
+                values.as_ptr(),
+            );
+        }
+        let name = format!("<{} SPIR-V>", st);
+        shader.check_compiled(st, |diagnostic| diagnostic.render(&name, ""))
+    }
+
+    /// Turns a failed compilation (or specialization) into an [`Error::Compile`], using
+    /// `render` to show each diagnostic of the info log.
+    fn check_compiled<F>(self, st: ShaderType, render: F) -> Result<Self, Error>
+    where
+        F: Fn(&diagnostic::Diagnostic) -> String,
+    {
+        if self.is_successfully_compiled() {
+            return Ok(self);
+        }
+        let log = self.get_info_log();
+        self.delete();
+        let diagnostics = diagnostic::parse_log(&log);
+        let report = diagnostics
+            .iter()
+            .map(render)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Err(Error::Compile {
+            stage: st,
+            diagnostics,
+            report,
+        })
+    }
+}
+
+/// The files a [`ShaderProgram`] was built from, kept so it can be rebuilt when they change.
+#[derive(Clone, Debug)]
+pub struct ProgramSources {
+    preprocessor: Preprocessor,
+    stages: Vec<(ShaderType, PathBuf)>,
+    files: Vec<PathBuf>,
+    cache: Option<ProgramCache>,
+}
+
+impl ProgramSources {
+    /// Returns the path of the root file of every stage.
+    pub fn get_stages(&self) -> &[(ShaderType, PathBuf)] {
+        &self.stages
+    }
+
+    /// Returns every file the program depends on, including the included ones.
+    pub fn get_files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Preprocesses the stages again and returns the files they depend on now, e.g. after
+    /// an edit added an `#include`. Files that can't be read are returned too, and each
+    /// stage stops at its first preprocessing error.
+    pub(crate) fn collect_files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for (_, path) in &self.stages {
+            let _ = self.preprocessor.process_with(path, |path| {
+                if !files.iter().any(|file| file == path) {
+                    files.push(path.to_path_buf());
+                }
+                fs::read_to_string(path)
+            });
         }
+        files
     }
 }
 
@@ -113,6 +278,10 @@ impl Shader {
 pub struct ShaderProgram {
     id: u32,
     locations: HashMap<String, i32>,
+    sources: Option<ProgramSources>,
+    reflection: ProgramReflection,
+    /// Uniforms already warned about being set with the wrong type.
+    mistyped: HashSet<String>,
 }
 
 impl ShaderProgram {
@@ -126,6 +295,9 @@ impl ShaderProgram {
             Some(Self {
                 id: program_id,
                 locations: HashMap::new(),
+                sources: None,
+                reflection: ProgramReflection::default(),
+                mistyped: HashSet::new(),
             })
         } else {
             None
@@ -180,14 +352,14 @@ impl ShaderProgram {
         unsafe { gl::UseProgram(0) };
     }
 
-    /// Marks the program for deletion.
+    /// Marks the program for deletion, the same as dropping it.
     ///
     /// Note: This **does not** immediately delete the program. If the program
     /// is currently in use it won't be deleted until it's not the active
     /// program. When a program is finally deleted and attached shaders are
     /// unattached.
     pub fn delete(self) {
-        unsafe { gl::DeleteProgram(self.id) };
+        drop(self);
     }
 
     /// Takes a vertex shader source string and a fragment shader source string
@@ -200,30 +372,28 @@ impl ShaderProgram {
         vert_src: &str,
         frag_src: &str,
         geom_src: Option<&str>,
-    ) -> Result<Self, String> {
-        let program = Self::new().ok_or_else(|| String::from("Couldn't allocate a program"))?;
-        let vert_shader = Shader::from_source(ShaderType::Vertex, vert_src)?;
-        let frag_shader = Shader::from_source(ShaderType::Fragment, frag_src)?;
-        program.attach_shader(&vert_shader);
-        program.attach_shader(&frag_shader);
+    ) -> Result<Self, Error> {
+        let mut stages = vec![
+            (ShaderType::Vertex, "<VERTEX>", vert_src),
+            (ShaderType::Fragment, "<FRAGMENT>", frag_src),
+        ];
         if let Some(source) = geom_src {
-            let geom_shader = Shader::from_source(ShaderType::Geometry, source)?;
-            program.attach_shader(&geom_shader);
-        }
-        program.link();
-        if program.is_linked_successfully() {
-            Ok(program)
-        } else {
-            let log = program.get_info_log();
-            program.delete();
-            Err(format!("Failed to link PROGRAM:\n{}", log))
+            stages.push((ShaderType::Geometry, "<GEOMETRY>", source));
         }
+        Self::build(
+            stages
+                .into_iter()
+                .map(|(st, name, source)| Shader::from_named_source(st, name, source)),
+            false,
+        )
     }
 
     /// Takes two file paths and possibly a third containing GLSL code and
     /// compiles them into a ShaderProgram. If a problem occurs during this
-    /// process an error is returned with a string that has information
-    /// about the error.
+    /// process an error is returned describing what went wrong.
+    ///
+    /// The files go through the [`Preprocessor`] first, so they may `#include` other
+    /// files relative to themselves.
     ///
     /// This is the preferred way to create a shader program in most cases. It's
     /// just less error prone than doing all the steps yourself.
@@ -231,127 +401,411 @@ impl ShaderProgram {
         vert_path: &str,
         frag_path: &str,
         geom_path: Option<&str>,
-    ) -> Result<Self, String> {
-        let vert_src = extract_source(vert_path)?;
-        let frag_src = extract_source(frag_path)?;
+    ) -> Result<Self, Error> {
+        Self::from_file_with(&Preprocessor::new(), vert_path, frag_path, geom_path)
+    }
 
+    /// Same as [`ShaderProgram::from_file`] but every stage gets the given
+    /// `(name, value)` pairs as `#define`s.
+    pub fn from_file_with_defines(
+        vert_path: &str,
+        frag_path: &str,
+        geom_path: Option<&str>,
+        defines: &[(&str, &str)],
+    ) -> Result<Self, Error> {
+        let mut preprocessor = Preprocessor::new();
+        for (name, value) in defines {
+            preprocessor.define(name, value);
+        }
+        Self::from_file_with(&preprocessor, vert_path, frag_path, geom_path)
+    }
+
+    /// Same as [`ShaderProgram::from_file`] but the files are preprocessed by the given
+    /// `preprocessor`.
+    pub fn from_file_with(
+        preprocessor: &Preprocessor,
+        vert_path: &str,
+        frag_path: &str,
+        geom_path: Option<&str>,
+    ) -> Result<Self, Error> {
+        let mut stages = vec![
+            (ShaderType::Vertex, PathBuf::from(vert_path)),
+            (ShaderType::Fragment, PathBuf::from(frag_path)),
+        ];
         if let Some(path) = geom_path {
-            let geom_src = extract_source(path)?;
-            Self::from_source(&vert_src, &frag_src, Some(&geom_src))
-        } else {
-            Self::from_source(&vert_src, &frag_src, None)
+            stages.push((ShaderType::Geometry, PathBuf::from(path)));
         }
+        Self::build_files(preprocessor, stages, None)
     }
 
-    /// Getter.
-    pub fn get_id(&self) -> u32 {
-        self.id
+    /// Builds a program from any combination of stages, given as `(type, source)` pairs.
+    ///
+    /// Use it for programs with tessellation stages or for compute programs (see also
+    /// [`ComputeProgram`](compute::ComputeProgram)).
+    pub fn from_stage_sources(stages: &[(ShaderType, &str)]) -> Result<Self, Error> {
+        Self::build(
+            stages.iter().map(|&(st, source)| {
+                Shader::from_named_source(st, &format!("<{}>", st), source)
+            }),
+            false,
+        )
+    }
+
+    /// Builds a program from any combination of stages, given as `(type, path)` pairs. The
+    /// files are preprocessed by `preprocessor`.
+    pub fn from_stage_files(
+        preprocessor: &Preprocessor,
+        stages: &[(ShaderType, &str)],
+    ) -> Result<Self, Error> {
+        let stages = stages
+            .iter()
+            .map(|&(st, path)| (st, PathBuf::from(path)))
+            .collect();
+        Self::build_files(preprocessor, stages, None)
+    }
+
+    /// Same as [`ShaderProgram::from_stage_files`] but the linked program binary is stored
+    /// in `cache`, and loaded from it instead of compiling the sources when they haven't
+    /// changed.
+    pub fn from_stage_files_cached(
+        preprocessor: &Preprocessor,
+        stages: &[(ShaderType, &str)],
+        cache: &ProgramCache,
+    ) -> Result<Self, Error> {
+        let stages = stages
+            .iter()
+            .map(|&(st, path)| (st, PathBuf::from(path)))
+            .collect();
+        Self::build_files(preprocessor, stages, Some(cache))
+    }
+
+    /// Builds a program from SPIR-V modules given as `(type, module)` pairs, using `main` as
+    /// the entry point of every stage. See [`Shader::from_spirv`].
+    pub fn from_spirv(stages: &[(ShaderType, &[u8])]) -> Result<Self, Error> {
+        Self::build(
+            stages
+                .iter()
+                .map(|&(st, bytes)| Shader::from_spirv(st, bytes, "main", &[])),
+            false,
+        )
     }
 
-    fn get_uniform_location(&mut self, name: &str) -> i32 {
-        let name_cstr = CString::new(name).unwrap();
-        match self.locations.get(name) {
-            Some(&location) => location,
+    /// Builds a program from the files at the given paths, remembering them (and the files
+    /// they include) in the program's [`ProgramSources`].
+    fn build_files(
+        preprocessor: &Preprocessor,
+        stages: Vec<(ShaderType, PathBuf)>,
+        cache: Option<&ProgramCache>,
+    ) -> Result<Self, Error> {
+        let sources = stages
+            .iter()
+            .map(|(st, path)| Ok((*st, preprocessor.process_file(path)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut files = Vec::new();
+        for file in sources.iter().flat_map(|(_, source)| &source.files) {
+            if !files.contains(&file.path) {
+                files.push(file.path.clone());
+            }
+        }
+
+        let key = cache.map(|cache| {
+            let codes: Vec<_> = sources
+                .iter()
+                .map(|(st, source)| (*st, source.code.as_str()))
+                .collect();
+            cache.key(&codes)
+        });
+        let cached = cache
+            .zip(key)
+            .and_then(|(cache, key)| cache.load(key))
+            .and_then(|(format, binary)| Self::from_binary(format, &binary));
+        let mut program = match cached {
+            Some(program) => program,
             None => {
-                let location = unsafe {
-                    gl::GetUniformLocation(self.id, name_cstr.as_ptr())
-                };
-                if location < 0 {
-                    panic!("Failed to get location of uniform '{}'", name);
-                } else {
-                    self.locations.insert(name.to_string(), location);
+                let program = Self::build(
+                    sources
+                        .iter()
+                        .map(|(st, source)| Shader::from_preprocessed(*st, source)),
+                    cache.is_some(),
+                )?;
+                if let (Some(cache), Some(key)) = (cache, key) {
+                    program.store_binary(cache, key);
                 }
-                location
+                program
             },
-        }
+        };
+        program.sources = Some(ProgramSources {
+            preprocessor: preprocessor.clone(),
+            stages,
+            files,
+            cache: cache.cloned(),
+        });
+        Ok(program)
     }
 
-    /// Uploads a uniform to the current ShaderProgram
-    pub fn set_uniform_1i(&mut self, name: &str, value: i32) {
+    /// Creates a program from a binary retrieved with `glGetProgramBinary`. Returns `None`
+    /// if the driver rejects it.
+    fn from_binary(format: u32, binary: &[u8]) -> Option<Self> {
+        let mut program = Self::new()?;
         unsafe {
-            gl::Uniform1i(self.get_uniform_location(name), value);
+            gl::ProgramBinary(
+                program.id,
+                format,
+                binary.as_ptr() as *const c_void,
+                binary.len() as i32,
+            );
+        }
+        if program.is_linked_successfully() {
+            program.reflection = ProgramReflection::query(program.id);
+            Some(program)
+        } else {
+            debug!("Cached program binary rejected by the driver");
+            program.delete();
+            None
         }
     }
 
-    /// Uploads a uniform to the current ShaderProgram
-    pub fn set_uniform_1i_arr(&mut self, name: &str, values: &[i32]) {
+    /// Retrieves the binary of the linked program and stores it in `cache`.
+    fn store_binary(&self, cache: &ProgramCache, key: u64) {
+        let mut len = 0;
+        unsafe { gl::GetProgramiv(self.id, gl::PROGRAM_BINARY_LENGTH, &mut len) };
+        if len <= 0 {
+            return;
+        }
+        let mut binary = vec![0_u8; len as usize];
+        let mut format = 0;
+        let mut written_len = 0;
         unsafe {
-            gl::Uniform1iv(
-                self.get_uniform_location(name),
-                values.len() as i32,
-                values.as_ptr(),
+            gl::GetProgramBinary(
+                self.id,
+                len,
+                &mut written_len,
+                &mut format,
+                binary.as_mut_ptr() as *mut c_void,
+            );
+        }
+        binary.truncate(written_len as usize);
+        if let Err(err) = cache.store(key, format, &binary) {
+            warn!(
+                "Failed to store program binary in {}: {}",
+                cache.get_dir().display(),
+                err
             );
         }
     }
 
-    /// Uploads a uniform to the current ShaderProgram.
-    pub fn set_uniform_1f(&mut self, name: &str, value: f32) {
-        unsafe {
-            gl::Uniform1f(self.get_uniform_location(name), value);
-        }
+    /// Builds a new program from the same files this one was built from.
+    ///
+    /// Returns `None` if the program wasn't created from files.
+    pub fn rebuild(&self) -> Option<Result<Self, Error>> {
+        let sources = self.sources.as_ref()?;
+        Some(Self::build_files(
+            &sources.preprocessor,
+            sources.stages.clone(),
+            sources.cache.as_ref(),
+        ))
+    }
+
+    /// Returns the files this program was built from, if it was built from files.
+    pub fn get_sources(&self) -> Option<&ProgramSources> {
+        self.sources.as_ref()
     }
 
-    /// Uploads a uniform to the current ShaderProgram.
-    pub fn set_uniform_2f(&mut self, name: &str, v: Vec2) {
+    /// Checks if the program has a shader of type `stage`. Programs loaded from a
+    /// [`ProgramCache`] have no shaders attached, so their files are checked instead.
+    pub fn has_stage(&self, stage: ShaderType) -> bool {
+        if let Some(sources) = &self.sources {
+            return sources.stages.iter().any(|(st, _)| *st == stage);
+        }
+        let mut count = 0;
+        unsafe { gl::GetProgramiv(self.id, gl::ATTACHED_SHADERS, &mut count) };
+        let mut shaders = vec![0; count as usize];
         unsafe {
-            gl::Uniform2f(self.get_uniform_location(name), v.x, v.y);
+            gl::GetAttachedShaders(self.id, count, ptr::null_mut(), shaders.as_mut_ptr())
+        };
+        shaders.into_iter().any(|shader| {
+            let mut ty = 0;
+            unsafe { gl::GetShaderiv(shader, gl::SHADER_TYPE, &mut ty) };
+            ty as u32 == stage as u32
+        })
+    }
+
+    /// Links the given shaders into a program. The shaders are created lazily, stopping at
+    /// the first one that fails.
+    ///
+    /// `retrievable` sets `GL_PROGRAM_BINARY_RETRIEVABLE_HINT` before linking, which some
+    /// drivers need to return the binary of the program for a [`ProgramCache`].
+    fn build<I>(shaders: I, retrievable: bool) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = Result<Shader, Error>>,
+    {
+        let mut program = Self::new().ok_or(Error::Allocation { object: "program" })?;
+        let mut compiled = Vec::new();
+        for shader in shaders {
+            match shader {
+                Ok(shader) => compiled.push(shader),
+                Err(err) => {
+                    compiled.into_iter().for_each(Shader::delete);
+                    program.delete();
+                    return Err(err);
+                },
+            }
+        }
+        for shader in &compiled {
+            program.attach_shader(shader);
+        }
+        if retrievable {
+            unsafe {
+                gl::ProgramParameteri(
+                    program.id,
+                    gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    gl::TRUE as i32,
+                )
+            };
+        }
+        program.link();
+        // The shaders are only really deleted once the program is.
+        compiled.into_iter().for_each(Shader::delete);
+        if program.is_linked_successfully() {
+            program.reflection = ProgramReflection::query(program.id);
+            Ok(program)
+        } else {
+            let log = program.get_info_log();
+            program.delete();
+            Err(Error::Link { log })
         }
     }
 
-    /// Uploads a uniform to the current ShaderProgram.
-    pub fn set_uniform_3f(&mut self, name: &str, v: Vec3) {
-        unsafe {
-            gl::Uniform3f(self.get_uniform_location(name), v.x, v.y, v.z);
+    /// Returns the active resources (uniforms, blocks and inputs) of the program.
+    ///
+    /// Empty for programs that were linked manually with [`ShaderProgram::link`].
+    pub fn get_reflection(&self) -> &ProgramReflection {
+        &self.reflection
+    }
+
+    /// Getter.
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// Checks if the program has an active uniform with the given name. Names with interior
+    /// NUL bytes never match.
+    pub fn has_uniform(&mut self, name: &str) -> bool {
+        if self.locations.contains_key(name) {
+            return true;
+        }
+        let name_cstr = match CString::new(name) {
+            Ok(name_cstr) => name_cstr,
+            Err(_) => return false,
+        };
+        let location = unsafe { gl::GetUniformLocation(self.id, name_cstr.as_ptr()) };
+        if location >= 0 {
+            self.locations.insert(name.to_string(), location);
         }
+        location >= 0
     }
 
-    /// Uploads a uniform to the current ShaderProgram.
-    pub fn set_uniform_4f(&mut self, name: &str, v: Vec4) {
-        unsafe {
-            gl::Uniform4f(self.get_uniform_location(name), v.x, v.y, v.z, v.w);
+    /// Assigns the uniform block called `name` to the uniform buffer binding point
+    /// `binding`, see [`UniformBuffer::bind`](crate::buffer::UniformBuffer::bind).
+    /// Names with interior NUL bytes are reported as missing blocks.
+    ///
+    /// The assignment is lost if the program is rebuilt, e.g. by a
+    /// [`ShaderReloader`](reload::ShaderReloader).
+    pub fn bind_uniform_block(&mut self, name: &str, binding: u32) -> Result<(), Error> {
+        let index = match CString::new(name) {
+            Ok(name_cstr) => unsafe { gl::GetUniformBlockIndex(self.id, name_cstr.as_ptr()) },
+            Err(_) => gl::INVALID_INDEX,
+        };
+        if index == gl::INVALID_INDEX {
+            return Err(Error::MissingBlock { name: name.to_string() });
         }
+        unsafe { gl::UniformBlockBinding(self.id, index, binding) };
+        Ok(())
     }
 
-    /// Uploads a uniform to the current ShaderProgram.
-    pub fn set_uniform_1f_arr(&mut self, name: &str, values: &[f32]) {
-        unsafe {
-            gl::Uniform1fv(
-                self.get_uniform_location(name),
-                values.len() as i32,
-                values.as_ptr(),
-            );
+    /// Returns the location of the uniform, warning (once per uniform) if `data_type`
+    /// doesn't match the type the uniform has in the shader.
+    fn get_uniform_location(&mut self, name: &str, data_type: DataType) -> Result<i32, Error> {
+        if !self.has_uniform(name) {
+            return Err(Error::MissingUniform { name: name.to_string() });
         }
+        if let Some(uniform) = self.reflection.get_uniform(name) {
+            if !uniform.data_type.accepts(data_type) && !self.mistyped.contains(name) {
+                warn!(
+                    "Setting uniform '{}' of type {} with a {} value",
+                    name, uniform.data_type, data_type
+                );
+                self.mistyped.insert(name.to_string());
+            }
+        }
+        Ok(self.locations[name])
+    }
+
+    /// Uploads a uniform to this program. It doesn't need to be bound.
+    ///
+    /// `value` can be any [`Uniform`]: scalars, `bool`s, glam vectors and matrices, and
+    /// slices or arrays of them for array uniforms.
+    ///
+    /// ```ignore
+    /// program.set("u_model", &model)?;
+    /// program.set("u_lights", &[Vec3::X, Vec3::Y])?;
+    /// ```
+    pub fn set<T: Uniform + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        let location = self.get_uniform_location(name, T::DATA_TYPE)?;
+        value.set_uniform(self.id, location);
+        Ok(())
+    }
+
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
+    pub fn set_uniform_1i(&mut self, name: &str, value: i32) -> Result<(), Error> {
+        self.set(name, &value)
+    }
+
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
+    pub fn set_uniform_1i_arr(&mut self, name: &str, values: &[i32]) -> Result<(), Error> {
+        self.set(name, values)
+    }
+
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
+    pub fn set_uniform_1f(&mut self, name: &str, value: f32) -> Result<(), Error> {
+        self.set(name, &value)
     }
 
-    /// Uploads a uniform to the currently bound `ShaderProgram`.
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
+    pub fn set_uniform_2f(&mut self, name: &str, v: Vec2) -> Result<(), Error> {
+        self.set(name, &v)
+    }
+
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
+    pub fn set_uniform_3f(&mut self, name: &str, v: Vec3) -> Result<(), Error> {
+        self.set(name, &v)
+    }
+
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
+    pub fn set_uniform_4f(&mut self, name: &str, v: Vec4) -> Result<(), Error> {
+        self.set(name, &v)
+    }
+
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
+    pub fn set_uniform_1f_arr(&mut self, name: &str, values: &[f32]) -> Result<(), Error> {
+        self.set(name, values)
+    }
+
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
     /// `GLSL`'s matrix data type is column-major.
-    pub fn set_uniform_mat3(&mut self, name: &str, matrix: &Mat3) {
-        unsafe {
-            gl::UniformMatrix3fv(
-                self.get_uniform_location(name),
-                1,
-                gl::FALSE,
-                (matrix as *const Mat3) as *const f32,
-            );
-        }
+    pub fn set_uniform_mat3(&mut self, name: &str, matrix: &Mat3) -> Result<(), Error> {
+        self.set(name, matrix)
     }
 
-    /// Uploads a uniform to the currently bound `ShaderProgram`.
+    /// Uploads a uniform to this ShaderProgram, see [`ShaderProgram::set`].
     /// `GLSL`'s matrix data type is column-major.
-    pub fn set_uniform_mat4(&mut self, name: &str, matrix: &Mat4) {
-        // NOTE(Miguel): The `glam` crate stores matrices in column-major order, just how
-        // OpenGL expects them, so no further transformations are needed.
-        unsafe {
-            gl::UniformMatrix4fv(
-                self.get_uniform_location(name),
-                1,
-                gl::FALSE,
-                (matrix as *const Mat4) as *const f32,
-            );
-        }
+    pub fn set_uniform_mat4(&mut self, name: &str, matrix: &Mat4) -> Result<(), Error> {
+        self.set(name, matrix)
     }
 }
 
-fn extract_source(path: &str) -> Result<String, String> {
-    fs::read_to_string(path).map_err(|_| format!("Failed to read file {}", path))
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteProgram(self.id) };
+    }
 }
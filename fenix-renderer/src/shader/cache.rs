@@ -0,0 +1,198 @@
+//! On-disk cache of linked program binaries.
+//!
+//! Linking a program from GLSL is slow, so after a successful link the binary produced by
+//! the driver (`glGetProgramBinary`) is stored in a cache directory. The next time the same
+//! sources are built the binary is loaded with `glProgramBinary` instead. Binaries are only
+//! valid for the driver that produced them, so the cache key includes the GL vendor,
+//! renderer and version strings; drivers may still reject a binary (e.g. after an update),
+//! in which case the program is compiled as usual.
+
+use super::ShaderType;
+use std::{
+    ffi::CStr,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+const MAGIC: &[u8; 4] = b"FXPB";
+const HEADER_LEN: usize = 8;
+
+/// A directory where program binaries are stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl ProgramCache {
+    /// Creates a cache that stores binaries in `dir`. The directory is created when the
+    /// first binary is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the directory of the cache.
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Computes the key of a program made of the given preprocessed stages for the
+    /// current driver.
+    pub fn key(&self, stages: &[(ShaderType, &str)]) -> u64 {
+        hash_key(&driver_id(), stages)
+    }
+
+    /// Returns the `(format, binary)` stored under `key`, if any.
+    pub fn load(&self, key: u64) -> Option<(u32, Vec<u8>)> {
+        let data = fs::read(self.path(key)).ok()?;
+        decode(&data).map(|(format, binary)| (format, binary.to_vec()))
+    }
+
+    /// Stores a program binary of the given format under `key`.
+    ///
+    /// The entry is written to a temporary file first and then renamed, so a crash while
+    /// writing never leaves a truncated binary behind.
+    pub fn store(&self, key: u64, format: u32, binary: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let temp = path.with_extension(format!("tmp{}", process::id()));
+        fs::write(&temp, encode(format, binary))
+            .and_then(|()| fs::rename(&temp, &path))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&temp);
+            })
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+}
+
+/// Returns a string identifying the driver of the current context.
+fn driver_id() -> String {
+    [gl::VENDOR, gl::RENDERER, gl::VERSION]
+        .iter()
+        .map(|&name| unsafe {
+            let string = gl::GetString(name);
+            if string.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(string as *const _)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Hashes the driver id and the stages with 64-bit FNV-1a.
+fn hash_key(driver: &str, stages: &[(ShaderType, &str)]) -> u64 {
+    let mut hash = Fnv1a::new();
+    hash.write(driver.as_bytes());
+    for &(st, source) in stages {
+        hash.write(&[0]);
+        hash.write(&(st as u32).to_le_bytes());
+        hash.write(source.as_bytes());
+    }
+    hash.finish()
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn encode(format: u32, binary: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(HEADER_LEN + binary.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&format.to_le_bytes());
+    data.extend_from_slice(binary);
+    data
+}
+
+fn decode(data: &[u8]) -> Option<(u32, &[u8])> {
+    if data.len() <= HEADER_LEN || &data[..4] != MAGIC {
+        return None;
+    }
+    let format = u32::from_le_bytes(data[4..8].try_into().unwrap());
+    Some((format, &data[HEADER_LEN..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_matches_reference_values() {
+        let hash = |bytes: &[u8]| {
+            let mut hash = Fnv1a::new();
+            hash.write(bytes);
+            hash.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn key_depends_on_driver_stages_and_sources() {
+        let stages = [
+            (ShaderType::Vertex, "void main() {}"),
+            (ShaderType::Fragment, "void main() {}"),
+        ];
+        let key = hash_key("Mesa\nllvmpipe\n4.5", &stages);
+        assert_eq!(key, hash_key("Mesa\nllvmpipe\n4.5", &stages));
+        assert_ne!(key, hash_key("Mesa\nllvmpipe\n4.6", &stages));
+        assert_ne!(
+            key,
+            hash_key("Mesa\nllvmpipe\n4.5", &[stages[1], stages[0]])
+        );
+        assert_ne!(
+            key,
+            hash_key(
+                "Mesa\nllvmpipe\n4.5",
+                &[stages[0], (ShaderType::Fragment, "void main() { }")]
+            )
+        );
+    }
+
+    #[test]
+    fn stored_entries_are_loaded() {
+        let dir = std::env::temp_dir().join(format!("fenix-cache-{}", process::id()));
+        let cache = ProgramCache::new(&dir);
+        cache.store(42, 0x8E21, &[1, 2, 3]).unwrap();
+        cache.store(42, 0x8E21, &[4, 5]).unwrap();
+        assert_eq!(cache.load(42), Some((0x8E21, vec![4, 5])));
+        assert_eq!(cache.load(43), None);
+
+        let files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, [format!("{:016x}.bin", 42)]);
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let data = encode(0x8E21, &[1, 2, 3]);
+        assert_eq!(decode(&data), Some((0x8E21, &[1, 2, 3][..])));
+        assert_eq!(decode(b"FXPB\x00\x00\x00\x00"), None);
+        assert_eq!(decode(b"XXXX\x00\x00\x00\x00\x01"), None);
+    }
+}
@@ -0,0 +1,87 @@
+//! Programs made of a single compute shader.
+
+use super::{preprocessor::Preprocessor, ShaderProgram, ShaderType};
+use crate::error::Error;
+
+/// A linked program with a single [compute shader](https://www.khronos.org/opengl/wiki/Compute_Shader).
+///
+/// Uniforms are set through the underlying [`ShaderProgram`], see
+/// [`ComputeProgram::get_program_mut`]. Results written to buffers or images must be made
+/// visible to later commands with [`memory_barrier`](crate::buffer::memory_barrier).
+pub struct ComputeProgram {
+    program: ShaderProgram,
+    work_group_size: [u32; 3],
+}
+
+impl ComputeProgram {
+    /// Compiles a compute shader from a source string and links it into a program.
+    pub fn from_source(source: &str) -> Result<Self, Error> {
+        ShaderProgram::from_stage_sources(&[(ShaderType::Compute, source)])
+            .and_then(Self::from_program)
+    }
+
+    /// Compiles the compute shader at `path` and links it into a program. The file goes
+    /// through the [`Preprocessor`] first.
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        ShaderProgram::from_stage_files(&Preprocessor::new(), &[(ShaderType::Compute, path)])
+            .and_then(Self::from_program)
+    }
+
+    /// Wraps a program that was linked from a compute shader, e.g. one rebuilt by a
+    /// [`ShaderReloader`](super::reload::ShaderReloader).
+    ///
+    /// Fails with [`Error::MissingStage`] if the program isn't linked or has no compute
+    /// shader, in which case the program is dropped.
+    pub fn from_program(program: ShaderProgram) -> Result<Self, Error> {
+        if !program.is_linked_successfully() || !program.has_stage(ShaderType::Compute) {
+            return Err(Error::MissingStage {
+                stage: ShaderType::Compute,
+            });
+        }
+        let mut size = [0; 3];
+        unsafe {
+            gl::GetProgramiv(
+                program.get_id(),
+                gl::COMPUTE_WORK_GROUP_SIZE,
+                size.as_mut_ptr(),
+            )
+        };
+        Ok(Self {
+            program,
+            work_group_size: size.map(|n| n as u32),
+        })
+    }
+
+    /// Returns the `local_size_x/y/z` declared by the compute shader.
+    pub fn get_work_group_size(&self) -> [u32; 3] {
+        self.work_group_size
+    }
+
+    /// Returns the underlying program.
+    pub fn get_program(&self) -> &ShaderProgram {
+        &self.program
+    }
+
+    /// Returns the underlying program, needed to set uniforms.
+    pub fn get_program_mut(&mut self) -> &mut ShaderProgram {
+        &mut self.program
+    }
+
+    /// Takes back the underlying program.
+    pub fn into_program(self) -> ShaderProgram {
+        self.program
+    }
+
+    /// Binds the program and launches `x * y * z` work groups.
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        self.program.bind();
+        unsafe { gl::DispatchCompute(x, y, z) };
+    }
+
+    /// Binds the program and launches enough work groups to cover `x * y * z`
+    /// invocations, rounding up to whole work groups.
+    pub fn dispatch_invocations(&self, x: u32, y: u32, z: u32) {
+        let [size_x, size_y, size_z] = self.work_group_size.map(|n| n.max(1));
+        self.dispatch(x.div_ceil(size_x), y.div_ceil(size_y), z.div_ceil(size_z));
+    }
+}
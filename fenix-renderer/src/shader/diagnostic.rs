@@ -0,0 +1,337 @@
+//! Parsing of GLSL compiler info logs into structured diagnostics.
+//!
+//! Every driver formats its log differently, the ones understood here are:
+//!
+//! - NVIDIA: `0(12) : error C1008: undefined variable "foo"`
+//! - Mesa: `0:12(5): error: `foo' undeclared`
+//! - AMD (also Intel and Apple): `ERROR: 0:12: 'foo' : undeclared identifier`
+//!
+//! Lines in any other format are kept as [`Severity::Info`] diagnostics without location so
+//! no information from the log is lost.
+
+use std::fmt::{self, Write};
+
+/// How serious a diagnostic is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A single message of a compiler info log.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The source string number (the file index of `#line` directives).
+    pub file: Option<u32>,
+    /// Line number, starting at 1.
+    pub line: Option<u32>,
+    /// Column number, starting at 1. Only reported by some drivers.
+    pub column: Option<u32>,
+    pub severity: Severity,
+    /// Driver specific error code, e.g. `C1008` on NVIDIA.
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic rustc-style, showing the offending line of `source` with a
+    /// caret under the reported column (or under the whole line when there's no column).
+    ///
+    /// `name` is the name shown for the source, usually its file path.
+    pub fn render(&self, name: &str, source: &str) -> String {
+        let mut out = String::new();
+        match &self.code {
+            Some(code) => write!(out, "{}[{}]: {}", self.severity, code, self.message),
+            None => write!(out, "{}: {}", self.severity, self.message),
+        }
+        .unwrap();
+
+        let line_num = match self.line {
+            Some(line) => line,
+            None => return out,
+        };
+        let gutter = line_num.to_string().len();
+        match self.column {
+            Some(column) => write!(out, "\n{:gutter$}--> {}:{}:{}", "", name, line_num, column),
+            None => write!(out, "\n{:gutter$}--> {}:{}", "", name, line_num),
+        }
+        .unwrap();
+
+        let line = match source.lines().nth(line_num.saturating_sub(1) as usize) {
+            Some(line) => line,
+            None => return out,
+        };
+        let (start, len) = match self.column {
+            Some(column) => (column.saturating_sub(1) as usize, 1),
+            None => {
+                let trimmed = line.trim_start();
+                (line.len() - trimmed.len(), trimmed.trim_end().len().max(1))
+            },
+        };
+        write!(out, "\n{:gutter$} |", "").unwrap();
+        write!(out, "\n{} | {}", line_num, line).unwrap();
+        write!(out, "\n{:gutter$} | {:start$}{}", "", "", "^".repeat(len)).unwrap();
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(file), Some(line)) = (self.file, self.line) {
+            match self.column {
+                Some(column) => write!(f, "{}:{}:{}: ", file, line, column)?,
+                None => write!(f, "{}:{}: ", file, line)?,
+            }
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Parses a compiler info log into a list of diagnostics. Empty lines are skipped.
+pub fn parse_log(log: &str) -> Vec<Diagnostic> {
+    log.lines()
+        .map(|line| line.trim_end_matches('\0').trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            parse_mesa(line)
+                .or_else(|| parse_nvidia(line))
+                .or_else(|| parse_amd(line))
+                .unwrap_or_else(|| Diagnostic {
+                    file: None,
+                    line: None,
+                    column: None,
+                    severity: Severity::Info,
+                    code: None,
+                    message: line.to_string(),
+                })
+        })
+        .collect()
+}
+
+fn parse_severity(s: &str) -> Option<Severity> {
+    match s.to_ascii_lowercase().as_str() {
+        "error" => Some(Severity::Error),
+        "warning" => Some(Severity::Warning),
+        "info" | "note" => Some(Severity::Info),
+        _ => None,
+    }
+}
+
+/// `0:12(5): error: message`
+fn parse_mesa(line: &str) -> Option<Diagnostic> {
+    let (file, rest) = line.split_once(':')?;
+    let (line_num, rest) = rest.split_once('(')?;
+    let (column, rest) = rest.split_once("): ")?;
+    let (severity, message) = rest.split_once(": ")?;
+    Some(Diagnostic {
+        file: Some(file.parse().ok()?),
+        line: Some(line_num.parse().ok()?),
+        column: Some(column.parse().ok()?),
+        severity: parse_severity(severity)?,
+        code: None,
+        message: message.to_string(),
+    })
+}
+
+/// `0(12) : error C1008: message`
+fn parse_nvidia(line: &str) -> Option<Diagnostic> {
+    let (file, rest) = line.split_once('(')?;
+    let (line_num, rest) = rest.split_once(") : ")?;
+    let (kind, message) = rest.split_once(": ")?;
+    let (severity, code) = match kind.split_once(' ') {
+        Some((severity, code)) => (severity, Some(code.to_string())),
+        None => (kind, None),
+    };
+    Some(Diagnostic {
+        file: Some(file.parse().ok()?),
+        line: Some(line_num.parse().ok()?),
+        column: None,
+        severity: parse_severity(severity)?,
+        code,
+        message: message.to_string(),
+    })
+}
+
+/// `ERROR: 0:12: message`, or `ERROR: message` for messages without location.
+fn parse_amd(line: &str) -> Option<Diagnostic> {
+    let (severity, rest) = line.split_once(": ")?;
+    let severity = parse_severity(severity)?;
+    let location = rest.split_once(": ").and_then(|(location, message)| {
+        let (file, line_num) = location.split_once(':')?;
+        Some((file.parse().ok()?, line_num.parse().ok()?, message))
+    });
+    Some(match location {
+        Some((file, line_num, message)) => Diagnostic {
+            file: Some(file),
+            line: Some(line_num),
+            column: None,
+            severity,
+            code: None,
+            message: message.to_string(),
+        },
+        None => Diagnostic {
+            file: None,
+            line: None,
+            column: None,
+            severity,
+            code: None,
+            message: rest.to_string(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "#version 450 core\n\
+                          \n\
+                          out vec4 frag_color;\n\
+                          \n\
+                          void main()\n\
+                          {\n\
+                          \x20   frag_color = colr;\n\
+                          }\n";
+
+    #[test]
+    fn parses_nvidia_log() {
+        let log = "0(7) : error C1008: undefined variable \"colr\"\n\
+                   0(3) : warning C7050: \"frag_color\" might be used before being initialized\n";
+        let diagnostics = parse_log(log);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    file: Some(0),
+                    line: Some(7),
+                    column: None,
+                    severity: Severity::Error,
+                    code: Some("C1008".to_string()),
+                    message: "undefined variable \"colr\"".to_string(),
+                },
+                Diagnostic {
+                    file: Some(0),
+                    line: Some(3),
+                    column: None,
+                    severity: Severity::Warning,
+                    code: Some("C7050".to_string()),
+                    message: "\"frag_color\" might be used before being initialized".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_mesa_log() {
+        let log = "0:7(18): error: `colr' undeclared\n\
+                   0:7(5): error: value of type error cannot be assigned to variable `frag_color'\n\
+                   \0";
+        let diagnostics = parse_log(log);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0],
+            Diagnostic {
+                file: Some(0),
+                line: Some(7),
+                column: Some(18),
+                severity: Severity::Error,
+                code: None,
+                message: "`colr' undeclared".to_string(),
+            }
+        );
+        assert_eq!(diagnostics[1].column, Some(5));
+    }
+
+    #[test]
+    fn parses_amd_log() {
+        let log = "ERROR: 0:7: 'colr' : undeclared identifier \n\
+                   WARNING: 1:2: 'foo' : extension not supported\n\
+                   ERROR: 1 compilation errors.  No code generated.\n\n";
+        let diagnostics = parse_log(log);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    file: Some(0),
+                    line: Some(7),
+                    column: None,
+                    severity: Severity::Error,
+                    code: None,
+                    message: "'colr' : undeclared identifier".to_string(),
+                },
+                Diagnostic {
+                    file: Some(1),
+                    line: Some(2),
+                    column: None,
+                    severity: Severity::Warning,
+                    code: None,
+                    message: "'foo' : extension not supported".to_string(),
+                },
+                Diagnostic {
+                    file: None,
+                    line: None,
+                    column: None,
+                    severity: Severity::Error,
+                    code: None,
+                    message: "1 compilation errors.  No code generated.".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_lines() {
+        let diagnostics = parse_log("Internal compiler error\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Info);
+        assert_eq!(diagnostics[0].line, None);
+        assert_eq!(diagnostics[0].message, "Internal compiler error");
+    }
+
+    #[test]
+    fn renders_caret_under_column() {
+        let diagnostic = &parse_log("0:7(18): error: `colr' undeclared")[0];
+        assert_eq!(
+            diagnostic.render("shader.frag", SOURCE),
+            "error: `colr' undeclared\n \
+             --> shader.frag:7:18\n  \
+             |\n\
+             7 |     frag_color = colr;\n  \
+             |                  ^"
+        );
+    }
+
+    #[test]
+    fn renders_whole_line_without_column() {
+        let diagnostic = &parse_log("0(7) : error C1008: undefined variable \"colr\"")[0];
+        assert_eq!(
+            diagnostic.render("shader.frag", SOURCE),
+            "error[C1008]: undefined variable \"colr\"\n \
+             --> shader.frag:7\n  \
+             |\n\
+             7 |     frag_color = colr;\n  \
+             |     ^^^^^^^^^^^^^^^^^^"
+        );
+    }
+
+    #[test]
+    fn renders_message_without_location() {
+        let diagnostic = &parse_log("ERROR: 1 compilation errors.  No code generated.")[0];
+        assert_eq!(
+            diagnostic.render("shader.frag", SOURCE),
+            "error: 1 compilation errors.  No code generated."
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! A small GLSL preprocessor that runs before the driver's one.
+//!
+//! It resolves `#include "path"` directives (relative to the including file), injects
+//! `#define`s given from Rust right after the `#version` directive and emits `#line`
+//! directives so the line numbers reported by the compiler point to the original files.
+//! The source string number of every `#line` is the index of the file in
+//! [`Source::files`].
+
+use crate::error::Error;
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// A file that took part in building a [`Source`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+/// The result of preprocessing a shader file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Source {
+    /// The code to hand to the GLSL compiler.
+    pub code: String,
+    /// The root file followed by every included file, in order of first inclusion.
+    pub files: Vec<SourceFile>,
+}
+
+/// Resolves includes and injects defines into GLSL sources.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preprocessor {
+    defines: Vec<(String, String)>,
+}
+
+impl Preprocessor {
+    /// Creates a preprocessor without defines.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a `#define name value` to the processed sources.
+    pub fn define(&mut self, name: &str, value: &str) -> &mut Self {
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Returns the defines added to the preprocessor.
+    pub fn get_defines(&self) -> &[(String, String)] {
+        &self.defines
+    }
+
+    /// Preprocesses the shader at `path`, reading it and its includes from disk.
+    pub fn process_file(&self, path: impl AsRef<Path>) -> Result<Source, Error> {
+        self.process_with(path, |path| fs::read_to_string(path))
+    }
+
+    /// Preprocesses the shader at `path`, reading it and its includes with `read`.
+    pub fn process_with<F>(&self, path: impl AsRef<Path>, mut read: F) -> Result<Source, Error>
+    where
+        F: FnMut(&Path) -> io::Result<String>,
+    {
+        let path = normalize(path.as_ref());
+        let contents = read(&path).map_err(|source| Error::Io {
+            path: path.clone(),
+            source,
+        })?;
+
+        let mut source = Source {
+            code: String::new(),
+            files: vec![SourceFile {
+                path: path.clone(),
+                contents,
+            }],
+        };
+        let mut stack = vec![path];
+        self.expand(0, &mut source, &mut stack, &mut read)?;
+        Ok(source)
+    }
+
+    /// Appends the contents of `source.files[index]` to `source.code`, expanding includes.
+    fn expand<F>(
+        &self,
+        index: usize,
+        source: &mut Source,
+        stack: &mut Vec<PathBuf>,
+        read: &mut F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Path) -> io::Result<String>,
+    {
+        let contents = source.files[index].contents.clone();
+        let dir = source.files[index]
+            .path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let is_root = index == 0;
+        let mut found_version = false;
+
+        if is_root && !contents.lines().any(|line| is_directive(line, "version")) {
+            self.write_defines(&mut source.code);
+            source.code.push_str("#line 1 0\n");
+        }
+
+        for (i, line) in contents.lines().enumerate() {
+            let line_num = i + 1;
+
+            if is_directive(line, "version") {
+                if is_root && !found_version {
+                    found_version = true;
+                    source.code.push_str(line);
+                    source.code.push('\n');
+                    self.write_defines(&mut source.code);
+                    source
+                        .code
+                        .push_str(&format!("#line {} {}\n", line_num + 1, index));
+                } else {
+                    // Only the root file decides the version, an included file may still
+                    // declare one so it can be compiled on its own.
+                    source.code.push('\n');
+                }
+                continue;
+            }
+
+            if let Some(include) = directive_argument(line, "include") {
+                let include = parse_include_path(include).ok_or_else(|| Error::Preprocess {
+                    path: source.files[index].path.clone(),
+                    line: line_num,
+                    message: format!("malformed include directive '{}'", line.trim()),
+                })?;
+                let path = normalize(&dir.join(include));
+
+                if let Some(start) = stack.iter().position(|p| *p == path) {
+                    let cycle: Vec<_> = stack[start..]
+                        .iter()
+                        .chain([&path])
+                        .map(|p| p.display().to_string())
+                        .collect();
+                    return Err(Error::Preprocess {
+                        path: source.files[index].path.clone(),
+                        line: line_num,
+                        message: format!("include cycle: {}", cycle.join(" -> ")),
+                    });
+                }
+
+                let included = match source.files.iter().position(|file| file.path == path) {
+                    Some(included) => included,
+                    None => {
+                        let contents = read(&path).map_err(|err| Error::Io {
+                            path: path.clone(),
+                            source: err,
+                        })?;
+                        source.files.push(SourceFile {
+                            path: path.clone(),
+                            contents,
+                        });
+                        source.files.len() - 1
+                    },
+                };
+
+                source.code.push_str(&format!("#line 1 {}\n", included));
+                stack.push(path);
+                self.expand(included, source, stack, read)?;
+                stack.pop();
+                source
+                    .code
+                    .push_str(&format!("#line {} {}\n", line_num + 1, index));
+                continue;
+            }
+
+            source.code.push_str(line);
+            source.code.push('\n');
+        }
+
+        Ok(())
+    }
+
+    fn write_defines(&self, code: &mut String) {
+        for (name, value) in &self.defines {
+            code.push_str(&format!("#define {} {}\n", name, value));
+        }
+    }
+}
+
+/// Returns the text after `#name` if `line` is that directive.
+fn directive_argument<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix(name)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) || rest.starts_with('"') {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+fn is_directive(line: &str, name: &str) -> bool {
+    directive_argument(line, name).is_some()
+}
+
+/// Extracts `path` from `"path"`, ignoring a trailing `//` comment.
+fn parse_include_path(arg: &str) -> Option<&str> {
+    let rest = arg.strip_prefix('"')?;
+    let (path, rest) = rest.split_once('"')?;
+    let rest = rest.trim();
+    if path.is_empty() || !(rest.is_empty() || rest.starts_with("//")) {
+        return None;
+    }
+    Some(path)
+}
+
+/// Removes `.` components and resolves `..` ones without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn process(
+        preprocessor: &Preprocessor,
+        files: &[(&str, &str)],
+        root: &str,
+    ) -> Result<Source, Error> {
+        let files: HashMap<PathBuf, String> = files
+            .iter()
+            .map(|(path, contents)| (PathBuf::from(path), contents.to_string()))
+            .collect();
+        preprocessor.process_with(root, |path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        })
+    }
+
+    #[test]
+    fn injects_defines_after_version() {
+        let mut preprocessor = Preprocessor::new();
+        preprocessor
+            .define("MAX_LIGHTS", "4")
+            .define("USE_FOG", "1");
+        let source = process(
+            &preprocessor,
+            &[("shader.frag", "#version 450 core\nvoid main() {}\n")],
+            "shader.frag",
+        )
+        .unwrap();
+        assert_eq!(
+            source.code,
+            "#version 450 core\n\
+             #define MAX_LIGHTS 4\n\
+             #define USE_FOG 1\n\
+             #line 2 0\n\
+             void main() {}\n"
+        );
+    }
+
+    #[test]
+    fn resolves_includes_relative_to_the_including_file() {
+        let source = process(
+            &Preprocessor::new(),
+            &[
+                (
+                    "shaders/main.frag",
+                    "#version 450\n#include \"lib/light.glsl\"\nvoid main() {}\n",
+                ),
+                (
+                    "shaders/lib/light.glsl",
+                    "#include \"../common.glsl\"\nvec3 light() {}\n",
+                ),
+                ("shaders/common.glsl", "const float PI = 3.14;\n"),
+            ],
+            "shaders/main.frag",
+        )
+        .unwrap();
+        assert_eq!(
+            source.code,
+            "#version 450\n\
+             #line 2 0\n\
+             #line 1 1\n\
+             #line 1 2\n\
+             const float PI = 3.14;\n\
+             #line 2 1\n\
+             vec3 light() {}\n\
+             #line 3 0\n\
+             void main() {}\n"
+        );
+        let paths: Vec<_> = source.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("shaders/main.frag"),
+                PathBuf::from("shaders/lib/light.glsl"),
+                PathBuf::from("shaders/common.glsl"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reuses_the_index_of_files_included_twice() {
+        let source = process(
+            &Preprocessor::new(),
+            &[
+                ("a.glsl", "#include \"b.glsl\"\n#include \"b.glsl\"\n"),
+                ("b.glsl", "float b;\n"),
+            ],
+            "a.glsl",
+        )
+        .unwrap();
+        assert_eq!(source.files.len(), 2);
+        assert_eq!(
+            source.code,
+            "#line 1 0\n#line 1 1\nfloat b;\n#line 2 0\n#line 1 1\nfloat b;\n#line 3 0\n"
+        );
+    }
+
+    #[test]
+    fn detects_include_cycles() {
+        let err = process(
+            &Preprocessor::new(),
+            &[
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "\n#include \"./a.glsl\"\n"),
+            ],
+            "a.glsl",
+        )
+        .unwrap_err();
+        match err {
+            Error::Preprocess {
+                path,
+                line,
+                message,
+            } => {
+                assert_eq!(path, PathBuf::from("b.glsl"));
+                assert_eq!(line, 2);
+                assert_eq!(message, "include cycle: a.glsl -> b.glsl -> a.glsl");
+            },
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn reports_missing_includes() {
+        let err = process(
+            &Preprocessor::new(),
+            &[("a.glsl", "#include \"b.glsl\"\n")],
+            "a.glsl",
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Io { path, .. } if path == Path::new("b.glsl")));
+    }
+
+    #[test]
+    fn rejects_malformed_includes() {
+        let err = process(
+            &Preprocessor::new(),
+            &[("a.glsl", "#include <b.glsl>\n")],
+            "a.glsl",
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Preprocess { line: 1, .. }));
+    }
+}
@@ -0,0 +1,518 @@
+//! Introspection of the active resources of a linked program.
+//!
+//! Uses the [program interface query](https://www.khronos.org/opengl/wiki/Program_Introspection#Interface_query)
+//! API of OpenGL 4.3 to list the uniforms, uniform blocks, shader storage blocks and vertex
+//! inputs of a program.
+
+use std::fmt;
+
+/// The GLSL type of a uniform or vertex input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Double,
+    DVec2,
+    DVec3,
+    DVec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Bool,
+    BVec2,
+    BVec3,
+    BVec4,
+    Mat2,
+    Mat3,
+    Mat4,
+    Mat2x3,
+    Mat2x4,
+    Mat3x2,
+    Mat3x4,
+    Mat4x2,
+    Mat4x3,
+    DMat2,
+    DMat3,
+    DMat4,
+    Sampler1D,
+    Sampler2D,
+    Sampler3D,
+    SamplerCube,
+    Sampler2DShadow,
+    Sampler2DArray,
+    SamplerCubeArray,
+    Sampler2DMultisample,
+    ISampler2D,
+    USampler2D,
+    Image2D,
+    Image3D,
+    ImageCube,
+    Image2DArray,
+    /// Any other type, with the raw OpenGL enum.
+    Other(u32),
+}
+
+impl DataType {
+    /// Converts the value of a `GL_TYPE` query.
+    pub fn from_gl(ty: u32) -> Self {
+        match ty {
+            gl::FLOAT => DataType::Float,
+            gl::FLOAT_VEC2 => DataType::Vec2,
+            gl::FLOAT_VEC3 => DataType::Vec3,
+            gl::FLOAT_VEC4 => DataType::Vec4,
+            gl::DOUBLE => DataType::Double,
+            gl::DOUBLE_VEC2 => DataType::DVec2,
+            gl::DOUBLE_VEC3 => DataType::DVec3,
+            gl::DOUBLE_VEC4 => DataType::DVec4,
+            gl::INT => DataType::Int,
+            gl::INT_VEC2 => DataType::IVec2,
+            gl::INT_VEC3 => DataType::IVec3,
+            gl::INT_VEC4 => DataType::IVec4,
+            gl::UNSIGNED_INT => DataType::UInt,
+            gl::UNSIGNED_INT_VEC2 => DataType::UVec2,
+            gl::UNSIGNED_INT_VEC3 => DataType::UVec3,
+            gl::UNSIGNED_INT_VEC4 => DataType::UVec4,
+            gl::BOOL => DataType::Bool,
+            gl::BOOL_VEC2 => DataType::BVec2,
+            gl::BOOL_VEC3 => DataType::BVec3,
+            gl::BOOL_VEC4 => DataType::BVec4,
+            gl::FLOAT_MAT2 => DataType::Mat2,
+            gl::FLOAT_MAT3 => DataType::Mat3,
+            gl::FLOAT_MAT4 => DataType::Mat4,
+            gl::FLOAT_MAT2x3 => DataType::Mat2x3,
+            gl::FLOAT_MAT2x4 => DataType::Mat2x4,
+            gl::FLOAT_MAT3x2 => DataType::Mat3x2,
+            gl::FLOAT_MAT3x4 => DataType::Mat3x4,
+            gl::FLOAT_MAT4x2 => DataType::Mat4x2,
+            gl::FLOAT_MAT4x3 => DataType::Mat4x3,
+            gl::DOUBLE_MAT2 => DataType::DMat2,
+            gl::DOUBLE_MAT3 => DataType::DMat3,
+            gl::DOUBLE_MAT4 => DataType::DMat4,
+            gl::SAMPLER_1D => DataType::Sampler1D,
+            gl::SAMPLER_2D => DataType::Sampler2D,
+            gl::SAMPLER_3D => DataType::Sampler3D,
+            gl::SAMPLER_CUBE => DataType::SamplerCube,
+            gl::SAMPLER_2D_SHADOW => DataType::Sampler2DShadow,
+            gl::SAMPLER_2D_ARRAY => DataType::Sampler2DArray,
+            gl::SAMPLER_CUBE_MAP_ARRAY => DataType::SamplerCubeArray,
+            gl::SAMPLER_2D_MULTISAMPLE => DataType::Sampler2DMultisample,
+            gl::INT_SAMPLER_2D => DataType::ISampler2D,
+            gl::UNSIGNED_INT_SAMPLER_2D => DataType::USampler2D,
+            gl::IMAGE_2D => DataType::Image2D,
+            gl::IMAGE_3D => DataType::Image3D,
+            gl::IMAGE_CUBE => DataType::ImageCube,
+            gl::IMAGE_2D_ARRAY => DataType::Image2DArray,
+            other => DataType::Other(other),
+        }
+    }
+
+    /// Checks if the type is an opaque sampler or image type, which are set as `int`
+    /// uniforms holding a texture unit.
+    pub fn is_opaque(self) -> bool {
+        matches!(
+            self,
+            DataType::Sampler1D
+                | DataType::Sampler2D
+                | DataType::Sampler3D
+                | DataType::SamplerCube
+                | DataType::Sampler2DShadow
+                | DataType::Sampler2DArray
+                | DataType::SamplerCubeArray
+                | DataType::Sampler2DMultisample
+                | DataType::ISampler2D
+                | DataType::USampler2D
+                | DataType::Image2D
+                | DataType::Image3D
+                | DataType::ImageCube
+                | DataType::Image2DArray
+        )
+    }
+
+    /// Checks if a uniform of this type can be set with the `glUniform*` function meant
+    /// for `value`. Booleans can be set with both the float and the integer functions and
+    /// samplers and images with the integer ones.
+    pub fn accepts(self, value: DataType) -> bool {
+        match (self, value) {
+            (a, b) if a == b => true,
+            (DataType::Bool, DataType::Int | DataType::UInt | DataType::Float) => true,
+            (DataType::BVec2, DataType::IVec2 | DataType::UVec2 | DataType::Vec2) => true,
+            (DataType::BVec3, DataType::IVec3 | DataType::UVec3 | DataType::Vec3) => true,
+            (DataType::BVec4, DataType::IVec4 | DataType::UVec4 | DataType::Vec4) => true,
+            (ty, DataType::Int) => ty.is_opaque(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DataType::Float => "float",
+            DataType::Vec2 => "vec2",
+            DataType::Vec3 => "vec3",
+            DataType::Vec4 => "vec4",
+            DataType::Double => "double",
+            DataType::DVec2 => "dvec2",
+            DataType::DVec3 => "dvec3",
+            DataType::DVec4 => "dvec4",
+            DataType::Int => "int",
+            DataType::IVec2 => "ivec2",
+            DataType::IVec3 => "ivec3",
+            DataType::IVec4 => "ivec4",
+            DataType::UInt => "uint",
+            DataType::UVec2 => "uvec2",
+            DataType::UVec3 => "uvec3",
+            DataType::UVec4 => "uvec4",
+            DataType::Bool => "bool",
+            DataType::BVec2 => "bvec2",
+            DataType::BVec3 => "bvec3",
+            DataType::BVec4 => "bvec4",
+            DataType::Mat2 => "mat2",
+            DataType::Mat3 => "mat3",
+            DataType::Mat4 => "mat4",
+            DataType::Mat2x3 => "mat2x3",
+            DataType::Mat2x4 => "mat2x4",
+            DataType::Mat3x2 => "mat3x2",
+            DataType::Mat3x4 => "mat3x4",
+            DataType::Mat4x2 => "mat4x2",
+            DataType::Mat4x3 => "mat4x3",
+            DataType::DMat2 => "dmat2",
+            DataType::DMat3 => "dmat3",
+            DataType::DMat4 => "dmat4",
+            DataType::Sampler1D => "sampler1D",
+            DataType::Sampler2D => "sampler2D",
+            DataType::Sampler3D => "sampler3D",
+            DataType::SamplerCube => "samplerCube",
+            DataType::Sampler2DShadow => "sampler2DShadow",
+            DataType::Sampler2DArray => "sampler2DArray",
+            DataType::SamplerCubeArray => "samplerCubeArray",
+            DataType::Sampler2DMultisample => "sampler2DMS",
+            DataType::ISampler2D => "isampler2D",
+            DataType::USampler2D => "usampler2D",
+            DataType::Image2D => "image2D",
+            DataType::Image3D => "image3D",
+            DataType::ImageCube => "imageCube",
+            DataType::Image2DArray => "image2DArray",
+            DataType::Other(ty) => return write!(f, "<0x{:04X}>", ty),
+        };
+        f.write_str(name)
+    }
+}
+
+/// An active uniform, either from the default block or from a uniform block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UniformInfo {
+    /// The name as reported by OpenGL, arrays end in `[0]`.
+    pub name: String,
+    pub data_type: DataType,
+    /// Number of elements, 1 for non-array uniforms.
+    pub array_size: u32,
+    /// The location of the uniform, `None` for uniforms inside a block.
+    pub location: Option<i32>,
+    /// Index in [`ProgramReflection::get_uniform_blocks`] of the block of the uniform.
+    pub block: Option<usize>,
+    /// Offset in bytes from the start of the block, `None` outside of blocks.
+    pub offset: Option<u32>,
+}
+
+/// An active uniform block or shader storage block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub name: String,
+    /// The binding point the block is currently assigned to.
+    pub binding: u32,
+    /// Minimum size in bytes of the buffer backing the block.
+    pub data_size: u32,
+    /// Names of the active variables inside the block.
+    pub members: Vec<String>,
+}
+
+/// An active input of the first stage of the program, usually the vertex shader.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputInfo {
+    pub name: String,
+    pub data_type: DataType,
+    pub array_size: u32,
+    /// The location of the input, `None` for built-ins like `gl_VertexID`.
+    pub location: Option<u32>,
+}
+
+/// The active resources of a linked program.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramReflection {
+    uniforms: Vec<UniformInfo>,
+    uniform_blocks: Vec<BlockInfo>,
+    storage_blocks: Vec<BlockInfo>,
+    inputs: Vec<InputInfo>,
+}
+
+impl ProgramReflection {
+    /// Queries the active resources of the linked program `program`.
+    pub fn query(program: u32) -> Self {
+        let uniforms = (0..resource_count(program, gl::UNIFORM))
+            .map(|index| {
+                let [ty, array_size, location, block, offset] = resource_properties(
+                    program,
+                    gl::UNIFORM,
+                    index,
+                    [
+                        gl::TYPE,
+                        gl::ARRAY_SIZE,
+                        gl::LOCATION,
+                        gl::BLOCK_INDEX,
+                        gl::OFFSET,
+                    ],
+                );
+                UniformInfo {
+                    name: resource_name(program, gl::UNIFORM, index),
+                    data_type: DataType::from_gl(ty as u32),
+                    array_size: array_size as u32,
+                    location: (location >= 0).then_some(location),
+                    block: (block >= 0).then_some(block as usize),
+                    offset: (block >= 0).then_some(offset as u32),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let uniform_blocks = query_blocks(program, gl::UNIFORM_BLOCK, |index| {
+            uniforms[index as usize].name.clone()
+        });
+        let storage_blocks = query_blocks(program, gl::SHADER_STORAGE_BLOCK, |index| {
+            resource_name(program, gl::BUFFER_VARIABLE, index as u32)
+        });
+
+        let inputs = (0..resource_count(program, gl::PROGRAM_INPUT))
+            .map(|index| {
+                let [ty, array_size, location] = resource_properties(
+                    program,
+                    gl::PROGRAM_INPUT,
+                    index,
+                    [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION],
+                );
+                InputInfo {
+                    name: resource_name(program, gl::PROGRAM_INPUT, index),
+                    data_type: DataType::from_gl(ty as u32),
+                    array_size: array_size as u32,
+                    location: (location >= 0).then_some(location as u32),
+                }
+            })
+            .collect();
+
+        Self {
+            uniforms,
+            uniform_blocks,
+            storage_blocks,
+            inputs,
+        }
+    }
+
+    /// Returns every active uniform, including the ones inside uniform blocks.
+    pub fn get_uniforms(&self) -> &[UniformInfo] {
+        &self.uniforms
+    }
+
+    /// Returns the active uniform called `name`. For arrays both `name` and `name[0]` are
+    /// accepted.
+    pub fn get_uniform(&self, name: &str) -> Option<&UniformInfo> {
+        self.uniforms.iter().find(|uniform| {
+            uniform.name == name
+                || uniform
+                    .name
+                    .strip_suffix("[0]")
+                    .is_some_and(|base| base == name)
+        })
+    }
+
+    /// Returns the active uniform blocks.
+    pub fn get_uniform_blocks(&self) -> &[BlockInfo] {
+        &self.uniform_blocks
+    }
+
+    /// Returns the active shader storage blocks.
+    pub fn get_storage_blocks(&self) -> &[BlockInfo] {
+        &self.storage_blocks
+    }
+
+    /// Returns the active inputs of the program.
+    pub fn get_inputs(&self) -> &[InputInfo] {
+        &self.inputs
+    }
+}
+
+fn query_blocks<F>(program: u32, interface: u32, member_name: F) -> Vec<BlockInfo>
+where
+    F: Fn(i32) -> String,
+{
+    (0..resource_count(program, interface))
+        .map(|index| {
+            let [binding, data_size, member_count] = resource_properties(
+                program,
+                interface,
+                index,
+                [
+                    gl::BUFFER_BINDING,
+                    gl::BUFFER_DATA_SIZE,
+                    gl::NUM_ACTIVE_VARIABLES,
+                ],
+            );
+            let mut members = vec![0; member_count as usize];
+            if member_count > 0 {
+                unsafe {
+                    gl::GetProgramResourceiv(
+                        program,
+                        interface,
+                        index,
+                        1,
+                        &gl::ACTIVE_VARIABLES,
+                        member_count,
+                        std::ptr::null_mut(),
+                        members.as_mut_ptr(),
+                    );
+                }
+            }
+            BlockInfo {
+                name: resource_name(program, interface, index),
+                binding: binding as u32,
+                data_size: data_size as u32,
+                members: members.into_iter().map(&member_name).collect(),
+            }
+        })
+        .collect()
+}
+
+fn resource_count(program: u32, interface: u32) -> u32 {
+    let mut count = 0;
+    unsafe { gl::GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count) };
+    count as u32
+}
+
+fn resource_name(program: u32, interface: u32, index: u32) -> String {
+    let mut max_len = 0;
+    unsafe { gl::GetProgramInterfaceiv(program, interface, gl::MAX_NAME_LENGTH, &mut max_len) };
+    let mut name: Vec<u8> = Vec::with_capacity(max_len as usize);
+    let mut written_len = 0;
+    unsafe {
+        gl::GetProgramResourceName(
+            program,
+            interface,
+            index,
+            max_len,
+            &mut written_len,
+            name.as_mut_ptr() as *mut i8,
+        );
+        name.set_len(written_len as usize);
+    }
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+fn resource_properties<const N: usize>(
+    program: u32,
+    interface: u32,
+    index: u32,
+    properties: [u32; N],
+) -> [i32; N] {
+    let mut values = [0; N];
+    unsafe {
+        gl::GetProgramResourceiv(
+            program,
+            interface,
+            index,
+            N as i32,
+            properties.as_ptr(),
+            N as i32,
+            std::ptr::null_mut(),
+            values.as_mut_ptr(),
+        );
+    }
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform(name: &str, data_type: DataType, array_size: u32, location: i32) -> UniformInfo {
+        UniformInfo {
+            name: name.to_string(),
+            data_type,
+            array_size,
+            location: Some(location),
+            block: None,
+            offset: None,
+        }
+    }
+
+    #[test]
+    fn gl_types_are_converted() {
+        assert_eq!(DataType::from_gl(gl::FLOAT_VEC3), DataType::Vec3);
+        assert_eq!(DataType::from_gl(gl::UNSIGNED_INT_VEC2), DataType::UVec2);
+        assert_eq!(DataType::from_gl(gl::BOOL_VEC4), DataType::BVec4);
+        assert_eq!(DataType::from_gl(gl::FLOAT_MAT3x4), DataType::Mat3x4);
+        assert_eq!(DataType::from_gl(gl::DOUBLE_MAT4), DataType::DMat4);
+        assert_eq!(DataType::from_gl(gl::SAMPLER_CUBE_MAP_ARRAY), DataType::SamplerCubeArray);
+        assert_eq!(DataType::from_gl(gl::IMAGE_2D_ARRAY), DataType::Image2DArray);
+        assert_eq!(
+            DataType::from_gl(gl::SAMPLER_BUFFER),
+            DataType::Other(gl::SAMPLER_BUFFER)
+        );
+    }
+
+    #[test]
+    fn booleans_accept_floats_and_integers() {
+        assert!(DataType::Bool.accepts(DataType::Bool));
+        assert!(DataType::Bool.accepts(DataType::Int));
+        assert!(DataType::Bool.accepts(DataType::UInt));
+        assert!(DataType::Bool.accepts(DataType::Float));
+        assert!(DataType::BVec3.accepts(DataType::Vec3));
+        assert!(DataType::BVec2.accepts(DataType::UVec2));
+        assert!(!DataType::BVec2.accepts(DataType::Vec3));
+        assert!(!DataType::Bool.accepts(DataType::Double));
+    }
+
+    #[test]
+    fn numbers_are_not_coerced() {
+        assert!(DataType::Float.accepts(DataType::Float));
+        assert!(!DataType::Float.accepts(DataType::Int));
+        assert!(!DataType::Int.accepts(DataType::UInt));
+        assert!(!DataType::UInt.accepts(DataType::Int));
+        assert!(!DataType::Vec4.accepts(DataType::IVec4));
+        assert!(!DataType::Mat4.accepts(DataType::Mat3));
+    }
+
+    #[test]
+    fn samplers_and_images_accept_texture_units() {
+        assert!(DataType::Sampler2D.accepts(DataType::Int));
+        assert!(DataType::USampler2D.accepts(DataType::Int));
+        assert!(DataType::Image3D.accepts(DataType::Int));
+        assert!(!DataType::Sampler2D.accepts(DataType::UInt));
+        assert!(!DataType::Sampler2D.accepts(DataType::Float));
+        assert!(!DataType::Int.is_opaque());
+    }
+
+    #[test]
+    fn arrays_are_found_with_and_without_index() {
+        let reflection = ProgramReflection {
+            uniforms: vec![
+                uniform("u_lights[0]", DataType::Vec3, 4, 0),
+                uniform("u_light", DataType::Float, 1, 4),
+                uniform("u_material.diffuse", DataType::Vec3, 1, 5),
+            ],
+            ..Default::default()
+        };
+        let location = |name| reflection.get_uniform(name).and_then(|u| u.location);
+        assert_eq!(location("u_lights"), Some(0));
+        assert_eq!(location("u_lights[0]"), Some(0));
+        assert_eq!(location("u_light"), Some(4));
+        assert_eq!(location("u_material.diffuse"), Some(5));
+        assert_eq!(location("u_lights[1]"), None);
+        assert_eq!(location("u_lights[0"), None);
+        assert_eq!(location("u_material"), None);
+    }
+}
@@ -0,0 +1,141 @@
+//! Hot-reloading of shader programs when their source files change on disk.
+//!
+//! There is no file watcher: changes are found by comparing the modification times of
+//! the files every time [`ShaderReloader::poll`] is called, which costs a `stat` per file.
+
+use super::ShaderProgram;
+use log::{error, info};
+use std::{
+    fs, mem,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Identifies a program owned by a [`ShaderReloader`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProgramHandle(usize);
+
+/// Modification times of the files a program was built from, used to tell when it must be
+/// rebuilt.
+pub(crate) struct SourceTimes(Vec<(PathBuf, Option<SystemTime>)>);
+
+impl SourceTimes {
+    pub(crate) fn new(program: &ShaderProgram) -> Self {
+        let files = program
+            .get_sources()
+            .map(|sources| sources.get_files().to_vec())
+            .unwrap_or_default();
+        Self::of_files(files)
+    }
+
+    fn of_files(files: Vec<PathBuf>) -> Self {
+        Self(
+            files
+                .into_iter()
+                .map(|path| {
+                    let time = modified_time(&path);
+                    (path, time)
+                })
+                .collect(),
+        )
+    }
+
+    pub(crate) fn is_outdated(&self) -> bool {
+        self.0
+            .iter()
+            .any(|(path, time)| modified_time(path) != *time)
+    }
+
+    /// Remembers the current files of a program that failed to rebuild and their times, so
+    /// they aren't outdated until modified again. The files are collected again because
+    /// the edit that broke the program may have added includes, which must be watched too.
+    pub(crate) fn update(&mut self, program: &ShaderProgram) {
+        if let Some(sources) = program.get_sources() {
+            *self = Self::of_files(sources.collect_files());
+        }
+    }
+}
+
+struct WatchedProgram {
+    program: ShaderProgram,
+    times: SourceTimes,
+}
+
+impl WatchedProgram {
+    fn new(program: ShaderProgram) -> Self {
+        let times = SourceTimes::new(&program);
+        Self { program, times }
+    }
+}
+
+/// Owns shader programs and rebuilds them when any of the files they were built from
+/// (including the `#include`d ones) is modified.
+///
+/// Changes are detected by polling the modification time of the files, so
+/// [`ShaderReloader::poll`] should be called regularly, e.g. once per frame. When a
+/// program fails to rebuild the error is logged and the last working program is kept.
+#[derive(Default)]
+pub struct ShaderReloader {
+    programs: Vec<WatchedProgram>,
+}
+
+impl ShaderReloader {
+    /// Creates a reloader without programs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes ownership of a program and starts watching its files.
+    ///
+    /// Programs that weren't built from files (see [`ShaderProgram::from_file`]) are kept
+    /// but never reloaded.
+    pub fn watch(&mut self, program: ShaderProgram) -> ProgramHandle {
+        self.programs.push(WatchedProgram::new(program));
+        ProgramHandle(self.programs.len() - 1)
+    }
+
+    /// Returns the current program of the handle.
+    pub fn get(&self, handle: ProgramHandle) -> &ShaderProgram {
+        &self.programs[handle.0].program
+    }
+
+    /// Returns the current program of the handle.
+    pub fn get_mut(&mut self, handle: ProgramHandle) -> &mut ShaderProgram {
+        &mut self.programs[handle.0].program
+    }
+
+    /// Rebuilds the programs whose files changed since the last poll and returns the
+    /// handles of the ones that were successfully replaced.
+    ///
+    /// The replaced programs are deleted, so programs that were bound must be bound again.
+    pub fn poll(&mut self) -> Vec<ProgramHandle> {
+        let mut reloaded = Vec::new();
+        for (index, watched) in self.programs.iter_mut().enumerate() {
+            if !watched.times.is_outdated() {
+                continue;
+            }
+            match watched.program.rebuild() {
+                Some(Ok(program)) => {
+                    info!("Reloaded shader program {:?}", ProgramHandle(index));
+                    let old = mem::replace(watched, WatchedProgram::new(program));
+                    old.program.delete();
+                    reloaded.push(ProgramHandle(index));
+                },
+                Some(Err(err)) => {
+                    error!("{}", err);
+                    // Remember the new times anyway, otherwise the broken program would be
+                    // rebuilt on every poll until the files are fixed.
+                    watched.times.update(&watched.program);
+                },
+                None => {},
+            }
+        }
+        reloaded
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
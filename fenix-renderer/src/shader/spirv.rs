@@ -0,0 +1,200 @@
+//! CPU-side validation of SPIR-V modules before they are handed to the driver.
+//!
+//! This isn't a full validator, it only checks the module header and that the requested
+//! entry point exists for the right stage, which catches the usual mistakes (truncated
+//! files, GLSL given as SPIR-V, wrong entry point or stage) with a readable error instead
+//! of a driver crash.
+
+use super::ShaderType;
+
+/// The first word of every SPIR-V module.
+pub const MAGIC: u32 = 0x0723_0203;
+
+const HEADER_WORDS: usize = 5;
+const OP_ENTRY_POINT: u32 = 15;
+
+/// The contents of a SPIR-V module header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// SPIR-V `(major, minor)` version of the module.
+    pub version: (u8, u8),
+    /// Id of the tool that generated the module.
+    pub generator: u32,
+    /// All the ids in the module are smaller than this.
+    pub bound: u32,
+}
+
+/// Returns the SPIR-V execution model of a shader stage.
+fn execution_model(st: ShaderType) -> u32 {
+    match st {
+        ShaderType::Vertex => 0,
+        ShaderType::TessControl => 1,
+        ShaderType::TessEvaluation => 2,
+        ShaderType::Geometry => 3,
+        ShaderType::Fragment => 4,
+        ShaderType::Compute => 5,
+    }
+}
+
+/// Checks that `bytes` is a SPIR-V module with an entry point called `entry_point` for the
+/// stage `st`, returning its header or a message describing the problem.
+pub fn validate(bytes: &[u8], st: ShaderType, entry_point: &str) -> Result<Header, String> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(format!(
+            "size ({} bytes) isn't a multiple of the word size",
+            bytes.len()
+        ));
+    }
+    let words: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    if words.len() < HEADER_WORDS {
+        return Err(format!("module of {} bytes is too small", bytes.len()));
+    }
+
+    match words[0] {
+        MAGIC => {},
+        magic if magic.swap_bytes() == MAGIC => {
+            return Err("module is big-endian, only little-endian modules are supported".into())
+        },
+        magic => return Err(format!("invalid magic number 0x{:08X}", magic)),
+    }
+    let version = words[1];
+    let (major, minor) = ((version >> 16) as u8, (version >> 8) as u8);
+    if version & 0xFF00_00FF != 0 || major != 1 {
+        return Err(format!("unsupported version 0x{:08X}", version));
+    }
+    if words[3] == 0 {
+        return Err("id bound is 0".into());
+    }
+    if words[4] != 0 {
+        return Err(format!("reserved header word is {}, expected 0", words[4]));
+    }
+
+    let model = execution_model(st);
+    let mut entry_points = Vec::new();
+    let mut i = HEADER_WORDS;
+    while i < words.len() {
+        let count = (words[i] >> 16) as usize;
+        let opcode = words[i] & 0xFFFF;
+        if count == 0 || i + count > words.len() {
+            return Err(format!("malformed instruction at word {}", i));
+        }
+        if opcode == OP_ENTRY_POINT && count >= 4 {
+            let name = parse_string(&words[i + 3..i + count]);
+            if words[i + 1] == model && name == entry_point {
+                return Ok(Header {
+                    version: (major, minor),
+                    generator: words[2],
+                    bound: words[3],
+                });
+            }
+            entry_points.push(name);
+        }
+        i += count;
+    }
+
+    if entry_points.is_empty() {
+        Err("module has no entry points".into())
+    } else {
+        Err(format!(
+            "no {} entry point called '{}' (found: {})",
+            st,
+            entry_point,
+            entry_points.join(", ")
+        ))
+    }
+}
+
+/// Decodes a nul terminated literal string.
+fn parse_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A module with the header and a single `OpEntryPoint Fragment %1 "main"`.
+    fn module() -> Vec<u32> {
+        vec![
+            MAGIC,
+            0x0001_0000,
+            0,
+            8,
+            0,
+            (5 << 16) | OP_ENTRY_POINT,
+            4,
+            1,
+            u32::from_le_bytes(*b"main"),
+            0,
+        ]
+    }
+
+    fn bytes(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn accepts_valid_module() {
+        let header = validate(&bytes(&module()), ShaderType::Fragment, "main").unwrap();
+        assert_eq!(
+            header,
+            Header {
+                version: (1, 0),
+                generator: 0,
+                bound: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let glsl = b"#version 450 core\nvoid main() {}\n\0\0\0";
+        assert!(validate(glsl, ShaderType::Fragment, "main")
+            .unwrap_err()
+            .starts_with("invalid magic number"));
+
+        let mut words = module();
+        words[0] = MAGIC.swap_bytes();
+        assert!(validate(&bytes(&words), ShaderType::Fragment, "main")
+            .unwrap_err()
+            .contains("big-endian"));
+
+        let mut words = module();
+        words[1] = 0x0002_0000;
+        assert!(validate(&bytes(&words), ShaderType::Fragment, "main").is_err());
+
+        let truncated = &bytes(&module())[..18];
+        assert!(validate(truncated, ShaderType::Fragment, "main").is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_instructions() {
+        let mut words = module();
+        words.pop();
+        assert_eq!(
+            validate(&bytes(&words), ShaderType::Fragment, "main").unwrap_err(),
+            "malformed instruction at word 5"
+        );
+    }
+
+    #[test]
+    fn checks_entry_point_name_and_stage() {
+        assert_eq!(
+            validate(&bytes(&module()), ShaderType::Vertex, "main").unwrap_err(),
+            "no VERTEX entry point called 'main' (found: main)"
+        );
+        assert!(validate(&bytes(&module()), ShaderType::Fragment, "frag_main").is_err());
+        assert_eq!(
+            validate(&bytes(&module()[..5]), ShaderType::Fragment, "main").unwrap_err(),
+            "module has no entry points"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Rust types that can be uploaded as uniforms with [`ShaderProgram::set`].
+//!
+//! [`ShaderProgram::set`]: super::ShaderProgram::set
+
+use super::reflection::DataType;
+use glam::{
+    DMat2, DMat3, DMat4, DVec2, DVec3, DVec4, IVec2, IVec3, IVec4, Mat2, Mat3, Mat4, UVec2, UVec3,
+    UVec4, Vec2, Vec3, Vec4,
+};
+use std::slice;
+
+/// A value that can be uploaded to a uniform of a program.
+pub trait Uniform {
+    /// The GLSL type of the uniform the value is meant for.
+    const DATA_TYPE: DataType;
+
+    /// Uploads the value to the uniform at `location` of `program` with the
+    /// `glProgramUniform*` functions, so the program doesn't need to be bound.
+    fn set_uniform(&self, program: u32, location: i32);
+}
+
+/// A [`Uniform`] that can also be uploaded as an array, through slices and arrays of it.
+pub trait UniformArray: Uniform + Sized {
+    /// Uploads `values` to the consecutive elements of the array uniform starting at
+    /// `location`.
+    fn set_uniform_array(values: &[Self], program: u32, location: i32);
+}
+
+impl<T: UniformArray> Uniform for [T] {
+    const DATA_TYPE: DataType = T::DATA_TYPE;
+
+    fn set_uniform(&self, program: u32, location: i32) {
+        T::set_uniform_array(self, program, location);
+    }
+}
+
+impl<T: UniformArray, const N: usize> Uniform for [T; N] {
+    const DATA_TYPE: DataType = T::DATA_TYPE;
+
+    fn set_uniform(&self, program: u32, location: i32) {
+        T::set_uniform_array(self, program, location);
+    }
+}
+
+macro_rules! impl_uniform {
+    ($($ty:ty => $data_type:ident, $function:ident($scalar:ty)),* $(,)?) => {
+        $(
+            impl Uniform for $ty {
+                const DATA_TYPE: DataType = DataType::$data_type;
+
+                fn set_uniform(&self, program: u32, location: i32) {
+                    Self::set_uniform_array(slice::from_ref(self), program, location);
+                }
+            }
+
+            impl UniformArray for $ty {
+                fn set_uniform_array(values: &[Self], program: u32, location: i32) {
+                    unsafe {
+                        gl::$function(
+                            program,
+                            location,
+                            values.len() as i32,
+                            values.as_ptr() as *const $scalar,
+                        );
+                    }
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_uniform_matrix {
+    ($($ty:ty => $data_type:ident, $function:ident($scalar:ty)),* $(,)?) => {
+        $(
+            impl Uniform for $ty {
+                const DATA_TYPE: DataType = DataType::$data_type;
+
+                fn set_uniform(&self, program: u32, location: i32) {
+                    Self::set_uniform_array(slice::from_ref(self), program, location);
+                }
+            }
+
+            impl UniformArray for $ty {
+                fn set_uniform_array(values: &[Self], program: u32, location: i32) {
+                    // The `glam` crate stores matrices in column-major order, just how OpenGL
+                    // expects them, so no transposition is needed.
+                    unsafe {
+                        gl::$function(
+                            program,
+                            location,
+                            values.len() as i32,
+                            gl::FALSE,
+                            values.as_ptr() as *const $scalar,
+                        );
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_uniform! {
+    f32 => Float, ProgramUniform1fv(f32),
+    Vec2 => Vec2, ProgramUniform2fv(f32),
+    Vec3 => Vec3, ProgramUniform3fv(f32),
+    Vec4 => Vec4, ProgramUniform4fv(f32),
+    f64 => Double, ProgramUniform1dv(f64),
+    DVec2 => DVec2, ProgramUniform2dv(f64),
+    DVec3 => DVec3, ProgramUniform3dv(f64),
+    DVec4 => DVec4, ProgramUniform4dv(f64),
+    i32 => Int, ProgramUniform1iv(i32),
+    IVec2 => IVec2, ProgramUniform2iv(i32),
+    IVec3 => IVec3, ProgramUniform3iv(i32),
+    IVec4 => IVec4, ProgramUniform4iv(i32),
+    u32 => UInt, ProgramUniform1uiv(u32),
+    UVec2 => UVec2, ProgramUniform2uiv(u32),
+    UVec3 => UVec3, ProgramUniform3uiv(u32),
+    UVec4 => UVec4, ProgramUniform4uiv(u32),
+}
+
+impl_uniform_matrix! {
+    Mat2 => Mat2, ProgramUniformMatrix2fv(f32),
+    Mat3 => Mat3, ProgramUniformMatrix3fv(f32),
+    Mat4 => Mat4, ProgramUniformMatrix4fv(f32),
+    DMat2 => DMat2, ProgramUniformMatrix2dv(f64),
+    DMat3 => DMat3, ProgramUniformMatrix3dv(f64),
+    DMat4 => DMat4, ProgramUniformMatrix4dv(f64),
+}
+
+impl Uniform for bool {
+    const DATA_TYPE: DataType = DataType::Bool;
+
+    fn set_uniform(&self, program: u32, location: i32) {
+        unsafe { gl::ProgramUniform1i(program, location, *self as i32) };
+    }
+}
+
+impl UniformArray for bool {
+    fn set_uniform_array(values: &[Self], program: u32, location: i32) {
+        let values: Vec<i32> = values.iter().map(|&value| value as i32).collect();
+        unsafe {
+            gl::ProgramUniform1iv(program, location, values.len() as i32, values.as_ptr());
+        }
+    }
+}
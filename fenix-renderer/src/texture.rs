@@ -1,7 +1,150 @@
 //! This module provides a simple interface to load an image to the GPU.
 
-use image::error::ImageError;
-use std::{cmp::PartialEq, ffi::c_void, ops::Drop};
+pub mod array;
+pub mod compressed;
+pub mod cube;
+pub mod format;
+pub mod loader;
+pub mod sampler;
+pub mod volume;
+
+use crate::{buffer::PixelUnpackBuffer, error::Error};
+use bytemuck::Zeroable;
+use format::{Conversion, PixelFormat};
+use image::{ColorType, DynamicImage, ImageBuffer, Luma, LumaA, Pixel, Rgb, Rgba};
+use sampler::SamplerState;
+use std::{cmp::PartialEq, ffi::c_void, mem, ops::Drop, path::Path};
+
+/// Options used to create a texture.
+///
+/// ```ignore
+/// let spec = TextureSpec::default()
+///     .mipmaps()
+///     .sampler(SamplerState::default().trilinear().anisotropy(8.0));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureSpec {
+    /// Number of mip levels, `None` allocates the full chain down to 1x1.
+    pub mip_levels: Option<u32>,
+    pub sampler: SamplerState,
+    /// Conversion of the pixels of images loaded from files.
+    pub conversion: Conversion,
+}
+
+impl Default for TextureSpec {
+    fn default() -> Self {
+        Self {
+            mip_levels: Some(1),
+            sampler: SamplerState::default(),
+            conversion: Conversion::Native,
+        }
+    }
+}
+
+impl TextureSpec {
+    /// Sets the number of mip levels.
+    pub fn mip_levels(mut self, levels: u32) -> Self {
+        self.mip_levels = Some(levels);
+        self
+    }
+
+    /// Allocates the full mip chain, which is generated when an image is loaded.
+    pub fn mipmaps(mut self) -> Self {
+        self.mip_levels = None;
+        self
+    }
+
+    /// Sets the sampler state of the texture.
+    pub fn sampler(mut self, sampler: SamplerState) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Sets the conversion of the pixels of images loaded from files.
+    pub fn conversion(mut self, conversion: Conversion) -> Self {
+        self.conversion = conversion;
+        self
+    }
+}
+
+/// Returns the number of levels of a full mip chain for a texture of the given size.
+pub fn mip_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+/// Operations shared by every texture type.
+pub trait Texture {
+    /// The OpenGL target of the texture, e.g. `GL_TEXTURE_CUBE_MAP`.
+    const TARGET: u32;
+
+    /// Returns the internal id of the texture used by OpenGL.
+    fn get_id(&self) -> u32;
+
+    /// Returns the size of the base level as `[width, height, depth]`. The depth is the
+    /// number of layers of array textures, 6 for cube maps and 1 for 2D textures.
+    fn get_size(&self) -> [u32; 3];
+
+    /// Binds the texture to the specified texture unit.
+    fn bind(&self, slot: u32) {
+        unsafe { gl::BindTextureUnit(slot, self.get_id()) };
+    }
+}
+
+/// Decodes an image and converts it to the layout it's uploaded with.
+pub(crate) fn load_image(
+    path: &str,
+    conversion: Conversion,
+) -> Result<(DynamicImage, PixelFormat), Error> {
+    let image = image::open(path).map_err(|source| Error::Image {
+        path: Path::new(path).to_path_buf(),
+        source,
+    })?;
+
+    let format = format::select_format(image.color(), conversion).ok_or_else(|| {
+        Error::UnsupportedImageFormat {
+            path: Path::new(path).to_path_buf(),
+            color_type: image.color(),
+        }
+    })?;
+    let image = match format.color_type {
+        color_type if color_type == image.color() => image,
+        image::ColorType::Rgba8 => DynamicImage::ImageRgba8(image.to_rgba8()),
+        image::ColorType::Rgba32F => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+        color_type => unreachable!("No conversion to {:?}", color_type),
+    };
+    Ok((image, format))
+}
+
+/// Flips an image read back from OpenGL, which stores the bottom row first, so the first
+/// row is at the top.
+pub(crate) fn flip_rows<P: Pixel>(image: &mut ImageBuffer<P, Vec<P::Subpixel>>) {
+    image::imageops::flip_vertical_in_place(image);
+}
+
+/// Returns the mip levels requested by `spec` for a texture whose largest side is `size`.
+pub(crate) fn mip_levels(spec: &TextureSpec, size: u32) -> u32 {
+    let max_levels = mip_count(size, size);
+    spec.mip_levels.unwrap_or(max_levels).clamp(1, max_levels)
+}
+
+/// Creates a texture object with the given swizzle and sampler state. Its storage must be
+/// allocated afterwards.
+pub(crate) fn create_texture(
+    target: u32,
+    swizzle: Option<[u32; 4]>,
+    sampler: &SamplerState,
+) -> u32 {
+    let mut id = 0;
+    unsafe {
+        gl::CreateTextures(target, 1, &mut id);
+        if let Some(swizzle) = swizzle {
+            let swizzle = swizzle.map(|channel| channel as i32);
+            gl::TextureParameteriv(id, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+        }
+    }
+    sampler.apply(id, gl::TextureParameteri, gl::TextureParameterfv);
+    id
+}
 
 #[allow(dead_code)]
 pub struct Texture2D {
@@ -9,104 +152,128 @@ pub struct Texture2D {
     id: u32,
     width: u32,
     height: u32,
-    format: u32,
-    internal_format: u32,
+    format: PixelFormat,
+    mip_levels: u32,
+    sampler: SamplerState,
 }
 
 impl Texture2D {
     /// Loads an image from memory to the GPU as an OpenGL texture.
-    pub fn from_file(path: &str) -> Result<Self, ImageError> {
-        let image = image::open(path)?;
-
-        let format;
-        let internal_format;
-
-        match image {
-            image::DynamicImage::ImageRgb8(_) => {
-                format = gl::RGB;
-                internal_format = gl::RGB8;
-            }
-            image::DynamicImage::ImageRgba8(_) => {
-                format = gl::RGBA;
-                internal_format = gl::RGBA8;
-            }
-            _ => panic!("Image {} has an unsupported color format!", path),
-        }
+    ///
+    /// The texture uses the format closest to the decoded image, see
+    /// [`select_format`](format::select_format).
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        Self::from_file_as(path, Conversion::Native)
+    }
+
+    /// Loads an image from memory to the GPU as an OpenGL texture, converting its pixels
+    /// first as requested by `conversion`.
+    pub fn from_file_as(path: &str, conversion: Conversion) -> Result<Self, Error> {
+        Self::from_file_with(path, &TextureSpec::default().conversion(conversion))
+    }
+
+    /// Loads an image from memory to the GPU as an OpenGL texture created as described by
+    /// `spec`. Mipmaps are generated if the texture has more than one level.
+    pub fn from_file_with(path: &str, spec: &TextureSpec) -> Result<Self, Error> {
+        let (image, format) = load_image(path, spec.conversion)?;
 
         let width = image.width();
         let height = image.height();
 
-        let mut id = 0;
+        let mut texture = Self::with_spec(width, height, format, spec);
+        texture.path = path.to_string();
+        unsafe { texture.overwrite(image.flipv().as_bytes()) };
+        texture.generate_mipmaps();
+        Ok(texture)
+    }
 
-        unsafe {
-            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut id);
-            gl::TextureStorage2D(id, 1, internal_format, width as i32, height as i32);
+    /// Allocates memory in the GPU to store a texture of the specified dimensions.
+    pub fn with_size(width: u32, height: u32) -> Self {
+        let format = format::select_format(image::ColorType::Rgba8, Conversion::Native).unwrap();
+        Self::with_format(width, height, format)
+    }
 
-            gl::TextureParameteri(id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-            gl::TextureParameteri(id, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl::TextureParameteri(id, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-            gl::TextureParameteri(id, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+    /// Allocates memory in the GPU to store a texture of the specified dimensions and
+    /// format.
+    pub fn with_format(width: u32, height: u32, format: PixelFormat) -> Self {
+        Self::with_spec(width, height, format, &TextureSpec::default())
+    }
 
-            gl::TextureSubImage2D(
+    /// Allocates memory in the GPU to store a texture of the specified dimensions and
+    /// format, with the mip levels and sampler state of `spec`.
+    pub fn with_spec(width: u32, height: u32, format: PixelFormat, spec: &TextureSpec) -> Self {
+        let mip_levels = mip_levels(spec, width.max(height));
+        let id = create_texture(gl::TEXTURE_2D, format.swizzle, &spec.sampler);
+        unsafe {
+            gl::TextureStorage2D(
                 id,
-                0,
-                0,
-                0,
+                mip_levels as i32,
+                format.internal_format,
                 width as i32,
                 height as i32,
-                format,
-                gl::UNSIGNED_BYTE,
-                image.flipv().as_bytes().as_ptr() as *const c_void,
             );
         }
 
-        Ok(Self {
-            path: path.to_string(),
-            id,
-            width,
-            height,
-            format,
-            internal_format,
-        })
-    }
-
-    /// Allocates memory in the GPU to store a texture of the specified dimensions.
-    pub fn with_size(width: u32, height: u32) -> Self {
-        let format = gl::RGBA;
-        let internal_format = gl::RGBA8;
-
-        let mut id = 0;
-        unsafe {
-            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut id);
-            gl::TextureStorage2D(id, 1, internal_format, width as i32, height as i32);
-
-            gl::TextureParameteri(id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-            gl::TextureParameteri(id, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-            gl::TextureParameteri(id, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-            gl::TextureParameteri(id, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-        }
-
         Self {
             path: "no path".to_string(),
             id,
             width,
             height,
             format,
-            internal_format,
+            mip_levels,
+            sampler: spec.sampler,
         }
     }
 
     /// Fills the memory region of the texture with the given data overwriting any
     /// previously stored information.
+    ///
+    /// Only the base level is written, call [`Texture2D::generate_mipmaps`] afterwards to
+    /// update the rest.
+    ///
+    /// # Safety
+    ///
+    /// `data` must hold the pixels in the layout of [`Texture2D::get_format`], which is
+    /// checked only by size, with the bottom row first.
     pub unsafe fn overwrite(&mut self, data: &[u8]) {
-        // Bytes per pixel
-        let bpp = if self.format == gl::RGBA { 4 } else { 3 };
         assert_eq!(
             data.len(),
-            (self.width * self.height * bpp) as usize,
+            self.width as usize * self.height as usize * self.format.bytes_per_pixel(),
             "Data does not have the same size as the texture!"
         );
 
+        // Rows of RGB8 or grayscale images aren't 4-byte aligned.
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TextureSubImage2D(
+            self.id,
+            0,
+            0,
+            0,
+            self.width as i32,
+            self.height as i32,
+            self.format.format,
+            self.format.data_type,
+            data.as_ptr() as *const c_void,
+        );
+    }
+
+    /// Fills the base level of the texture with pixels previously written to `buffer` at
+    /// `offset` with [`PixelUnpackBuffer::stream`]. The copy happens on the GPU, so this
+    /// returns without waiting for it.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must hold at `offset` the whole base level in the layout of
+    /// [`Texture2D::get_format`], with the bottom row first.
+    pub unsafe fn overwrite_from_buffer(&mut self, buffer: &PixelUnpackBuffer, offset: usize) {
+        assert!(
+            self.width as usize * self.height as usize * self.format.bytes_per_pixel()
+                <= buffer.get_section_size(),
+            "The texture does not fit in a section of the buffer!"
+        );
+
+        buffer.bind();
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
         gl::TextureSubImage2D(
             self.id,
             0,
@@ -114,10 +281,84 @@ impl Texture2D {
             0,
             self.width as i32,
             self.height as i32,
-            self.format,
-            gl::UNSIGNED_BYTE,
-            data.as_ptr().cast(),
+            self.format.format,
+            self.format.data_type,
+            offset as *const c_void,
         );
+        PixelUnpackBuffer::unbind();
+    }
+
+    /// Reads back the base level of the texture as an image in the layout of
+    /// [`Texture2D::get_format`], with the first row at the top of the image.
+    ///
+    /// Textures created with a conversion to `Rgba16F` are read as 32-bit floats.
+    pub fn read_pixels(&self) -> DynamicImage {
+        match self.format.color_type {
+            ColorType::L8 => self.read_image::<Luma<u8>>(),
+            ColorType::La8 => self.read_image::<LumaA<u8>>(),
+            ColorType::Rgb8 => self.read_image::<Rgb<u8>>(),
+            ColorType::Rgba8 => self.read_image::<Rgba<u8>>(),
+            ColorType::L16 => self.read_image::<Luma<u16>>(),
+            ColorType::La16 => self.read_image::<LumaA<u16>>(),
+            ColorType::Rgb16 => self.read_image::<Rgb<u16>>(),
+            ColorType::Rgba16 => self.read_image::<Rgba<u16>>(),
+            ColorType::Rgb32F => self.read_image::<Rgb<f32>>(),
+            ColorType::Rgba32F => self.read_image::<Rgba<f32>>(),
+            color_type => unreachable!("No texture format uses {:?}", color_type),
+        }
+    }
+
+    /// Reads the base level with `glGetTextureImage` as pixels of type `P`, which must
+    /// match the color type and data type of the format.
+    fn read_image<P>(&self) -> DynamicImage
+    where
+        P: Pixel,
+        P::Subpixel: Zeroable,
+        DynamicImage: From<ImageBuffer<P, Vec<P::Subpixel>>>,
+    {
+        let len = self.width as usize * self.height as usize * P::CHANNEL_COUNT as usize;
+        let mut pixels = vec![P::Subpixel::zeroed(); len];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTextureImage(
+                self.id,
+                0,
+                self.format.format,
+                self.format.data_type,
+                mem::size_of_val(pixels.as_slice()) as i32,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+        let mut image = ImageBuffer::from_raw(self.width, self.height, pixels).unwrap();
+        flip_rows(&mut image);
+        DynamicImage::from(image)
+    }
+
+    /// Generates every mip level from the base level. Does nothing if the texture has a
+    /// single level.
+    pub fn generate_mipmaps(&self) {
+        if self.mip_levels > 1 {
+            unsafe { gl::GenerateTextureMipmap(self.id) };
+        }
+    }
+
+    /// Replaces the sampler state of the texture. It's overridden by any [`Sampler`]
+    /// bound to the same unit.
+    ///
+    /// [`Sampler`]: sampler::Sampler
+    pub fn set_sampler_state(&mut self, sampler: SamplerState) {
+        sampler.apply(self.id, gl::TextureParameteri, gl::TextureParameterfv);
+        self.sampler = sampler;
+    }
+
+    /// Returns the sampler state of the texture.
+    pub fn get_sampler_state(&self) -> &SamplerState {
+        &self.sampler
+    }
+
+    /// Returns the number of mip levels of the texture.
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
     }
 
     /// Binds the texture to the specified texture unit.
@@ -135,12 +376,29 @@ impl Texture2D {
         self.height
     }
 
+    /// Returns the formats the texture is stored and uploaded with.
+    pub fn get_format(&self) -> &PixelFormat {
+        &self.format
+    }
+
     /// Returns the internal id of the texture used by OpenGL.
     pub fn get_id(&self) -> u32 {
         self.id
     }
 }
 
+impl Texture for Texture2D {
+    const TARGET: u32 = gl::TEXTURE_2D;
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn get_size(&self) -> [u32; 3] {
+        [self.width, self.height, 1]
+    }
+}
+
 impl Drop for Texture2D {
     fn drop(&mut self) {
         unsafe { gl::DeleteTextures(1, &self.id) };
@@ -152,3 +410,18 @@ impl PartialEq for Texture2D {
         self.id == other.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_count_covers_the_largest_side() {
+        assert_eq!(mip_count(1, 1), 1);
+        assert_eq!(mip_count(2, 1), 2);
+        assert_eq!(mip_count(512, 512), 10);
+        assert_eq!(mip_count(640, 480), 10);
+        assert_eq!(mip_count(1, 1025), 11);
+        assert_eq!(mip_count(0, 0), 1);
+    }
+}
@@ -0,0 +1,187 @@
+//! 2D array textures, e.g. for shadow cascades or sprite atlases.
+
+use super::{
+    create_texture, format::PixelFormat, load_image, mip_levels, sampler::SamplerState, Texture,
+    TextureSpec,
+};
+use crate::error::Error;
+use std::path::Path;
+
+/// A texture with several 2D layers of the same size and format.
+pub struct Texture2DArray {
+    id: u32,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: PixelFormat,
+    mip_levels: u32,
+    sampler: SamplerState,
+}
+
+impl Texture2DArray {
+    /// Loads each image as a layer. All of them must have the same size and format.
+    /// Mipmaps are generated if the texture has more than one level.
+    ///
+    /// Panics if `paths` is empty.
+    pub fn from_files(paths: &[&str], spec: &TextureSpec) -> Result<Self, Error> {
+        assert!(!paths.is_empty(), "Array textures need at least one layer");
+        let mut layers = Vec::with_capacity(paths.len());
+        for path in paths {
+            layers.push(load_image(path, spec.conversion)?);
+        }
+
+        let (first, format) = &layers[0];
+        let (width, height, color_type) = (first.width(), first.height(), first.color());
+        for (path, (layer, _)) in paths.iter().zip(&layers) {
+            let message = if (layer.width(), layer.height()) != (width, height) {
+                format!(
+                    "layer is {}x{}, the first one is {}x{}",
+                    layer.width(),
+                    layer.height(),
+                    width,
+                    height
+                )
+            } else if layer.color() != color_type {
+                format!(
+                    "layer is {:?}, the first one is {:?}",
+                    layer.color(),
+                    color_type
+                )
+            } else {
+                continue;
+            };
+            return Err(Error::Texture {
+                path: Path::new(path).to_path_buf(),
+                message,
+            });
+        }
+
+        let mut texture = Self::with_spec(width, height, paths.len() as u32, *format, spec);
+        for (i, (layer, _)) in layers.iter().enumerate() {
+            unsafe { texture.set_layer(i as u32, layer.flipv().as_bytes()) };
+        }
+        texture.generate_mipmaps();
+        Ok(texture)
+    }
+
+    /// Allocates memory in the GPU to store `layers` layers of the specified dimensions
+    /// and format, with the mip levels and sampler state of `spec`.
+    pub fn with_spec(
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: PixelFormat,
+        spec: &TextureSpec,
+    ) -> Self {
+        let mip_levels = mip_levels(spec, width.max(height));
+        let id = create_texture(gl::TEXTURE_2D_ARRAY, format.swizzle, &spec.sampler);
+        unsafe {
+            gl::TextureStorage3D(
+                id,
+                mip_levels as i32,
+                format.internal_format,
+                width as i32,
+                height as i32,
+                layers as i32,
+            );
+        }
+
+        Self {
+            id,
+            width,
+            height,
+            layers,
+            format,
+            mip_levels,
+            sampler: spec.sampler,
+        }
+    }
+
+    /// Fills the base level of a layer with the given data.
+    ///
+    /// # Safety
+    ///
+    /// `data` must hold the pixels in the layout of [`Texture2DArray::get_format`], which
+    /// is checked only by size, with the bottom row first.
+    pub unsafe fn set_layer(&mut self, layer: u32, data: &[u8]) {
+        assert!(layer < self.layers, "Layer {} doesn't exist", layer);
+        assert_eq!(
+            data.len(),
+            self.width as usize * self.height as usize * self.format.bytes_per_pixel(),
+            "Data does not have the same size as the layer!"
+        );
+
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TextureSubImage3D(
+            self.id,
+            0,
+            0,
+            0,
+            layer as i32,
+            self.width as i32,
+            self.height as i32,
+            1,
+            self.format.format,
+            self.format.data_type,
+            data.as_ptr().cast(),
+        );
+    }
+
+    /// Generates every mip level of every layer from the base level. Does nothing if the
+    /// texture has a single level.
+    pub fn generate_mipmaps(&self) {
+        if self.mip_levels > 1 {
+            unsafe { gl::GenerateTextureMipmap(self.id) };
+        }
+    }
+
+    /// Replaces the sampler state of the texture.
+    pub fn set_sampler_state(&mut self, sampler: SamplerState) {
+        sampler.apply(self.id, gl::TextureParameteri, gl::TextureParameterfv);
+        self.sampler = sampler;
+    }
+
+    /// Returns the sampler state of the texture.
+    pub fn get_sampler_state(&self) -> &SamplerState {
+        &self.sampler
+    }
+
+    /// Returns the number of layers.
+    pub fn get_layers(&self) -> u32 {
+        self.layers
+    }
+
+    /// Returns the formats the texture is stored and uploaded with.
+    pub fn get_format(&self) -> &PixelFormat {
+        &self.format
+    }
+
+    /// Returns the number of mip levels of the texture.
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+}
+
+impl Texture for Texture2DArray {
+    const TARGET: u32 = gl::TEXTURE_2D_ARRAY;
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn get_size(&self) -> [u32; 3] {
+        [self.width, self.height, self.layers]
+    }
+}
+
+impl Drop for Texture2DArray {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) };
+    }
+}
+
+impl PartialEq for Texture2DArray {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
@@ -0,0 +1,322 @@
+//! Block-compressed textures loaded from KTX2 or DDS containers. Their mip levels are
+//! uploaded as they are stored, without decoding them on the CPU.
+
+pub mod dds;
+pub mod ktx2;
+
+use super::{create_texture, mip_count, sampler::SamplerState, Texture, TextureSpec};
+use crate::{error::Error, gl_ext};
+use std::{fs, path::Path};
+
+/// Families of compressed formats. Each of them is supported through a different OpenGL
+/// version or extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// BC1 to BC3 (`EXT_texture_compression_s3tc`).
+    S3tc,
+    /// BC4 and BC5 (core since OpenGL 3.0).
+    Rgtc,
+    /// BC6H and BC7 (core since OpenGL 4.2).
+    Bptc,
+    /// ETC2 and EAC (core since OpenGL 4.3).
+    Etc2,
+    /// ASTC LDR (`KHR_texture_compression_astc_ldr`).
+    Astc,
+}
+
+impl Compression {
+    /// Returns the name of the family used in [`Error::Unsupported`].
+    fn feature(self) -> &'static str {
+        match self {
+            Compression::S3tc => "S3TC (BC1-3) textures",
+            Compression::Rgtc => "RGTC (BC4-5) textures",
+            Compression::Bptc => "BPTC (BC6H-7) textures",
+            Compression::Etc2 => "ETC2/EAC textures",
+            Compression::Astc => "ASTC textures",
+        }
+    }
+}
+
+/// A block-compressed OpenGL format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompressedFormat {
+    pub compression: Compression,
+    /// Format used to store the texture, e.g. `GL_COMPRESSED_RGBA_BPTC_UNORM`.
+    pub internal_format: u32,
+    /// Width and height in texels of a block.
+    pub block_size: [u32; 2],
+    /// Size in bytes of a block.
+    pub block_bytes: u32,
+}
+
+impl CompressedFormat {
+    /// Creates a format with 4x4 blocks, which all but ASTC use.
+    pub(crate) fn new(compression: Compression, internal_format: u32, block_bytes: u32) -> Self {
+        Self {
+            compression,
+            internal_format,
+            block_size: [4, 4],
+            block_bytes,
+        }
+    }
+
+    /// Creates the ASTC format with the block size at `index` of
+    /// [`gl_ext::ASTC_BLOCK_SIZES`], or `None` if there isn't one.
+    pub(crate) fn astc(index: usize, srgb: bool) -> Option<Self> {
+        let base = match srgb {
+            false => gl_ext::COMPRESSED_RGBA_ASTC_4X4,
+            true => gl_ext::COMPRESSED_SRGB8_ALPHA8_ASTC_4X4,
+        };
+        Some(Self {
+            compression: Compression::Astc,
+            internal_format: base + index as u32,
+            block_size: *gl_ext::ASTC_BLOCK_SIZES.get(index)?,
+            block_bytes: 16,
+        })
+    }
+
+    /// Returns the size in bytes of a mip level of a texture whose base level is `width`
+    /// by `height` texels. Partial blocks at the edges take a whole block.
+    pub fn level_size(&self, width: u32, height: u32, level: u32) -> usize {
+        let [block_width, block_height] = self.block_size;
+        let blocks_x = (width >> level).max(1).div_ceil(block_width);
+        let blocks_y = (height >> level).max(1).div_ceil(block_height);
+        blocks_x as usize * blocks_y as usize * self.block_bytes as usize
+    }
+}
+
+/// A compressed 2D image and its mip chain, as stored in a container file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressedImage {
+    pub format: CompressedFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Data of each mip level, starting with the base level.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    /// Parses a KTX2 or DDS file, which are told apart by their signature. Returns a
+    /// message describing the problem if the file is malformed or holds something other
+    /// than a compressed 2D texture.
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.starts_with(&ktx2::IDENTIFIER) {
+            ktx2::parse(bytes)
+        } else if bytes.starts_with(&dds::MAGIC) {
+            dds::parse(bytes)
+        } else {
+            Err("not a KTX2 or DDS file".into())
+        }
+    }
+}
+
+/// Returns the number of levels of a mip chain stored in a container for a texture of the
+/// given size, where 0 also means a single level.
+fn level_count(width: u32, height: u32, count: u32) -> Result<u32, String> {
+    if width == 0 || height == 0 {
+        return Err(format!("invalid size {}x{}", width, height));
+    }
+    let max_count = mip_count(width, height);
+    if count > max_count {
+        return Err(format!(
+            "{} mip levels for a {}x{} texture, the maximum is {}",
+            count, width, height, max_count
+        ));
+    }
+    Ok(count.max(1))
+}
+
+/// Returns `len` bytes starting at `offset`, or an error if the file is too short.
+fn read_bytes(bytes: &[u8], offset: u64, len: u64) -> Result<&[u8], String> {
+    let end = offset
+        .checked_add(len)
+        .filter(|&end| end <= bytes.len() as u64);
+    match end {
+        Some(end) => Ok(&bytes[offset as usize..end as usize]),
+        None => Err("file is truncated".into()),
+    }
+}
+
+/// Reads a little-endian `u32` at `offset`.
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, String> {
+    let bytes = read_bytes(bytes, offset as u64, 4)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a little-endian `u64` at `offset`.
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, String> {
+    let bytes = read_bytes(bytes, offset as u64, 8)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// A 2D texture stored in a block-compressed format.
+pub struct CompressedTexture2D {
+    id: u32,
+    width: u32,
+    height: u32,
+    format: CompressedFormat,
+    mip_levels: u32,
+    sampler: SamplerState,
+}
+
+impl CompressedTexture2D {
+    /// Loads a KTX2 or DDS file to the GPU.
+    ///
+    /// Only the mip levels stored in the file are uploaded, `spec` can limit how many of
+    /// them but compressed textures can't generate the rest. Its conversion is ignored.
+    pub fn from_file(path: &str, spec: &TextureSpec) -> Result<Self, Error> {
+        let bytes = fs::read(path).map_err(|source| Error::Io {
+            path: Path::new(path).to_path_buf(),
+            source,
+        })?;
+        let image = CompressedImage::parse(&bytes).map_err(|message| Error::Texture {
+            path: Path::new(path).to_path_buf(),
+            message,
+        })?;
+        Self::from_image(&image, spec)
+    }
+
+    /// Uploads a compressed image to the GPU, see [`CompressedTexture2D::from_file`].
+    pub fn from_image(image: &CompressedImage, spec: &TextureSpec) -> Result<Self, Error> {
+        let format = image.format;
+        let mut supported = 0;
+        unsafe {
+            gl::GetInternalformativ(
+                gl::TEXTURE_2D,
+                format.internal_format,
+                gl::INTERNALFORMAT_SUPPORTED,
+                1,
+                &mut supported,
+            );
+        }
+        if supported != gl::TRUE as i32 {
+            return Err(Error::Unsupported {
+                feature: format.compression.feature(),
+            });
+        }
+
+        let mip_levels = spec
+            .mip_levels
+            .unwrap_or(u32::MAX)
+            .clamp(1, image.levels.len() as u32);
+        let id = create_texture(gl::TEXTURE_2D, None, &spec.sampler);
+        unsafe {
+            gl::TextureStorage2D(
+                id,
+                mip_levels as i32,
+                format.internal_format,
+                image.width as i32,
+                image.height as i32,
+            );
+            for (level, data) in image.levels.iter().take(mip_levels as usize).enumerate() {
+                gl::CompressedTextureSubImage2D(
+                    id,
+                    level as i32,
+                    0,
+                    0,
+                    (image.width >> level).max(1) as i32,
+                    (image.height >> level).max(1) as i32,
+                    format.internal_format,
+                    data.len() as i32,
+                    data.as_ptr().cast(),
+                );
+            }
+        }
+
+        Ok(Self {
+            id,
+            width: image.width,
+            height: image.height,
+            format,
+            mip_levels,
+            sampler: spec.sampler,
+        })
+    }
+
+    /// Replaces the sampler state of the texture.
+    pub fn set_sampler_state(&mut self, sampler: SamplerState) {
+        sampler.apply(self.id, gl::TextureParameteri, gl::TextureParameterfv);
+        self.sampler = sampler;
+    }
+
+    /// Returns the sampler state of the texture.
+    pub fn get_sampler_state(&self) -> &SamplerState {
+        &self.sampler
+    }
+
+    /// Returns the format the texture is stored with.
+    pub fn get_format(&self) -> &CompressedFormat {
+        &self.format
+    }
+
+    /// Returns the number of mip levels of the texture.
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+}
+
+impl Texture for CompressedTexture2D {
+    const TARGET: u32 = gl::TEXTURE_2D;
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn get_size(&self) -> [u32; 3] {
+        [self.width, self.height, 1]
+    }
+}
+
+impl Drop for CompressedTexture2D {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) };
+    }
+}
+
+impl PartialEq for CompressedTexture2D {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_size_rounds_up_to_whole_blocks() {
+        let bc1 = CompressedFormat::new(Compression::S3tc, gl_ext::COMPRESSED_RGB_S3TC_DXT1, 8);
+        assert_eq!(bc1.level_size(8, 8, 0), 32);
+        assert_eq!(bc1.level_size(10, 10, 0), 72);
+        assert_eq!(bc1.level_size(10, 10, 1), 32);
+        assert_eq!(bc1.level_size(10, 10, 3), 8);
+
+        let astc = CompressedFormat::astc(13, false).unwrap();
+        assert_eq!(astc.internal_format, 0x93BD);
+        assert_eq!(astc.block_size, [12, 12]);
+        assert_eq!(astc.level_size(25, 12, 0), 3 * 16);
+        assert_eq!(CompressedFormat::astc(14, false), None);
+    }
+
+    #[test]
+    fn parse_tells_containers_apart() {
+        let ktx2 = include_bytes!("../../tests/fixtures/bc1_8x8.ktx2");
+        let dds = include_bytes!("../../tests/fixtures/bc1_8x8.dds");
+        assert_eq!(
+            CompressedImage::parse(ktx2).unwrap().levels,
+            CompressedImage::parse(dds).unwrap().levels
+        );
+        assert_eq!(
+            CompressedImage::parse(b"\x89PNG\r\n\x1a\n"),
+            Err("not a KTX2 or DDS file".into())
+        );
+    }
+
+    #[test]
+    fn level_count_is_checked_against_the_size() {
+        assert_eq!(level_count(8, 8, 0), Ok(1));
+        assert_eq!(level_count(8, 2, 4), Ok(4));
+        assert!(level_count(8, 8, 5).is_err());
+        assert!(level_count(0, 8, 1).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Parser for [DDS](https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dx-graphics-dds-pguide)
+//! files holding a block-compressed 2D texture, with either a legacy FourCC or a DX10
+//! header.
+
+use super::{level_count, read_bytes, read_u32, CompressedFormat, CompressedImage, Compression};
+use crate::gl_ext;
+
+/// The first bytes of every DDS file.
+pub const MAGIC: [u8; 4] = *b"DDS ";
+
+/// Size of the magic number and the header, after which the DX10 header or the data start.
+const HEADER_LEN: usize = 128;
+const DX10_HEADER_LEN: usize = 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+/// Parses a DDS file, returning a message describing the problem if it's malformed or it
+/// holds something other than a compressed 2D texture.
+pub fn parse(bytes: &[u8]) -> Result<CompressedImage, String> {
+    if !bytes.starts_with(&MAGIC) {
+        return Err("invalid DDS magic number".into());
+    }
+    let header_size = read_u32(bytes, 4)?;
+    if header_size != 124 {
+        return Err(format!("invalid header size {}, expected 124", header_size));
+    }
+    let flags = read_u32(bytes, 8)?;
+    let [height, width] = [read_u32(bytes, 12)?, read_u32(bytes, 16)?];
+    let levels = read_u32(bytes, 28)?;
+    let pixel_flags = read_u32(bytes, 80)?;
+    let four_cc = read_bytes(bytes, 84, 4)?;
+    let caps2 = read_u32(bytes, 112)?;
+
+    if pixel_flags & DDPF_FOURCC == 0 {
+        return Err("uncompressed DDS files aren't supported".into());
+    }
+    if caps2 & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 {
+        return Err("only 2D textures are supported, not 3D or cube map ones".into());
+    }
+
+    let (format, mut offset) = if four_cc == b"DX10" {
+        let dxgi_format = read_u32(bytes, HEADER_LEN)?;
+        let dimension = read_u32(bytes, HEADER_LEN + 4)?;
+        let misc_flags = read_u32(bytes, HEADER_LEN + 8)?;
+        let array_size = read_u32(bytes, HEADER_LEN + 12)?;
+        if dimension != D3D10_RESOURCE_DIMENSION_TEXTURE2D
+            || misc_flags & D3D10_RESOURCE_MISC_TEXTURECUBE != 0
+            || array_size > 1
+        {
+            return Err(
+                "only 2D textures are supported, not 1D, 3D, array or cube map ones".into(),
+            );
+        }
+        let format = dxgi_format_to_gl(dxgi_format).ok_or_else(|| {
+            format!(
+                "DXGI format {} isn't a supported compressed format",
+                dxgi_format
+            )
+        })?;
+        (format, HEADER_LEN + DX10_HEADER_LEN)
+    } else {
+        let format =
+            four_cc_to_gl(four_cc, pixel_flags & DDPF_ALPHAPIXELS != 0).ok_or_else(|| {
+                format!(
+                    "FourCC '{}' isn't a supported compressed format",
+                    String::from_utf8_lossy(four_cc)
+                )
+            })?;
+        (format, HEADER_LEN)
+    };
+
+    let levels = match flags & DDSD_MIPMAPCOUNT {
+        0 => level_count(width, height, 1)?,
+        _ => level_count(width, height, levels)?,
+    };
+    let mut data = Vec::with_capacity(levels as usize);
+    for level in 0..levels {
+        let len = format.level_size(width, height, level);
+        data.push(read_bytes(bytes, offset as u64, len as u64)?.to_vec());
+        offset += len;
+    }
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        levels: data,
+    })
+}
+
+/// Returns the OpenGL format of a compressed `DXGI_FORMAT`.
+fn dxgi_format_to_gl(dxgi_format: u32) -> Option<CompressedFormat> {
+    use Compression::*;
+    let format = CompressedFormat::new;
+
+    #[rustfmt::skip]
+    let format = match dxgi_format {
+        71 => format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT1, 8),
+        72 => format(S3tc, gl_ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT1, 8),
+        74 => format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT3, 16),
+        75 => format(S3tc, gl_ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT3, 16),
+        77 => format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT5, 16),
+        78 => format(S3tc, gl_ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT5, 16),
+        80 => format(Rgtc, gl::COMPRESSED_RED_RGTC1, 8),
+        81 => format(Rgtc, gl::COMPRESSED_SIGNED_RED_RGTC1, 8),
+        83 => format(Rgtc, gl::COMPRESSED_RG_RGTC2, 16),
+        84 => format(Rgtc, gl::COMPRESSED_SIGNED_RG_RGTC2, 16),
+        95 => format(Bptc, gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT, 16),
+        96 => format(Bptc, gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, 16),
+        98 => format(Bptc, gl::COMPRESSED_RGBA_BPTC_UNORM, 16),
+        99 => format(Bptc, gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM, 16),
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// Returns the OpenGL format of a legacy FourCC. `alpha` selects the BC1 variant with
+/// 1-bit alpha.
+fn four_cc_to_gl(four_cc: &[u8], alpha: bool) -> Option<CompressedFormat> {
+    use Compression::*;
+    let format = CompressedFormat::new;
+
+    #[rustfmt::skip]
+    let format = match four_cc {
+        b"DXT1" if alpha => format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT1, 8),
+        b"DXT1" => format(S3tc, gl_ext::COMPRESSED_RGB_S3TC_DXT1, 8),
+        b"DXT3" => format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT3, 16),
+        b"DXT5" => format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT5, 16),
+        b"ATI1" | b"BC4U" => format(Rgtc, gl::COMPRESSED_RED_RGTC1, 8),
+        b"BC4S" => format(Rgtc, gl::COMPRESSED_SIGNED_RED_RGTC1, 8),
+        b"ATI2" | b"BC5U" => format(Rgtc, gl::COMPRESSED_RG_RGTC2, 16),
+        b"BC5S" => format(Rgtc, gl::COMPRESSED_SIGNED_RG_RGTC2, 16),
+        _ => return None,
+    };
+    Some(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BC1: &[u8] = include_bytes!("../../../tests/fixtures/bc1_8x8.dds");
+    const BC7: &[u8] = include_bytes!("../../../tests/fixtures/bc7_4x4_dx10.dds");
+
+    /// Returns a copy of `bytes` with the `u32` at `offset` replaced.
+    fn with_u32(bytes: &[u8], offset: usize, value: u32) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn parses_a_legacy_mip_chain() {
+        // 8x8 DXT1 with 4 levels, each a solid color: red, green, blue and white.
+        let image = parse(BC1).unwrap();
+        assert_eq!(
+            image.format.internal_format,
+            gl_ext::COMPRESSED_RGB_S3TC_DXT1
+        );
+        assert_eq!((image.width, image.height), (8, 8));
+        let sizes: Vec<usize> = image.levels.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [32, 8, 8, 8]);
+        assert_eq!(image.levels[2][..4], [0x1F, 0x00, 0x1F, 0x00]);
+
+        let alpha = parse(&with_u32(BC1, 80, DDPF_FOURCC | DDPF_ALPHAPIXELS)).unwrap();
+        assert_eq!(
+            alpha.format.internal_format,
+            gl_ext::COMPRESSED_RGBA_S3TC_DXT1
+        );
+    }
+
+    #[test]
+    fn parses_a_dx10_header() {
+        let image = parse(BC7).unwrap();
+        assert_eq!(image.format.internal_format, gl::COMPRESSED_RGBA_BPTC_UNORM);
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(image.levels, [BC7[148..].to_vec()]);
+    }
+
+    #[test]
+    fn ignores_the_mip_count_without_its_flag() {
+        let flags = read_u32(BC1, 8).unwrap() & !DDSD_MIPMAPCOUNT;
+        let image = parse(&with_u32(BC1, 8, flags)).unwrap();
+        assert_eq!(image.levels.len(), 1);
+    }
+
+    #[test]
+    fn rejects_unsupported_files() {
+        assert_eq!(
+            parse(&BC1[..BC1.len() - 1]),
+            Err("file is truncated".into())
+        );
+        assert_eq!(parse(&BC7[..140]), Err("file is truncated".into()));
+        assert!(parse(&with_u32(BC1, 4, 100))
+            .unwrap_err()
+            .contains("header size"));
+        assert!(parse(&with_u32(BC1, 80, 0x40))
+            .unwrap_err()
+            .contains("uncompressed"));
+        assert!(parse(&with_u32(BC1, 84, u32::from_le_bytes(*b"DXT2")))
+            .unwrap_err()
+            .contains("'DXT2'"));
+        assert!(parse(&with_u32(BC1, 112, DDSCAPS2_CUBEMAP))
+            .unwrap_err()
+            .contains("only 2D"));
+        assert!(parse(&with_u32(BC7, 128, 28))
+            .unwrap_err()
+            .contains("DXGI format 28"));
+        assert!(parse(&with_u32(BC7, 140, 4))
+            .unwrap_err()
+            .contains("only 2D"));
+    }
+}
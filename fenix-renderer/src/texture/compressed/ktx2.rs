@@ -0,0 +1,196 @@
+//! Parser for [KTX2](https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html) files
+//! holding a block-compressed 2D texture.
+
+use super::{
+    level_count, read_bytes, read_u32, read_u64, CompressedFormat, CompressedImage, Compression,
+};
+use crate::gl_ext;
+
+/// The first bytes of every KTX2 file.
+pub const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// Size of the identifier, the header and the index, after which the level index starts.
+const HEADER_LEN: usize = 80;
+const LEVEL_INDEX_ENTRY_LEN: usize = 24;
+
+/// `VK_FORMAT_BC1_RGB_UNORM_BLOCK`, the first of the compressed formats.
+const VK_FORMAT_BC1_RGB_UNORM: u32 = 131;
+/// `VK_FORMAT_ASTC_4x4_UNORM_BLOCK`, after which ASTC formats alternate between UNORM and
+/// SRGB for each block size.
+const VK_FORMAT_ASTC_4X4_UNORM: u32 = 157;
+
+/// Parses a KTX2 file, returning a message describing the problem if it's malformed or it
+/// holds something other than a compressed 2D texture without supercompression.
+pub fn parse(bytes: &[u8]) -> Result<CompressedImage, String> {
+    if !bytes.starts_with(&IDENTIFIER) {
+        return Err("invalid KTX2 identifier".into());
+    }
+    let field = |i: usize| read_u32(bytes, IDENTIFIER.len() + 4 * i);
+    let vk_format = field(0)?;
+    let [width, height, depth] = [field(2)?, field(3)?, field(4)?];
+    let [layers, faces, levels, supercompression] = [field(5)?, field(6)?, field(7)?, field(8)?];
+
+    let format = match vk_format {
+        0 => return Err("VK_FORMAT_UNDEFINED (e.g. Basis Universal) isn't supported".into()),
+        _ => vk_format_to_gl(vk_format)
+            .ok_or_else(|| format!("VkFormat {} isn't a supported compressed format", vk_format))?,
+    };
+    if supercompression != 0 {
+        return Err(format!(
+            "supercompression scheme {} isn't supported",
+            supercompression
+        ));
+    }
+    if height == 0 || depth != 0 || layers != 0 || faces != 1 {
+        return Err("only 2D textures are supported, not 1D, 3D, array or cube map ones".into());
+    }
+
+    let levels = level_count(width, height, levels)?;
+    let mut data = Vec::with_capacity(levels as usize);
+    for level in 0..levels {
+        let entry = HEADER_LEN + level as usize * LEVEL_INDEX_ENTRY_LEN;
+        let (offset, len) = (read_u64(bytes, entry)?, read_u64(bytes, entry + 8)?);
+        let expected = format.level_size(width, height, level);
+        if len != expected as u64 {
+            return Err(format!(
+                "level {} has {} bytes, expected {}",
+                level, len, expected
+            ));
+        }
+        data.push(read_bytes(bytes, offset, len)?.to_vec());
+    }
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        levels: data,
+    })
+}
+
+/// Returns the OpenGL format of a compressed `VkFormat`.
+fn vk_format_to_gl(vk_format: u32) -> Option<CompressedFormat> {
+    use Compression::*;
+    let format = CompressedFormat::new;
+
+    if vk_format >= VK_FORMAT_ASTC_4X4_UNORM {
+        let index = vk_format - VK_FORMAT_ASTC_4X4_UNORM;
+        return CompressedFormat::astc(index as usize / 2, index % 2 == 1);
+    }
+    #[rustfmt::skip]
+    let formats = [
+        format(S3tc, gl_ext::COMPRESSED_RGB_S3TC_DXT1, 8),
+        format(S3tc, gl_ext::COMPRESSED_SRGB_S3TC_DXT1, 8),
+        format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT1, 8),
+        format(S3tc, gl_ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT1, 8),
+        format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT3, 16),
+        format(S3tc, gl_ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT3, 16),
+        format(S3tc, gl_ext::COMPRESSED_RGBA_S3TC_DXT5, 16),
+        format(S3tc, gl_ext::COMPRESSED_SRGB_ALPHA_S3TC_DXT5, 16),
+        format(Rgtc, gl::COMPRESSED_RED_RGTC1, 8),
+        format(Rgtc, gl::COMPRESSED_SIGNED_RED_RGTC1, 8),
+        format(Rgtc, gl::COMPRESSED_RG_RGTC2, 16),
+        format(Rgtc, gl::COMPRESSED_SIGNED_RG_RGTC2, 16),
+        format(Bptc, gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT, 16),
+        format(Bptc, gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, 16),
+        format(Bptc, gl::COMPRESSED_RGBA_BPTC_UNORM, 16),
+        format(Bptc, gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM, 16),
+        format(Etc2, gl::COMPRESSED_RGB8_ETC2, 8),
+        format(Etc2, gl::COMPRESSED_SRGB8_ETC2, 8),
+        format(Etc2, gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2, 8),
+        format(Etc2, gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2, 8),
+        format(Etc2, gl::COMPRESSED_RGBA8_ETC2_EAC, 16),
+        format(Etc2, gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC, 16),
+        format(Etc2, gl::COMPRESSED_R11_EAC, 8),
+        format(Etc2, gl::COMPRESSED_SIGNED_R11_EAC, 8),
+        format(Etc2, gl::COMPRESSED_RG11_EAC, 16),
+        format(Etc2, gl::COMPRESSED_SIGNED_RG11_EAC, 16),
+    ];
+    let index = vk_format.checked_sub(VK_FORMAT_BC1_RGB_UNORM)?;
+    formats.get(index as usize).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BC1: &[u8] = include_bytes!("../../../tests/fixtures/bc1_8x8.ktx2");
+    const ASTC: &[u8] = include_bytes!("../../../tests/fixtures/astc_6x6_10x10.ktx2");
+
+    /// Returns a copy of `bytes` with a header field replaced.
+    fn with_field(bytes: &[u8], index: usize, value: u32) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        let offset = IDENTIFIER.len() + 4 * index;
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn parses_a_mip_chain() {
+        // 8x8 BC1 with 4 levels, each a solid color: red, green, blue and white.
+        let image = parse(BC1).unwrap();
+        assert_eq!(
+            image.format.internal_format,
+            gl_ext::COMPRESSED_RGB_S3TC_DXT1
+        );
+        assert_eq!((image.width, image.height), (8, 8));
+        let sizes: Vec<usize> = image.levels.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [32, 8, 8, 8]);
+        assert_eq!(image.levels[0][..8], [0x00, 0xF8, 0x00, 0xF8, 0, 0, 0, 0]);
+        assert_eq!(image.levels[1][..4], [0xE0, 0x07, 0xE0, 0x07]);
+        assert_eq!(image.levels[3][..4], [0xFF; 4]);
+    }
+
+    #[test]
+    fn parses_astc_with_partial_blocks() {
+        // 10x10 with 6x6 blocks needs 2x2 of them.
+        let image = parse(ASTC).unwrap();
+        assert_eq!(image.format.compression, Compression::Astc);
+        assert_eq!(image.format.block_size, [6, 6]);
+        assert_eq!(image.format.internal_format, 0x93B4);
+        assert_eq!(image.levels.len(), 1);
+        assert_eq!(image.levels[0].len(), 64);
+    }
+
+    #[test]
+    fn vk_formats_map_to_gl() {
+        let gl_format = |vk_format| vk_format_to_gl(vk_format).unwrap().internal_format;
+        assert_eq!(gl_format(146), gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM);
+        assert_eq!(gl_format(156), gl::COMPRESSED_SIGNED_RG11_EAC);
+        assert_eq!(gl_format(158), gl_ext::COMPRESSED_SRGB8_ALPHA8_ASTC_4X4);
+        assert_eq!(
+            gl_format(184),
+            gl_ext::COMPRESSED_SRGB8_ALPHA8_ASTC_4X4 + 13
+        );
+        assert_eq!(vk_format_to_gl(37), None); // VK_FORMAT_R8G8B8A8_UNORM
+        assert_eq!(vk_format_to_gl(185), None);
+    }
+
+    #[test]
+    fn rejects_unsupported_files() {
+        assert_eq!(
+            parse(&BC1[..BC1.len() - 1]),
+            Err("file is truncated".into())
+        );
+        assert_eq!(parse(&BC1[..40]), Err("file is truncated".into()));
+        assert!(parse(&with_field(BC1, 0, 0)).unwrap_err().contains("Basis"));
+        assert!(parse(&with_field(BC1, 0, 37))
+            .unwrap_err()
+            .contains("VkFormat 37"));
+        assert!(parse(&with_field(BC1, 8, 2))
+            .unwrap_err()
+            .contains("supercompression"));
+        assert!(parse(&with_field(BC1, 6, 6))
+            .unwrap_err()
+            .contains("only 2D"));
+        assert!(parse(&with_field(BC1, 7, 5))
+            .unwrap_err()
+            .contains("5 mip levels"));
+        // The base level size doesn't match a 16x8 texture.
+        assert!(parse(&with_field(BC1, 2, 16))
+            .unwrap_err()
+            .contains("level 0"));
+    }
+}
@@ -0,0 +1,328 @@
+//! Cube map textures, e.g. for skyboxes and environment lighting.
+
+use super::{
+    create_texture, format,
+    format::{Conversion, PixelFormat},
+    load_image, mip_levels,
+    sampler::SamplerState,
+    Texture, TextureSpec,
+};
+use crate::error::Error;
+use glam::Vec3;
+use image::{ColorType, Rgba32FImage};
+use std::{f32::consts::PI, path::Path};
+
+/// Number of faces of a cube map.
+pub const FACE_COUNT: usize = 6;
+
+/// A cube map texture. Faces are indexed in the OpenGL order: +X, -X, +Y, -Y, +Z, -Z.
+pub struct TextureCube {
+    id: u32,
+    size: u32,
+    format: PixelFormat,
+    mip_levels: u32,
+    sampler: SamplerState,
+}
+
+impl TextureCube {
+    /// Loads a cube map from six square images of the same size and format, in the order
+    /// +X, -X, +Y, -Y, +Z, -Z. Mipmaps are generated if the texture has more than one
+    /// level.
+    ///
+    /// Unlike 2D textures, faces aren't flipped: cube maps expect the top row first.
+    pub fn from_faces(paths: [&str; FACE_COUNT], spec: &TextureSpec) -> Result<Self, Error> {
+        let mut faces = Vec::with_capacity(FACE_COUNT);
+        for path in paths {
+            faces.push(load_image(path, spec.conversion)?);
+        }
+
+        let (first, format) = &faces[0];
+        let (size, color_type) = (first.width(), first.color());
+        for (path, (face, _)) in paths.iter().zip(&faces) {
+            let error = |message| {
+                Err(Error::Texture {
+                    path: Path::new(path).to_path_buf(),
+                    message,
+                })
+            };
+            if face.width() != face.height() {
+                return error(format!(
+                    "cube map faces must be square, found {}x{}",
+                    face.width(),
+                    face.height()
+                ));
+            }
+            if face.width() != size {
+                return error(format!(
+                    "face is {0}x{0}, the first one is {1}x{1}",
+                    face.width(),
+                    size
+                ));
+            }
+            if face.color() != color_type {
+                return error(format!(
+                    "face is {:?}, the first one is {:?}",
+                    face.color(),
+                    color_type
+                ));
+            }
+        }
+
+        let mut texture = Self::with_spec(size, *format, spec);
+        for (i, (face, _)) in faces.iter().enumerate() {
+            unsafe { texture.set_face(i, face.as_bytes()) };
+        }
+        texture.generate_mipmaps();
+        Ok(texture)
+    }
+
+    /// Loads a cube map with faces of `face_size` pixels from an equirectangular
+    /// (latitude-longitude) image, usually an HDR environment.
+    ///
+    /// The faces are stored as float RGBA unless `spec` requests another conversion.
+    pub fn from_equirectangular(
+        path: &str,
+        face_size: u32,
+        spec: &TextureSpec,
+    ) -> Result<Self, Error> {
+        let (image, _) = load_image(path, Conversion::Rgba32F)?;
+        let image = image.into_rgba32f();
+
+        let stored = format::select_format(ColorType::Rgba32F, spec.conversion).unwrap();
+        let format = PixelFormat {
+            internal_format: stored.internal_format,
+            ..format::select_format(ColorType::Rgba32F, Conversion::Native).unwrap()
+        };
+
+        let mut texture = Self::with_spec(face_size, format, spec);
+        for face in 0..FACE_COUNT {
+            let pixels = equirectangular_face(&image, face, face_size);
+            unsafe { texture.set_face(face, bytemuck::cast_slice(&pixels)) };
+        }
+        texture.generate_mipmaps();
+        Ok(texture)
+    }
+
+    /// Allocates memory in the GPU to store a cube map with faces of `size` by `size`
+    /// pixels, with the mip levels and sampler state of `spec`.
+    pub fn with_spec(size: u32, format: PixelFormat, spec: &TextureSpec) -> Self {
+        let mip_levels = mip_levels(spec, size);
+        let id = create_texture(gl::TEXTURE_CUBE_MAP, format.swizzle, &spec.sampler);
+        unsafe {
+            gl::TextureStorage2D(
+                id,
+                mip_levels as i32,
+                format.internal_format,
+                size as i32,
+                size as i32,
+            );
+        }
+
+        Self {
+            id,
+            size,
+            format,
+            mip_levels,
+            sampler: spec.sampler,
+        }
+    }
+
+    /// Fills the base level of a face with the given data.
+    ///
+    /// # Safety
+    ///
+    /// `data` must hold the pixels in the layout of [`TextureCube::get_format`], which is
+    /// checked only by size, with the top row first.
+    pub unsafe fn set_face(&mut self, face: usize, data: &[u8]) {
+        assert!(
+            face < FACE_COUNT,
+            "Cube maps only have {} faces",
+            FACE_COUNT
+        );
+        assert_eq!(
+            data.len(),
+            (self.size * self.size) as usize * self.format.bytes_per_pixel(),
+            "Data does not have the same size as the face!"
+        );
+
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TextureSubImage3D(
+            self.id,
+            0,
+            0,
+            0,
+            face as i32,
+            self.size as i32,
+            self.size as i32,
+            1,
+            self.format.format,
+            self.format.data_type,
+            data.as_ptr().cast(),
+        );
+    }
+
+    /// Generates every mip level from the base level. Does nothing if the texture has a
+    /// single level.
+    pub fn generate_mipmaps(&self) {
+        if self.mip_levels > 1 {
+            unsafe { gl::GenerateTextureMipmap(self.id) };
+        }
+    }
+
+    /// Replaces the sampler state of the texture.
+    pub fn set_sampler_state(&mut self, sampler: SamplerState) {
+        sampler.apply(self.id, gl::TextureParameteri, gl::TextureParameterfv);
+        self.sampler = sampler;
+    }
+
+    /// Returns the sampler state of the texture.
+    pub fn get_sampler_state(&self) -> &SamplerState {
+        &self.sampler
+    }
+
+    /// Returns the width and height of each face.
+    pub fn get_face_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns the formats the texture is stored and uploaded with.
+    pub fn get_format(&self) -> &PixelFormat {
+        &self.format
+    }
+
+    /// Returns the number of mip levels of the texture.
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+}
+
+impl Texture for TextureCube {
+    const TARGET: u32 = gl::TEXTURE_CUBE_MAP;
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn get_size(&self) -> [u32; 3] {
+        [self.size, self.size, FACE_COUNT as u32]
+    }
+}
+
+impl Drop for TextureCube {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) };
+    }
+}
+
+impl PartialEq for TextureCube {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Returns the direction sampled at the face coordinates `(s, t)`, each in `[-1, 1]` with
+/// `t` growing downwards, following the cube map selection table of the OpenGL spec.
+fn face_direction(face: usize, s: f32, t: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -t, -s),
+        1 => Vec3::new(-1.0, -t, s),
+        2 => Vec3::new(s, 1.0, t),
+        3 => Vec3::new(s, -1.0, -t),
+        4 => Vec3::new(s, -t, 1.0),
+        5 => Vec3::new(-s, -t, -1.0),
+        _ => unreachable!("Cube maps only have {} faces", FACE_COUNT),
+    }
+}
+
+/// Returns the coordinates in `[0, 1]` of an equirectangular image that correspond to a
+/// direction, with `v = 0` at the top (+Y).
+fn equirectangular_uv(direction: Vec3) -> (f32, f32) {
+    let direction = direction.normalize();
+    let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
+
+/// Samples an equirectangular image with bilinear filtering, wrapping horizontally.
+fn sample_bilinear(image: &Rgba32FImage, u: f32, v: f32) -> [f32; 4] {
+    let (width, height) = image.dimensions();
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(width as i64) as u32;
+        let y = (y as u32).min(height - 1);
+        image.get_pixel(x, y).0
+    };
+    let (a, b) = (texel(x0, y0), texel(x0 + 1.0, y0));
+    let (c, d) = (texel(x0, y0 + 1.0), texel(x0 + 1.0, y0 + 1.0));
+    std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+/// Projects an equirectangular image onto a face of `size` by `size` pixels, returned as
+/// float RGBA with the top row first.
+fn equirectangular_face(image: &Rgba32FImage, face: usize, size: u32) -> Vec<f32> {
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+            let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+            let (u, v) = equirectangular_uv(face_direction(face, s, t));
+            pixels.extend(sample_bilinear(image, u, v));
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn face_centers_point_along_the_axes() {
+        let axes = [Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z];
+        for (face, axis) in axes.into_iter().enumerate() {
+            assert_eq!(face_direction(face, 0.0, 0.0), axis);
+        }
+        // The top of the side faces is up and the top of +Y is towards -Z.
+        assert_eq!(face_direction(4, 0.0, -1.0).y, 1.0);
+        assert_eq!(face_direction(0, 0.0, -1.0).y, 1.0);
+        assert_eq!(face_direction(2, 0.0, -1.0).z, -1.0);
+    }
+
+    #[test]
+    fn equirectangular_poles_and_seam() {
+        assert_eq!(equirectangular_uv(Vec3::Y).1, 0.0);
+        assert_eq!(equirectangular_uv(-Vec3::Y).1, 1.0);
+        assert_eq!(equirectangular_uv(Vec3::X), (0.5, 0.5));
+        assert_eq!(equirectangular_uv(Vec3::Z).0, 0.75);
+        assert_eq!(equirectangular_uv(-Vec3::Z).0, 0.25);
+    }
+
+    #[test]
+    fn faces_sample_the_matching_region() {
+        // Top half red, bottom half blue.
+        let image = Rgba32FImage::from_fn(64, 32, |_, y| {
+            if y < 16 {
+                Rgba([1.0, 0.0, 0.0, 1.0])
+            } else {
+                Rgba([0.0, 0.0, 1.0, 1.0])
+            }
+        });
+        let up = equirectangular_face(&image, 2, 4);
+        let down = equirectangular_face(&image, 3, 4);
+        assert!(up.chunks(4).all(|pixel| pixel == [1.0, 0.0, 0.0, 1.0]));
+        assert!(down.chunks(4).all(|pixel| pixel == [0.0, 0.0, 1.0, 1.0]));
+
+        let side = equirectangular_face(&image, 4, 4);
+        assert_eq!(&side[..4], &[1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(&side[side.len() - 4..], &[0.0, 0.0, 1.0, 1.0]);
+    }
+}
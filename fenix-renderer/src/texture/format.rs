@@ -0,0 +1,161 @@
+//! Mapping from the pixel layouts decoded by the `image` crate to OpenGL texture formats.
+
+use image::ColorType;
+
+/// How the pixels of an image are converted before uploading them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Conversion {
+    /// Upload the pixels as they are decoded, in the closest OpenGL format.
+    #[default]
+    Native,
+    /// Convert to 8-bit RGBA.
+    Rgba8,
+    /// Convert to float RGBA and store it as half floats, e.g. to save memory on HDR images.
+    Rgba16F,
+    /// Convert to float RGBA.
+    Rgba32F,
+}
+
+/// The OpenGL formats used to store and upload an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelFormat {
+    /// Layout the pixels must have when they are uploaded, the image is converted to it if
+    /// it doesn't match the decoded one.
+    pub color_type: ColorType,
+    /// Format used to store the texture, e.g. `GL_RGB16`.
+    pub internal_format: u32,
+    /// Channels of the uploaded pixels, e.g. `GL_RG`.
+    pub format: u32,
+    /// Type of each channel of the uploaded pixels, e.g. `GL_UNSIGNED_SHORT`.
+    pub data_type: u32,
+    /// `GL_TEXTURE_SWIZZLE_RGBA` of the texture, which makes grayscale textures sample as
+    /// gray instead of red.
+    pub swizzle: Option<[u32; 4]>,
+}
+
+impl PixelFormat {
+    /// Returns the size in bytes of an uploaded pixel.
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.color_type.bytes_per_pixel() as usize
+    }
+}
+
+const GRAY: [u32; 4] = [gl::RED, gl::RED, gl::RED, gl::ONE];
+const GRAY_ALPHA: [u32; 4] = [gl::RED, gl::RED, gl::RED, gl::GREEN];
+
+/// Chooses the formats used to upload an image of the given color type, or `None` if there
+/// is no mapping for it.
+pub fn select_format(color_type: ColorType, conversion: Conversion) -> Option<PixelFormat> {
+    let format = |color_type, internal_format, format, data_type, swizzle| PixelFormat {
+        color_type,
+        internal_format,
+        format,
+        data_type,
+        swizzle,
+    };
+
+    #[rustfmt::skip]
+    let selected = match (conversion, color_type) {
+        (Conversion::Rgba8, _) => format(ColorType::Rgba8, gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE, None),
+        (Conversion::Rgba16F, _) => format(ColorType::Rgba32F, gl::RGBA16F, gl::RGBA, gl::FLOAT, None),
+        (Conversion::Rgba32F, _) => format(ColorType::Rgba32F, gl::RGBA32F, gl::RGBA, gl::FLOAT, None),
+
+        (Conversion::Native, ColorType::L8) => format(color_type, gl::R8, gl::RED, gl::UNSIGNED_BYTE, Some(GRAY)),
+        (Conversion::Native, ColorType::La8) => format(color_type, gl::RG8, gl::RG, gl::UNSIGNED_BYTE, Some(GRAY_ALPHA)),
+        (Conversion::Native, ColorType::Rgb8) => format(color_type, gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE, None),
+        (Conversion::Native, ColorType::Rgba8) => format(color_type, gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE, None),
+
+        (Conversion::Native, ColorType::L16) => format(color_type, gl::R16, gl::RED, gl::UNSIGNED_SHORT, Some(GRAY)),
+        (Conversion::Native, ColorType::La16) => format(color_type, gl::RG16, gl::RG, gl::UNSIGNED_SHORT, Some(GRAY_ALPHA)),
+        (Conversion::Native, ColorType::Rgb16) => format(color_type, gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT, None),
+        (Conversion::Native, ColorType::Rgba16) => format(color_type, gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT, None),
+
+        (Conversion::Native, ColorType::Rgb32F) => format(color_type, gl::RGB32F, gl::RGB, gl::FLOAT, None),
+        (Conversion::Native, ColorType::Rgba32F) => format(color_type, gl::RGBA32F, gl::RGBA, gl::FLOAT, None),
+
+        // `ColorType` is non-exhaustive, new layouts need a mapping first.
+        (Conversion::Native, _) => return None,
+    };
+    Some(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_every_decoded_layout() {
+        let expected = [
+            (ColorType::L8, gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+            (ColorType::La8, gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+            (ColorType::Rgb8, gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE),
+            (ColorType::Rgba8, gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            (ColorType::L16, gl::R16, gl::RED, gl::UNSIGNED_SHORT),
+            (ColorType::La16, gl::RG16, gl::RG, gl::UNSIGNED_SHORT),
+            (ColorType::Rgb16, gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT),
+            (ColorType::Rgba16, gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT),
+            (ColorType::Rgb32F, gl::RGB32F, gl::RGB, gl::FLOAT),
+            (ColorType::Rgba32F, gl::RGBA32F, gl::RGBA, gl::FLOAT),
+        ];
+        for (color_type, internal_format, format, data_type) in expected {
+            let selected = select_format(color_type, Conversion::Native).unwrap();
+            assert_eq!(selected.color_type, color_type);
+            assert_eq!(
+                (
+                    selected.internal_format,
+                    selected.format,
+                    selected.data_type
+                ),
+                (internal_format, format, data_type),
+                "{:?}",
+                color_type
+            );
+        }
+    }
+
+    #[test]
+    fn grayscale_is_swizzled() {
+        let gray = select_format(ColorType::L16, Conversion::Native).unwrap();
+        assert_eq!(gray.swizzle, Some([gl::RED, gl::RED, gl::RED, gl::ONE]));
+        let gray_alpha = select_format(ColorType::La8, Conversion::Native).unwrap();
+        assert_eq!(
+            gray_alpha.swizzle,
+            Some([gl::RED, gl::RED, gl::RED, gl::GREEN])
+        );
+        let rgb = select_format(ColorType::Rgb8, Conversion::Native).unwrap();
+        assert_eq!(rgb.swizzle, None);
+    }
+
+    #[test]
+    fn conversions_override_the_decoded_layout() {
+        let rgba8 = select_format(ColorType::Rgb32F, Conversion::Rgba8).unwrap();
+        assert_eq!(rgba8.color_type, ColorType::Rgba8);
+        assert_eq!(rgba8.internal_format, gl::RGBA8);
+        assert_eq!(rgba8.bytes_per_pixel(), 4);
+
+        let half = select_format(ColorType::L8, Conversion::Rgba16F).unwrap();
+        assert_eq!(half.color_type, ColorType::Rgba32F);
+        assert_eq!(
+            (half.internal_format, half.data_type),
+            (gl::RGBA16F, gl::FLOAT)
+        );
+        assert_eq!(half.bytes_per_pixel(), 16);
+
+        let float = select_format(ColorType::La16, Conversion::Rgba32F).unwrap();
+        assert_eq!(float.internal_format, gl::RGBA32F);
+    }
+
+    #[test]
+    fn bytes_per_pixel_matches_the_uploaded_layout() {
+        let size = |color_type| {
+            select_format(color_type, Conversion::Native)
+                .unwrap()
+                .bytes_per_pixel()
+        };
+        assert_eq!(size(ColorType::L8), 1);
+        assert_eq!(size(ColorType::Rgb8), 3);
+        assert_eq!(size(ColorType::La16), 4);
+        assert_eq!(size(ColorType::Rgb16), 6);
+        assert_eq!(size(ColorType::Rgb32F), 12);
+    }
+}
@@ -0,0 +1,305 @@
+//! Loading of textures on background threads, so decoding images doesn't stall the thread
+//! that renders.
+//!
+//! ```ignore
+//! let mut loader = TextureLoader::new(2).pixel_buffer(16 << 20, 2);
+//! let texture = loader.load("assets/image/container.jpg", &TextureSpec::default());
+//! loop {
+//!     loader.poll();
+//!     texture.bind(0); // The placeholder until the image is uploaded.
+//!     // ...
+//! }
+//! ```
+
+use super::{format::Conversion, format::PixelFormat, load_image, Texture, Texture2D, TextureSpec};
+use crate::{buffer::PixelUnpackBuffer, error::Error};
+use log::error;
+use std::{
+    cell::{Ref, RefCell},
+    collections::HashMap,
+    rc::{Rc, Weak},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+/// Progress of a texture loaded by a [`TextureLoader`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    /// The image is being decoded or waiting to be uploaded.
+    Loading,
+    /// The texture is uploaded and replaces the placeholder.
+    Ready,
+    /// The image couldn't be loaded and the placeholder stays. The error is logged.
+    Failed,
+}
+
+enum Slot {
+    Loading,
+    Ready(Texture2D),
+    Failed,
+}
+
+/// A texture loaded by a [`TextureLoader`], which shows the placeholder of the loader
+/// until the image is uploaded. Clones refer to the same texture.
+#[derive(Clone)]
+pub struct AsyncTexture {
+    slot: Rc<RefCell<Slot>>,
+    placeholder: Rc<Texture2D>,
+}
+
+impl AsyncTexture {
+    /// Returns the progress of the load.
+    pub fn get_state(&self) -> LoadState {
+        match &*self.slot.borrow() {
+            Slot::Loading => LoadState::Loading,
+            Slot::Ready(_) => LoadState::Ready,
+            Slot::Failed => LoadState::Failed,
+        }
+    }
+
+    /// Returns the texture once it's uploaded.
+    pub fn get_texture(&self) -> Option<Ref<'_, Texture2D>> {
+        Ref::filter_map(self.slot.borrow(), |slot| match slot {
+            Slot::Ready(texture) => Some(texture),
+            _ => None,
+        })
+        .ok()
+    }
+}
+
+impl Texture for AsyncTexture {
+    const TARGET: u32 = gl::TEXTURE_2D;
+
+    /// Returns the id of the texture, or of the placeholder while it isn't uploaded.
+    fn get_id(&self) -> u32 {
+        match &*self.slot.borrow() {
+            Slot::Ready(texture) => texture.get_id(),
+            _ => self.placeholder.get_id(),
+        }
+    }
+
+    /// Returns the size of the texture, or of the placeholder while it isn't uploaded.
+    fn get_size(&self) -> [u32; 3] {
+        match &*self.slot.borrow() {
+            Slot::Ready(texture) => Texture::get_size(texture),
+            _ => Texture::get_size(&*self.placeholder),
+        }
+    }
+}
+
+struct Request {
+    id: u64,
+    path: String,
+    conversion: Conversion,
+}
+
+/// Pixels decoded by a worker, already in the layout they are uploaded with.
+struct Decoded {
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    pixels: Vec<u8>,
+}
+
+/// A requested texture that hasn't been uploaded yet.
+struct Pending {
+    path: String,
+    spec: TextureSpec,
+    /// Nothing is uploaded if every handle is dropped before the image is decoded.
+    slot: Weak<RefCell<Slot>>,
+}
+
+/// Loads 2D textures decoding their images on worker threads.
+///
+/// [`TextureLoader::load`] returns an [`AsyncTexture`] right away, and the texture is
+/// created and uploaded on the thread of the OpenGL context by [`TextureLoader::poll`],
+/// which should be called regularly, e.g. once per frame. Dropping the loader waits for
+/// the workers to finish the image they are decoding.
+pub struct TextureLoader {
+    requests: Option<mpsc::Sender<Request>>,
+    results: mpsc::Receiver<(u64, Result<Decoded, Error>)>,
+    workers: Vec<JoinHandle<()>>,
+    pending: HashMap<u64, Pending>,
+    next_id: u64,
+    placeholder: Rc<Texture2D>,
+    pixel_buffer: Option<PixelUnpackBuffer>,
+}
+
+impl TextureLoader {
+    /// Creates a loader with `workers` threads (at least one) that shows a gray 1x1
+    /// placeholder while textures load.
+    pub fn new(workers: usize) -> Self {
+        let (requests, request_receiver) = mpsc::channel::<Request>();
+        let (result_sender, results) = mpsc::channel();
+        let request_receiver = Arc::new(Mutex::new(request_receiver));
+
+        let workers = (0..workers.max(1))
+            .map(|i| {
+                let requests = Arc::clone(&request_receiver);
+                let results = result_sender.clone();
+                thread::Builder::new()
+                    .name(format!("texture-loader-{}", i))
+                    .spawn(move || loop {
+                        // The lock is released as soon as a request arrives,
+                        // so other workers can take the next one while this one decodes.
+                        let request = match requests.lock().unwrap().recv() {
+                            Ok(request) => request,
+                            Err(_) => break,
+                        };
+                        let decoded = decode(&request.path, request.conversion);
+                        if results.send((request.id, decoded)).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("Failed to spawn texture loader thread")
+            })
+            .collect();
+
+        Self {
+            requests: Some(requests),
+            results,
+            workers,
+            pending: HashMap::new(),
+            next_id: 0,
+            placeholder: Rc::new(solid_texture([128, 128, 128, 255])),
+            pixel_buffer: None,
+        }
+    }
+
+    /// Replaces the placeholder of the textures loaded from now on by a 1x1 texture of the
+    /// given color.
+    pub fn placeholder(mut self, color: [u8; 4]) -> Self {
+        self.placeholder = Rc::new(solid_texture(color));
+        self
+    }
+
+    /// Streams the uploads through a pixel buffer of `section_count` sections of
+    /// `section_size` bytes each, so they don't wait for the previous ones to finish.
+    /// Images bigger than a section are uploaded directly.
+    pub fn pixel_buffer(mut self, section_size: u32, section_count: u32) -> Self {
+        self.pixel_buffer = Some(PixelUnpackBuffer::new(section_size, section_count));
+        self
+    }
+
+    /// Starts loading an image as a texture created as described by `spec`.
+    pub fn load(&mut self, path: &str, spec: &TextureSpec) -> AsyncTexture {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let slot = Rc::new(RefCell::new(Slot::Loading));
+        self.pending.insert(
+            id,
+            Pending {
+                path: path.to_string(),
+                spec: *spec,
+                slot: Rc::downgrade(&slot),
+            },
+        );
+        let request = Request {
+            id,
+            path: path.to_string(),
+            conversion: spec.conversion,
+        };
+        self.requests
+            .as_ref()
+            .unwrap()
+            .send(request)
+            .expect("Texture loader threads stopped");
+
+        AsyncTexture {
+            slot,
+            placeholder: Rc::clone(&self.placeholder),
+        }
+    }
+
+    /// Uploads the textures whose images are already decoded and returns how many of them
+    /// were uploaded. Images that failed to load are logged.
+    pub fn poll(&mut self) -> usize {
+        let mut uploaded = 0;
+        while let Ok((id, decoded)) = self.results.try_recv() {
+            uploaded += self.upload(id, decoded) as usize;
+        }
+        uploaded
+    }
+
+    /// Blocks until every texture requested so far is uploaded, e.g. behind a loading
+    /// screen.
+    pub fn finish(&mut self) {
+        while !self.pending.is_empty() {
+            match self.results.recv() {
+                Ok((id, decoded)) => {
+                    self.upload(id, decoded);
+                },
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Returns the number of textures that are still loading.
+    pub fn get_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Creates and fills the texture of a request, returning if it was uploaded.
+    fn upload(&mut self, id: u64, decoded: Result<Decoded, Error>) -> bool {
+        let pending = match self.pending.remove(&id) {
+            Some(pending) => pending,
+            None => return false,
+        };
+        let slot = match pending.slot.upgrade() {
+            Some(slot) => slot,
+            None => return false,
+        };
+        let image = match decoded {
+            Ok(image) => image,
+            Err(err) => {
+                error!("{}", err);
+                *slot.borrow_mut() = Slot::Failed;
+                return false;
+            },
+        };
+
+        let mut texture =
+            Texture2D::with_spec(image.width, image.height, image.format, &pending.spec);
+        texture.path = pending.path;
+        match self.pixel_buffer.as_mut() {
+            Some(buffer) if image.pixels.len() <= buffer.get_section_size() => {
+                let offset = buffer.stream(&image.pixels);
+                unsafe { texture.overwrite_from_buffer(buffer, offset) };
+                buffer.fence();
+            },
+            _ => unsafe { texture.overwrite(&image.pixels) },
+        }
+        texture.generate_mipmaps();
+        *slot.borrow_mut() = Slot::Ready(texture);
+        true
+    }
+}
+
+impl Drop for TextureLoader {
+    fn drop(&mut self) {
+        // Closing the channel stops the workers once they finish their current image.
+        self.requests = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Decodes an image on a worker thread.
+fn decode(path: &str, conversion: Conversion) -> Result<Decoded, Error> {
+    let (image, format) = load_image(path, conversion)?;
+    Ok(Decoded {
+        width: image.width(),
+        height: image.height(),
+        format,
+        pixels: image.flipv().into_bytes(),
+    })
+}
+
+/// Creates a 1x1 RGBA texture of the given color.
+fn solid_texture(color: [u8; 4]) -> Texture2D {
+    let mut texture = Texture2D::with_size(1, 1);
+    unsafe { texture.overwrite(&color) };
+    texture
+}
@@ -0,0 +1,193 @@
+//! Filtering and wrapping of texture lookups, either stored in the texture itself or in
+//! separate [sampler objects](https://www.khronos.org/opengl/wiki/Sampler_Object).
+
+use crate::gl_ext;
+
+/// How texels are combined when a texture is sampled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Filter {
+    Nearest = gl::NEAREST,
+    Linear = gl::LINEAR,
+}
+
+/// What is sampled outside of the `[0, 1]` range of texture coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Wrap {
+    Repeat = gl::REPEAT,
+    MirroredRepeat = gl::MIRRORED_REPEAT,
+    ClampToEdge = gl::CLAMP_TO_EDGE,
+    /// Samples the border color, see [`SamplerState::border_color`].
+    ClampToBorder = gl::CLAMP_TO_BORDER,
+    MirrorClampToEdge = gl::MIRROR_CLAMP_TO_EDGE,
+}
+
+/// The parameters that control how a texture is sampled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerState {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// Filter between mip levels, `None` samples only the base level.
+    pub mipmap_filter: Option<Filter>,
+    /// Wrap modes of the S, T and R coordinates.
+    pub wrap: [Wrap; 3],
+    pub border_color: [f32; 4],
+    /// Maximum anisotropy, 1 disables anisotropic filtering. It's clamped to the maximum
+    /// supported by the driver and ignored if it isn't supported at all.
+    pub anisotropy: f32,
+}
+
+impl Default for SamplerState {
+    /// The state textures have always been created with: linear minification, nearest
+    /// magnification, no mipmaps and repeat wrapping.
+    fn default() -> Self {
+        Self {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Nearest,
+            mipmap_filter: None,
+            wrap: [Wrap::Repeat; 3],
+            border_color: [0.0; 4],
+            anisotropy: 1.0,
+        }
+    }
+}
+
+impl SamplerState {
+    /// Sets the minification and magnification filters.
+    pub fn filters(mut self, min: Filter, mag: Filter) -> Self {
+        self.min_filter = min;
+        self.mag_filter = mag;
+        self
+    }
+
+    /// Sets the filter between mip levels.
+    pub fn mipmap_filter(mut self, filter: Filter) -> Self {
+        self.mipmap_filter = Some(filter);
+        self
+    }
+
+    /// Linear filtering within and between mip levels.
+    pub fn trilinear(self) -> Self {
+        self.filters(Filter::Linear, Filter::Linear)
+            .mipmap_filter(Filter::Linear)
+    }
+
+    /// Sets the same wrap mode for every coordinate.
+    pub fn wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap = [wrap; 3];
+        self
+    }
+
+    /// Sets the color sampled outside of the texture with [`Wrap::ClampToBorder`].
+    pub fn border_color(mut self, color: [f32; 4]) -> Self {
+        self.border_color = color;
+        self
+    }
+
+    /// Sets the maximum anisotropy.
+    pub fn anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    /// Returns the `GL_TEXTURE_MIN_FILTER` combining the minification and mipmap filters.
+    pub fn gl_min_filter(&self) -> u32 {
+        match (self.min_filter, self.mipmap_filter) {
+            (filter, None) => filter as u32,
+            (Filter::Nearest, Some(Filter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, Some(Filter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, Some(Filter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, Some(Filter::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    /// Sets the state on a texture or sampler object through the given
+    /// `gl*Parameteri`/`gl*Parameterfv` functions.
+    pub(crate) fn apply(
+        &self,
+        id: u32,
+        parameteri: unsafe fn(u32, u32, i32),
+        parameterfv: unsafe fn(u32, u32, *const f32),
+    ) {
+        let wrap_names = [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R];
+        unsafe {
+            parameteri(id, gl::TEXTURE_MIN_FILTER, self.gl_min_filter() as i32);
+            parameteri(id, gl::TEXTURE_MAG_FILTER, self.mag_filter as u32 as i32);
+            for (name, wrap) in wrap_names.into_iter().zip(self.wrap) {
+                parameteri(id, name, wrap as u32 as i32);
+            }
+            parameterfv(id, gl::TEXTURE_BORDER_COLOR, self.border_color.as_ptr());
+            if let Some(max_anisotropy) = gl_ext::max_anisotropy() {
+                let anisotropy = self.anisotropy.clamp(1.0, max_anisotropy);
+                parameterfv(id, gl_ext::TEXTURE_MAX_ANISOTROPY, &anisotropy);
+            }
+        }
+    }
+}
+
+/// Wrapper for a sampler object, which overrides the sampler state of any texture bound to
+/// the same unit.
+pub struct Sampler {
+    id: u32,
+    state: SamplerState,
+}
+
+impl Sampler {
+    /// Creates a sampler object with the given state.
+    pub fn new(state: SamplerState) -> Self {
+        let mut id = 0;
+        unsafe { gl::CreateSamplers(1, &mut id) };
+        state.apply(id, gl::SamplerParameteri, gl::SamplerParameterfv);
+        Self { id, state }
+    }
+
+    /// Replaces the state of the sampler.
+    pub fn set_state(&mut self, state: SamplerState) {
+        state.apply(self.id, gl::SamplerParameteri, gl::SamplerParameterfv);
+        self.state = state;
+    }
+
+    /// Returns the state of the sampler.
+    pub fn get_state(&self) -> &SamplerState {
+        &self.state
+    }
+
+    /// Binds the sampler to the specified texture unit.
+    pub fn bind(&self, unit: u32) {
+        unsafe { gl::BindSampler(unit, self.id) };
+    }
+
+    /// Unbinds any sampler from the texture unit, so textures bound to it use their own
+    /// sampler state again.
+    pub fn unbind(unit: u32) {
+        unsafe { gl::BindSampler(unit, 0) };
+    }
+
+    /// Returns the internal id of the sampler used by OpenGL.
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteSamplers(1, &self.id) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_filter_combines_mipmap_filter() {
+        let state = SamplerState::default();
+        assert_eq!(state.gl_min_filter(), gl::LINEAR);
+        assert_eq!(state.trilinear().gl_min_filter(), gl::LINEAR_MIPMAP_LINEAR);
+        let state = state
+            .filters(Filter::Nearest, Filter::Nearest)
+            .mipmap_filter(Filter::Linear);
+        assert_eq!(state.gl_min_filter(), gl::NEAREST_MIPMAP_LINEAR);
+    }
+}
@@ -0,0 +1,203 @@
+//! 3D textures, e.g. for volume data or color grading lookup tables.
+
+use super::{
+    create_texture, format::PixelFormat, load_image, mip_levels, sampler::SamplerState, Texture,
+    TextureSpec,
+};
+use crate::error::Error;
+use std::path::Path;
+
+/// A 3D texture.
+pub struct Texture3D {
+    id: u32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    format: PixelFormat,
+    mip_levels: u32,
+    sampler: SamplerState,
+}
+
+impl Texture3D {
+    /// Loads a cubic lookup table of `N` texels per side from an image of `N * N` by `N`
+    /// pixels, where the `N` slices along Z are laid side by side from left to right. Each
+    /// slice keeps the orientation of a [`Texture2D`](super::Texture2D).
+    pub fn from_lut_strip(path: &str, spec: &TextureSpec) -> Result<Self, Error> {
+        let (image, format) = load_image(path, spec.conversion)?;
+        let size = image.height();
+        if image.width() != size * size {
+            return Err(Error::Texture {
+                path: Path::new(path).to_path_buf(),
+                message: format!(
+                    "a lookup table strip must be {}x{} for {} slices, found {}x{}",
+                    size * size,
+                    size,
+                    size,
+                    image.width(),
+                    image.height()
+                ),
+            });
+        }
+
+        let data = strip_to_volume(
+            image.flipv().as_bytes(),
+            size as usize,
+            format.bytes_per_pixel(),
+        );
+        let mut texture = Self::with_spec(size, size, size, format, spec);
+        unsafe { texture.overwrite(&data) };
+        texture.generate_mipmaps();
+        Ok(texture)
+    }
+
+    /// Allocates memory in the GPU to store a 3D texture of the specified dimensions and
+    /// format, with the mip levels and sampler state of `spec`.
+    pub fn with_spec(
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: PixelFormat,
+        spec: &TextureSpec,
+    ) -> Self {
+        let mip_levels = mip_levels(spec, width.max(height).max(depth));
+        let id = create_texture(gl::TEXTURE_3D, format.swizzle, &spec.sampler);
+        unsafe {
+            gl::TextureStorage3D(
+                id,
+                mip_levels as i32,
+                format.internal_format,
+                width as i32,
+                height as i32,
+                depth as i32,
+            );
+        }
+
+        Self {
+            id,
+            width,
+            height,
+            depth,
+            format,
+            mip_levels,
+            sampler: spec.sampler,
+        }
+    }
+
+    /// Fills the base level of the texture with the given data, slice after slice.
+    ///
+    /// # Safety
+    ///
+    /// `data` must hold the texels in the layout of [`Texture3D::get_format`], which is
+    /// checked only by size.
+    pub unsafe fn overwrite(&mut self, data: &[u8]) {
+        assert_eq!(
+            data.len(),
+            self.width as usize
+                * self.height as usize
+                * self.depth as usize
+                * self.format.bytes_per_pixel(),
+            "Data does not have the same size as the texture!"
+        );
+
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TextureSubImage3D(
+            self.id,
+            0,
+            0,
+            0,
+            0,
+            self.width as i32,
+            self.height as i32,
+            self.depth as i32,
+            self.format.format,
+            self.format.data_type,
+            data.as_ptr().cast(),
+        );
+    }
+
+    /// Generates every mip level from the base level. Does nothing if the texture has a
+    /// single level.
+    pub fn generate_mipmaps(&self) {
+        if self.mip_levels > 1 {
+            unsafe { gl::GenerateTextureMipmap(self.id) };
+        }
+    }
+
+    /// Replaces the sampler state of the texture.
+    pub fn set_sampler_state(&mut self, sampler: SamplerState) {
+        sampler.apply(self.id, gl::TextureParameteri, gl::TextureParameterfv);
+        self.sampler = sampler;
+    }
+
+    /// Returns the sampler state of the texture.
+    pub fn get_sampler_state(&self) -> &SamplerState {
+        &self.sampler
+    }
+
+    /// Returns the formats the texture is stored and uploaded with.
+    pub fn get_format(&self) -> &PixelFormat {
+        &self.format
+    }
+
+    /// Returns the number of mip levels of the texture.
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+}
+
+impl Texture for Texture3D {
+    const TARGET: u32 = gl::TEXTURE_3D;
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn get_size(&self) -> [u32; 3] {
+        [self.width, self.height, self.depth]
+    }
+}
+
+impl Drop for Texture3D {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) };
+    }
+}
+
+impl PartialEq for Texture3D {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+/// Reorders the pixels of a strip of `size` slices of `size` by `size` texels laid side by
+/// side into consecutive slices.
+fn strip_to_volume(strip: &[u8], size: usize, bytes_per_pixel: usize) -> Vec<u8> {
+    let row = size * bytes_per_pixel;
+    let mut volume = Vec::with_capacity(strip.len());
+    for slice in 0..size {
+        for y in 0..size {
+            let start = y * row * size + slice * row;
+            volume.extend_from_slice(&strip[start..start + row]);
+        }
+    }
+    volume
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_slices_become_consecutive() {
+        // 2 slices of 2x2 single byte texels: slice 0 holds 0..4 and slice 1 holds 4..8.
+        let strip = [0, 1, 4, 5, 2, 3, 6, 7];
+        assert_eq!(strip_to_volume(&strip, 2, 1), [0, 1, 2, 3, 4, 5, 6, 7]);
+
+        let strip: Vec<u8> = [0, 1, 4, 5, 2, 3, 6, 7]
+            .iter()
+            .flat_map(|&texel| [texel; 3])
+            .collect();
+        let expected: Vec<u8> = (0..8).flat_map(|texel| [texel; 3]).collect();
+        assert_eq!(strip_to_volume(&strip, 2, 3), expected);
+    }
+}
@@ -1,5 +1,260 @@
-/// Basic wrapper for a [Vertex Array Object](https://www.khronos.org/opengl/wiki/Vertex_Specification#Vertex_Array_Object).
-pub struct VertexArray(pub u32);
+//! Vertex array objects and the description of the vertex data they read.
+
+use crate::buffer::{IndexBuffer, VertexBuffer};
+use glam::{
+    DVec2, DVec3, DVec4, IVec2, IVec3, IVec4, Mat2, Mat3, Mat4, UVec2, UVec3, UVec4, Vec2, Vec3,
+    Vec4,
+};
+use std::ops::Drop;
+
+pub use fenix_derive::Vertex;
+
+/// Data type of the components of a vertex attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ComponentType {
+    Byte = gl::BYTE,
+    UnsignedByte = gl::UNSIGNED_BYTE,
+    Short = gl::SHORT,
+    UnsignedShort = gl::UNSIGNED_SHORT,
+    Int = gl::INT,
+    UnsignedInt = gl::UNSIGNED_INT,
+    HalfFloat = gl::HALF_FLOAT,
+    Float = gl::FLOAT,
+    Double = gl::DOUBLE,
+}
+
+impl ComponentType {
+    /// Returns the size in bytes of a single component.
+    pub fn size(self) -> u32 {
+        match self {
+            ComponentType::Byte | ComponentType::UnsignedByte => 1,
+            ComponentType::Short | ComponentType::UnsignedShort | ComponentType::HalfFloat => 2,
+            ComponentType::Int | ComponentType::UnsignedInt | ComponentType::Float => 4,
+            ComponentType::Double => 8,
+        }
+    }
+
+    /// Checks if the type is an integer type.
+    pub fn is_integer(self) -> bool {
+        !matches!(
+            self,
+            ComponentType::HalfFloat | ComponentType::Float | ComponentType::Double
+        )
+    }
+}
+
+/// Description of a single attribute inside a vertex.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexAttribute {
+    pub name: String,
+    pub component_type: ComponentType,
+    /// Number of components of each location, between 1 and 4.
+    pub count: u32,
+    /// Number of consecutive locations the attribute takes, e.g. one per column of a
+    /// matrix or per element of an array.
+    pub columns: u32,
+    /// Whether integer data is normalized to `[0, 1]` (or `[-1, 1]` for signed types)
+    /// when read as floating point by the shader. Non-normalized integer attributes are
+    /// read as integers.
+    pub normalized: bool,
+    /// The `layout (location = N)` of the attribute in the vertex shader.
+    pub location: u32,
+    offset: u32,
+}
+
+impl VertexAttribute {
+    /// Creates a non-normalized attribute that takes a single location.
+    ///
+    /// # Panics
+    ///
+    /// If `count` isn't between 1 and 4, a location can't hold more components. Use
+    /// [`VertexAttribute::columns`] for matrices and arrays.
+    pub fn new(name: &str, location: u32, component_type: ComponentType, count: u32) -> Self {
+        assert!(
+            (1..=4).contains(&count),
+            "Vertex attributes have between 1 and 4 components per location"
+        );
+        Self {
+            name: name.to_string(),
+            component_type,
+            count,
+            columns: 1,
+            normalized: false,
+            location,
+            offset: 0,
+        }
+    }
+
+    /// Creates a non-normalized attribute whose type, component count and columns are
+    /// taken from the Rust type `T`.
+    pub fn of<T: AttributeFormat>(name: &str, location: u32) -> Self {
+        Self::new(name, location, T::COMPONENT_TYPE, T::COUNT).columns(T::COLUMNS)
+    }
+
+    /// Makes the attribute take `columns` consecutive locations starting at its location,
+    /// each of them with `count` components, like matrices and arrays do in GLSL.
+    ///
+    /// # Panics
+    ///
+    /// If `columns` is 0.
+    pub fn columns(mut self, columns: u32) -> Self {
+        assert!(columns > 0, "Vertex attributes take at least one location");
+        self.columns = columns;
+        self
+    }
+
+    /// Marks the attribute as normalized.
+    pub fn normalized(mut self) -> Self {
+        self.normalized = true;
+        self
+    }
+
+    /// Returns the size in bytes of a single column of the attribute.
+    pub fn column_size(&self) -> u32 {
+        self.component_type.size() * self.count
+    }
+
+    /// Returns the size in bytes of the attribute.
+    pub fn size(&self) -> u32 {
+        self.column_size() * self.columns
+    }
+
+    /// Returns the offset in bytes of the attribute from the start of the vertex.
+    pub fn get_offset(&self) -> u32 {
+        self.offset
+    }
+}
+
+/// Layout of the interleaved vertices stored in a vertex buffer.
+///
+/// Attributes are tightly packed in the order they are given, so their offsets and the
+/// stride of the vertices are computed automatically.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BufferLayout {
+    attributes: Vec<VertexAttribute>,
+    stride: u32,
+}
+
+impl BufferLayout {
+    /// Creates a layout from a list of attributes.
+    pub fn new(mut attributes: Vec<VertexAttribute>) -> Self {
+        let mut offset = 0;
+        for attribute in &mut attributes {
+            attribute.offset = offset;
+            offset += attribute.size();
+        }
+        Self {
+            attributes,
+            stride: offset,
+        }
+    }
+
+    /// Returns the attributes of the layout.
+    pub fn get_attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    /// Returns the size in bytes of a whole vertex.
+    pub fn get_stride(&self) -> u32 {
+        self.stride
+    }
+}
+
+/// Rust types that can be used as the fields of a [`Vertex`].
+pub trait AttributeFormat {
+    const COMPONENT_TYPE: ComponentType;
+    /// Components of each location, between 1 and 4.
+    const COUNT: u32;
+    /// Consecutive locations taken by the type.
+    const COLUMNS: u32 = 1;
+}
+
+macro_rules! impl_attribute_format {
+    ($($ty:ty => ($component_type:ident, $count:literal)),* $(,)?) => {
+        $(
+            impl AttributeFormat for $ty {
+                const COMPONENT_TYPE: ComponentType = ComponentType::$component_type;
+                const COUNT: u32 = $count;
+            }
+        )*
+    };
+}
+
+impl_attribute_format! {
+    f32 => (Float, 1), Vec2 => (Float, 2), Vec3 => (Float, 3), Vec4 => (Float, 4),
+    f64 => (Double, 1), DVec2 => (Double, 2), DVec3 => (Double, 3), DVec4 => (Double, 4),
+    i32 => (Int, 1), IVec2 => (Int, 2), IVec3 => (Int, 3), IVec4 => (Int, 4),
+    u32 => (UnsignedInt, 1), UVec2 => (UnsignedInt, 2), UVec3 => (UnsignedInt, 3),
+    UVec4 => (UnsignedInt, 4),
+    i16 => (Short, 1), u16 => (UnsignedShort, 1), i8 => (Byte, 1), u8 => (UnsignedByte, 1),
+}
+
+impl AttributeFormat for Mat2 {
+    const COMPONENT_TYPE: ComponentType = ComponentType::Float;
+    const COUNT: u32 = 2;
+    const COLUMNS: u32 = 2;
+}
+
+impl AttributeFormat for Mat3 {
+    const COMPONENT_TYPE: ComponentType = ComponentType::Float;
+    const COUNT: u32 = 3;
+    const COLUMNS: u32 = 3;
+}
+
+impl AttributeFormat for Mat4 {
+    const COMPONENT_TYPE: ComponentType = ComponentType::Float;
+    const COUNT: u32 = 4;
+    const COLUMNS: u32 = 4;
+}
+
+// Arrays of scalars are vectors, e.g. `[f32; 3]` is a `vec3`, while arrays of anything else
+// take a location per element like GLSL arrays.
+macro_rules! impl_attribute_format_array {
+    ($($count:literal),*) => {
+        $(
+            impl<T: AttributeFormat> AttributeFormat for [T; $count] {
+                const COMPONENT_TYPE: ComponentType = T::COMPONENT_TYPE;
+                const COUNT: u32 = match is_scalar::<T>() {
+                    true => $count,
+                    false => T::COUNT,
+                };
+                const COLUMNS: u32 = match is_scalar::<T>() {
+                    true => 1,
+                    false => T::COLUMNS * $count,
+                };
+            }
+        )*
+    };
+}
+
+const fn is_scalar<T: AttributeFormat>() -> bool {
+    T::COUNT == 1 && T::COLUMNS == 1
+}
+
+impl_attribute_format_array!(1, 2, 3, 4);
+
+/// A vertex type that can be stored in a [`VertexBuffer`].
+///
+/// Usually implemented with `#[derive(Vertex)]`, see [`fenix_derive::Vertex`].
+pub trait Vertex: bytemuck::Pod {
+    /// Returns the layout of the vertex inside a buffer.
+    fn layout() -> BufferLayout;
+
+    /// Views a slice of vertices as raw bytes.
+    fn as_bytes(vertices: &[Self]) -> &[u8] {
+        bytemuck::cast_slice(vertices)
+    }
+}
+
+/// Wrapper for a [Vertex Array Object](https://www.khronos.org/opengl/wiki/Vertex_Specification#Vertex_Array_Object).
+///
+/// The vertex array owns the buffers attached to it.
+pub struct VertexArray {
+    id: u32,
+    vertex_buffers: Vec<VertexBuffer>,
+    index_buffer: Option<IndexBuffer>,
+}
 
 impl VertexArray {
     /// Creates a new vertex array object. If the operation fails `None` is
@@ -10,15 +265,87 @@ impl VertexArray {
             gl::CreateVertexArrays(1, &mut vao);
         }
         if vao != 0 {
-            Some(Self(vao))
+            Some(Self {
+                id: vao,
+                vertex_buffers: Vec::new(),
+                index_buffer: None,
+            })
         } else {
             None
         }
     }
 
+    /// Attaches a vertex buffer whose contents are described by `layout`.
+    ///
+    /// Each vertex buffer gets its own binding index, in the order they are added.
+    pub fn add_vertex_buffer(&mut self, buffer: VertexBuffer, layout: &BufferLayout) {
+        let binding = self.vertex_buffers.len() as u32;
+        unsafe {
+            gl::VertexArrayVertexBuffer(
+                self.id,
+                binding,
+                buffer.get_id(),
+                0,
+                layout.get_stride() as i32,
+            );
+            for attribute in layout.get_attributes() {
+                let count = attribute.count as i32;
+                let ty = attribute.component_type as u32;
+                for column in 0..attribute.columns {
+                    let location = attribute.location + column;
+                    let offset = attribute.offset + column * attribute.column_size();
+                    gl::EnableVertexArrayAttrib(self.id, location);
+                    if attribute.component_type == ComponentType::Double {
+                        gl::VertexArrayAttribLFormat(self.id, location, count, ty, offset);
+                    } else if attribute.component_type.is_integer() && !attribute.normalized {
+                        gl::VertexArrayAttribIFormat(self.id, location, count, ty, offset);
+                    } else {
+                        gl::VertexArrayAttribFormat(
+                            self.id,
+                            location,
+                            count,
+                            ty,
+                            attribute.normalized as u8,
+                            offset,
+                        );
+                    }
+                    gl::VertexArrayAttribBinding(self.id, location, binding);
+                }
+            }
+        }
+        self.vertex_buffers.push(buffer);
+    }
+
+    /// Attaches the index buffer used for indexed drawing, replacing the previous one.
+    pub fn set_index_buffer(&mut self, buffer: IndexBuffer) {
+        unsafe { gl::VertexArrayElementBuffer(self.id, buffer.get_id()) };
+        self.index_buffer = Some(buffer);
+    }
+
+    /// Returns the vertex buffers attached to this vertex array.
+    pub fn get_vertex_buffers(&self) -> &[VertexBuffer] {
+        &self.vertex_buffers
+    }
+
+    /// Returns the vertex buffers attached to this vertex array so their contents can be
+    /// updated.
+    pub fn get_vertex_buffers_mut(&mut self) -> &mut [VertexBuffer] {
+        &mut self.vertex_buffers
+    }
+
+    /// Returns the index buffer attached to this vertex array, if any.
+    pub fn get_index_buffer(&self) -> Option<&IndexBuffer> {
+        self.index_buffer.as_ref()
+    }
+
+    /// Returns the internal id of the vertex array used by OpenGL.
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
     /// Binds this vertex array as the current vertex array object.
     pub fn bind(&self) {
-        unsafe { gl::BindVertexArray(self.0) };
+        unsafe { gl::BindVertexArray(self.id) };
     }
 
     /// Unbinds the current vertex array object.
@@ -27,4 +354,66 @@ impl VertexArray {
     }
 }
 
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteVertexArrays(1, &self.id) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_and_stride_are_packed() {
+        let layout = BufferLayout::new(vec![
+            VertexAttribute::of::<Vec3>("position", 0),
+            VertexAttribute::of::<[u8; 4]>("color", 1).normalized(),
+            VertexAttribute::of::<Vec2>("uv", 2),
+            VertexAttribute::of::<f64>("weight", 3),
+        ]);
+        let offsets: Vec<_> = layout
+            .get_attributes()
+            .iter()
+            .map(VertexAttribute::get_offset)
+            .collect();
+        assert_eq!(offsets, [0, 12, 16, 24]);
+        assert_eq!(layout.get_stride(), 32);
+        assert_eq!(layout.get_attributes()[1].count, 4);
+        assert_eq!(layout.get_attributes()[1].component_type, ComponentType::UnsignedByte);
+    }
+
+    #[test]
+    fn empty_layouts_have_no_stride() {
+        assert_eq!(BufferLayout::new(Vec::new()).get_stride(), 0);
+    }
+
+    #[test]
+    fn matrices_and_arrays_take_a_location_per_column() {
+        let model = VertexAttribute::of::<Mat4>("model", 4);
+        assert_eq!((model.count, model.columns, model.size()), (4, 4, 64));
+        let normal = VertexAttribute::of::<Mat3>("normal_matrix", 8);
+        assert_eq!((normal.count, normal.columns, normal.column_size()), (3, 3, 12));
 
+        let pair = VertexAttribute::of::<[Vec3; 2]>("pair", 0);
+        assert_eq!((pair.count, pair.columns), (3, 2));
+        let scalars = VertexAttribute::of::<[f32; 3]>("position", 0);
+        assert_eq!((scalars.count, scalars.columns), (3, 1));
+        let nested = VertexAttribute::of::<[[f32; 4]; 4]>("model", 0);
+        assert_eq!((nested.count, nested.columns), (4, 4));
+
+        let layout = BufferLayout::new(vec![
+            VertexAttribute::of::<Vec3>("position", 0),
+            model,
+            VertexAttribute::new("id", 8, ComponentType::UnsignedInt, 1),
+        ]);
+        assert_eq!(layout.get_attributes()[2].get_offset(), 12 + 64);
+        assert_eq!(layout.get_stride(), 12 + 64 + 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "between 1 and 4 components")]
+    fn locations_hold_at_most_four_components() {
+        VertexAttribute::new("too_wide", 0, ComponentType::Float, 5);
+    }
+}
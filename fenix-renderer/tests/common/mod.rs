@@ -0,0 +1,255 @@
+//! Harness for golden-image tests: renders scenes offscreen with a headless context and
+//! compares them against the reference PNGs in `tests/golden`.
+//!
+//! Set `FENIX_UPDATE_GOLDEN=1` to overwrite the references with the rendered images instead
+//! of comparing them. On a mismatch the rendered image and a diff image are written to
+//! `target/tmp/golden`, next to the error message.
+
+// Each test crate only uses part of the harness.
+#![allow(dead_code)]
+
+use fenix_renderer::{
+    framebuffer::{AttachmentFormat, AttachmentSpec, Framebuffer, FramebufferSpec},
+    headless::HeadlessContext,
+};
+use image::{Rgba, RgbaImage};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+/// Renders with a single context at a time. OpenGL functions are loaded into globals, so
+/// contexts created by tests running in parallel would race to load them.
+static CONTEXT_LOCK: Mutex<()> = Mutex::new(());
+
+/// A headless context that is current for the duration of a test.
+///
+/// Must be created before any OpenGL object and dropped after all of them, i.e. be the first
+/// variable of the test.
+pub struct TestContext {
+    _context: HeadlessContext,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TestContext {
+    pub fn new() -> Self {
+        let guard = CONTEXT_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let context = HeadlessContext::new().expect("Failed to create headless context");
+        Self {
+            _context: context,
+            _guard: guard,
+        }
+    }
+}
+
+/// Returns the path of a file in the `assets` directory of the workspace.
+pub fn asset(path: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../assets")
+        .join(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Calls `draw` with a `width` by `height` framebuffer bound, with color and depth, and
+/// reads back the result.
+pub fn render(width: u32, height: u32, draw: impl FnOnce()) -> RgbaImage {
+    let framebuffer = Framebuffer::new(
+        FramebufferSpec::new(width, height)
+            .color(AttachmentSpec::texture(AttachmentFormat::Rgba8))
+            .depth(AttachmentSpec::renderbuffer(
+                AttachmentFormat::Depth24Stencil8,
+            )),
+    )
+    .expect("Failed to create framebuffer");
+    framebuffer.bind();
+    draw();
+    Framebuffer::unbind();
+    framebuffer.read_pixels(0).expect("Failed to read framebuffer")
+}
+
+/// How much a rendered image may differ from its reference.
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    /// Maximum difference of any channel for two pixels to be considered equal.
+    pub channel: u8,
+    /// Fraction of pixels that may be different, e.g. along rasterized edges.
+    pub mismatched: f64,
+    /// Minimum structural similarity (SSIM) between both images, 1 being identical.
+    pub ssim: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            mismatched: 0.001,
+            ssim: 0.99,
+        }
+    }
+}
+
+/// The result of comparing two images of the same size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Comparison {
+    /// Number of pixels with a channel differing more than the tolerance.
+    pub mismatched: usize,
+    /// Largest difference of any channel.
+    pub max_difference: u8,
+    /// Mean structural similarity of the luma of both images.
+    pub ssim: f64,
+}
+
+impl Comparison {
+    pub fn passes(&self, tolerance: &Tolerance, pixel_count: usize) -> bool {
+        self.mismatched as f64 <= tolerance.mismatched * pixel_count as f64
+            && self.ssim >= tolerance.ssim
+    }
+}
+
+/// Compares `actual` against `expected`, which must have the same size.
+pub fn compare(expected: &RgbaImage, actual: &RgbaImage, channel_tolerance: u8) -> Comparison {
+    assert_eq!(expected.dimensions(), actual.dimensions());
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    for (a, b) in expected.pixels().zip(actual.pixels()) {
+        let difference = pixel_difference(a, b);
+        max_difference = max_difference.max(difference);
+        if difference > channel_tolerance {
+            mismatched += 1;
+        }
+    }
+    Comparison {
+        mismatched,
+        max_difference,
+        ssim: ssim(expected, actual),
+    }
+}
+
+fn pixel_difference(a: &Rgba<u8>, b: &Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0)
+        .map(|(&a, b)| a.abs_diff(b))
+        .max()
+        .unwrap()
+}
+
+/// Mean [SSIM](https://en.wikipedia.org/wiki/Structural_similarity) of the luma of two
+/// images, over 8x8 windows placed every 4 pixels.
+pub fn ssim(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    const WINDOW: u32 = 8;
+    const STRIDE: u32 = 4;
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let luma = |pixel: &Rgba<u8>| {
+        let [r, g, b, _] = pixel.0.map(f64::from);
+        0.299 * r + 0.587 * g + 0.114 * b
+    };
+    let (width, height) = a.dimensions();
+    let (window_width, window_height) = (WINDOW.min(width), WINDOW.min(height));
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y in (0..=height - window_height).step_by(STRIDE as usize) {
+        for x in (0..=width - window_width).step_by(STRIDE as usize) {
+            let samples: Vec<(f64, f64)> = (y..y + window_height)
+                .flat_map(|y| (x..x + window_width).map(move |x| (x, y)))
+                .map(|(x, y)| (luma(a.get_pixel(x, y)), luma(b.get_pixel(x, y))))
+                .collect();
+            let n = samples.len() as f64;
+            let mean_a = samples.iter().map(|s| s.0).sum::<f64>() / n;
+            let mean_b = samples.iter().map(|s| s.1).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for (la, lb) in &samples {
+                var_a += (la - mean_a) * (la - mean_a);
+                var_b += (lb - mean_b) * (lb - mean_b);
+                covariance += (la - mean_a) * (lb - mean_b);
+            }
+            let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+/// Highlights in red the pixels that differ more than `channel_tolerance`, over a darkened
+/// grayscale version of `expected`.
+pub fn diff_image(expected: &RgbaImage, actual: &RgbaImage, channel_tolerance: u8) -> RgbaImage {
+    RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let (a, b) = (expected.get_pixel(x, y), actual.get_pixel(x, y));
+        let difference = pixel_difference(a, b);
+        if difference > channel_tolerance {
+            Rgba([difference.max(128), 0, 0, 255])
+        } else {
+            let [r, g, b, _] = a.0.map(u32::from);
+            let gray = ((r * 299 + g * 587 + b * 114) / 1000 / 3) as u8;
+            Rgba([gray, gray, gray, 255])
+        }
+    })
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name))
+}
+
+fn output_path(name: &str, suffix: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(format!("{}.{}.png", name, suffix))
+}
+
+/// Checks that `actual` matches the golden image `tests/golden/<name>.png`.
+pub fn assert_golden(name: &str, actual: &RgbaImage, tolerance: Tolerance) {
+    let golden = golden_path(name);
+    if env::var_os("FENIX_UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        actual.save(&golden).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&golden) {
+        Ok(image) => image.into_rgba8(),
+        Err(err) => {
+            let actual_path = output_path(name, "actual");
+            actual.save(&actual_path).unwrap();
+            panic!(
+                "Couldn't open golden image {} ({}), rendered image saved to {}. \
+                 Run with FENIX_UPDATE_GOLDEN=1 to create it.",
+                golden.display(),
+                err,
+                actual_path.display()
+            );
+        },
+    };
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "{}: size differs from the golden image",
+        name
+    );
+
+    let comparison = compare(&expected, actual, tolerance.channel);
+    if !comparison.passes(&tolerance, expected.len() / 4) {
+        let actual_path = output_path(name, "actual");
+        let diff_path = output_path(name, "diff");
+        actual.save(&actual_path).unwrap();
+        diff_image(&expected, actual, tolerance.channel)
+            .save(&diff_path)
+            .unwrap();
+        panic!(
+            "{}: rendered image doesn't match the golden image ({:?}, tolerance {:?})\n\
+             rendered: {}\ndiff: {}",
+            name,
+            comparison,
+            tolerance,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
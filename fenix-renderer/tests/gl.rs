@@ -0,0 +1,226 @@
+//! Tests of OpenGL resources that need a headless context but render no reference scene,
+//! see `golden.rs` for those:
+//!
+//! ```sh
+//! cargo test -p fenix-renderer --features headless --test gl
+//! ```
+
+mod common;
+
+use common::TestContext;
+use fenix_renderer::{
+    framebuffer::{AttachmentFormat, AttachmentSpec, BlitMask, Framebuffer, FramebufferSpec},
+    shader::{
+        compute::ComputeProgram, preprocessor::Preprocessor, reflection::DataType,
+        reload::ShaderReloader, ShaderProgram, ShaderType,
+    },
+    Error,
+};
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// An edit that breaks a program by including a broken file must start watching that
+/// file, so fixing only the include reloads the program.
+#[test]
+fn reload_watches_includes_of_failed_builds() {
+    let _context = TestContext::new();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("reload");
+    fs::create_dir_all(&dir).unwrap();
+    let vert = dir.join("shader.vert");
+    let frag = dir.join("shader.frag");
+    let common = dir.join("common.glsl");
+    let write = |path: &Path, contents: &str, age: u64| {
+        fs::write(path, contents).unwrap();
+        let time = SystemTime::now() - Duration::from_secs(age);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(time))
+            .unwrap();
+    };
+    write(&vert, "#version 450 core\nvoid main() { gl_Position = vec4(0.0); }\n", 30);
+    write(
+        &frag,
+        "#version 450 core\nout vec4 color;\nvoid main() { color = vec4(1.0); }\n",
+        30,
+    );
+    let _ = fs::remove_file(&common);
+
+    let mut reloader = ShaderReloader::new();
+    let handle = reloader.watch(
+        ShaderProgram::from_stage_files(
+            &Preprocessor::new(),
+            &[
+                (ShaderType::Vertex, &vert.to_string_lossy()),
+                (ShaderType::Fragment, &frag.to_string_lossy()),
+            ],
+        )
+        .unwrap(),
+    );
+    assert!(reloader.poll().is_empty());
+
+    write(&common, "vec4 tint() { return vec4(1.0) }\n", 20);
+    write(
+        &frag,
+        "#version 450 core\n#include \"common.glsl\"\nout vec4 color;\n\
+         void main() { color = tint(); }\n",
+        20,
+    );
+    assert!(reloader.poll().is_empty());
+
+    write(&common, "vec4 tint() { return vec4(1.0); }\n", 10);
+    assert_eq!(reloader.poll(), [handle]);
+    let sources = reloader.get(handle).get_sources().unwrap();
+    assert!(sources.get_files().contains(&common));
+}
+
+/// The resources the driver reports for a program using every kind of them.
+#[test]
+fn program_reflection() {
+    let _context = TestContext::new();
+    let vertex = "#version 450 core
+layout(location = 0) in vec3 a_position;
+layout(location = 2) in vec2 a_uv;
+layout(std140, binding = 1) uniform Camera {
+    mat4 view;
+    mat4 projection;
+};
+out vec2 v_uv;
+void main() {
+    v_uv = a_uv;
+    gl_Position = projection * view * vec4(a_position, 1.0);
+}
+";
+    let fragment = "#version 450 core
+in vec2 v_uv;
+layout(location = 3) uniform vec4 u_tints[2];
+layout(location = 7) uniform sampler2D u_texture;
+layout(location = 8) uniform bool u_enabled;
+layout(std430, binding = 2) buffer Lights {
+    vec4 colors[];
+};
+out vec4 color;
+void main() {
+    color = u_enabled ? texture(u_texture, v_uv) * u_tints[1] * colors[0] : vec4(0.0);
+}
+";
+    let program = ShaderProgram::from_stage_sources(&[
+        (ShaderType::Vertex, vertex),
+        (ShaderType::Fragment, fragment),
+    ])
+    .unwrap();
+    let reflection = program.get_reflection();
+
+    let mut inputs: Vec<_> = reflection
+        .get_inputs()
+        .iter()
+        .map(|input| (input.name.as_str(), input.data_type, input.location))
+        .collect();
+    inputs.sort_by_key(|input| input.2);
+    assert_eq!(
+        inputs,
+        [
+            ("a_position", DataType::Vec3, Some(0)),
+            ("a_uv", DataType::Vec2, Some(2)),
+        ]
+    );
+
+    let tints = reflection.get_uniform("u_tints").unwrap();
+    assert_eq!(
+        (tints.name.as_str(), tints.data_type, tints.array_size, tints.location),
+        ("u_tints[0]", DataType::Vec4, 2, Some(3))
+    );
+    let texture = reflection.get_uniform("u_texture").unwrap();
+    assert_eq!((texture.data_type, texture.location), (DataType::Sampler2D, Some(7)));
+    let enabled = reflection.get_uniform("u_enabled").unwrap();
+    assert_eq!((enabled.data_type, enabled.location), (DataType::Bool, Some(8)));
+
+    let camera = &reflection.get_uniform_blocks()[0];
+    assert_eq!(
+        (camera.name.as_str(), camera.binding, camera.data_size),
+        ("Camera", 1, 128)
+    );
+    let mut members = camera.members.clone();
+    members.sort();
+    assert_eq!(members, ["projection", "view"]);
+    let projection = reflection.get_uniform("projection").unwrap();
+    assert_eq!(
+        (projection.data_type, projection.location, projection.block, projection.offset),
+        (DataType::Mat4, None, Some(0), Some(64))
+    );
+
+    let lights = &reflection.get_storage_blocks()[0];
+    assert_eq!((lights.name.as_str(), lights.binding), ("Lights", 2));
+    assert_eq!(lights.members, ["colors[0]"]);
+}
+
+/// Compute programs know their work group size, and other programs are rejected.
+#[test]
+fn compute_programs_need_a_compute_stage() {
+    let _context = TestContext::new();
+    let compute = ComputeProgram::from_source(
+        "#version 450 core\nlayout(local_size_x = 8, local_size_y = 4) in;\nvoid main() {}\n",
+    )
+    .unwrap();
+    assert_eq!(compute.get_work_group_size(), [8, 4, 1]);
+    assert!(ComputeProgram::from_program(compute.into_program()).is_ok());
+
+    let graphics = ShaderProgram::from_stage_sources(&[
+        (
+            ShaderType::Vertex,
+            "#version 450 core\nvoid main() { gl_Position = vec4(0.0); }\n",
+        ),
+        (
+            ShaderType::Fragment,
+            "#version 450 core\nout vec4 color;\nvoid main() { color = vec4(1.0); }\n",
+        ),
+    ])
+    .unwrap();
+    assert!(matches!(
+        ComputeProgram::from_program(graphics),
+        Err(Error::MissingStage { stage: ShaderType::Compute })
+    ));
+    assert_eq!(unsafe { gl::GetError() }, gl::NO_ERROR);
+}
+
+/// Integer attachments are blitted with nearest filtering even when scaled, and can't be
+/// read back as 8-bit RGBA.
+#[test]
+fn integer_attachments() {
+    let _context = TestContext::new();
+    let ids = |width, height| {
+        Framebuffer::new(
+            FramebufferSpec::new(width, height)
+                .color(AttachmentSpec::texture(AttachmentFormat::R32I)),
+        )
+        .unwrap()
+    };
+    let small = ids(4, 4);
+    let large = ids(8, 8);
+    unsafe { gl::ClearNamedFramebufferiv(small.get_id(), gl::COLOR, 0, [7, 0, 0, 0].as_ptr()) };
+    small.blit_to(&large, BlitMask::COLOR);
+    assert_eq!(unsafe { gl::GetError() }, gl::NO_ERROR);
+
+    let mut id = 0;
+    unsafe {
+        gl::GetTextureSubImage(
+            large.get_color_attachment_id(0).unwrap(),
+            0,
+            7,
+            7,
+            0,
+            1,
+            1,
+            1,
+            gl::RED_INTEGER,
+            gl::INT,
+            4,
+            &mut id as *mut i32 as *mut _,
+        )
+    };
+    assert_eq!(id, 7);
+    assert!(matches!(large.read_pixels(0), Err(Error::Framebuffer { .. })));
+}
@@ -0,0 +1,391 @@
+//! Golden-image tests of reference scenes, rendered with a headless context. They are meant
+//! to run on Mesa's llvmpipe so the output doesn't depend on the GPU:
+//!
+//! ```sh
+//! LIBGL_ALWAYS_SOFTWARE=1 cargo test -p fenix-renderer --features headless --test golden
+//! ```
+//!
+//! See the `common` module to update the golden images.
+
+mod common;
+
+use bytemuck::{Pod, Zeroable};
+use common::{assert_golden, asset, render, TestContext, Tolerance};
+use fenix_renderer::{
+    asset::AssetManager,
+    block_layout::BlockLayout,
+    buffer::{IndexBuffer, UniformBuffer, VertexBuffer},
+    framebuffer::{AttachmentFormat, AttachmentSpec, Framebuffer, FramebufferSpec},
+    mesh::Mesh,
+    shader::{preprocessor::Preprocessor, ShaderProgram, ShaderType},
+    texture::{
+        loader::{LoadState, TextureLoader},
+        format::Conversion,
+        Texture, Texture2D, TextureSpec,
+    },
+    vertex_array::{Vertex, VertexArray},
+};
+use glam::{Mat4, Vec2, Vec3};
+use image::{Rgba, RgbaImage};
+use std::mem;
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 180;
+const CAMERA_BINDING: u32 = 0;
+
+#[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+#[repr(C)]
+struct QuadVertex {
+    #[location(0)]
+    pos: Vec3,
+    #[location(3)]
+    uv: Vec2,
+}
+
+#[derive(BlockLayout)]
+struct Camera {
+    view: Mat4,
+    projection: Mat4,
+}
+
+fn camera(view: Mat4) -> UniformBuffer<Camera> {
+    let projection = Mat4::perspective_rh(45.0, WIDTH as f32 / HEIGHT as f32, 0.1, 10.0);
+    let buffer = UniformBuffer::new(&Camera { view, projection });
+    buffer.bind(CAMERA_BINDING);
+    buffer
+}
+
+fn draw_indexed(vertex_array: &VertexArray, offset: usize, count: usize) {
+    vertex_array.bind();
+    unsafe {
+        gl::DrawElements(
+            gl::TRIANGLES,
+            count as i32,
+            gl::UNSIGNED_INT,
+            (offset * mem::size_of::<u32>()) as *const _,
+        );
+    }
+    VertexArray::unbind();
+}
+
+const QUAD_VERTEX_SHADER: &str = r#"
+#version 450 core
+
+layout (location = 0) in vec3 a_pos;
+layout (location = 3) in vec2 a_texcoord;
+
+out vec2 v_texcoord;
+
+layout (std140) uniform Camera
+{
+    mat4 view;
+    mat4 projection;
+} u_camera;
+
+uniform mat4 u_model;
+
+void main()
+{
+    v_texcoord = a_texcoord;
+    gl_Position = u_camera.projection * u_camera.view * u_model * vec4(a_pos, 1.0);
+}
+"#;
+
+const QUAD_FRAGMENT_SHADER: &str = r#"
+#version 450 core
+
+in vec2 v_texcoord;
+
+out vec4 frag_color;
+
+uniform sampler2D color_map0;
+uniform sampler2D color_map1;
+
+void main()
+{
+    frag_color = mix(
+        texture(color_map0, v_texcoord),
+        texture(color_map1, v_texcoord),
+        0.25
+    );
+}
+"#;
+
+/// Renders the textured quad `fenix-app` used to draw before it loaded `assets/cube.obj`,
+/// binding its textures with `bind_textures`.
+fn render_textured_quad(bind_textures: impl Fn()) -> RgbaImage {
+    let vertices = [
+        ([0.0, 0.0, 0.0], [0.0, 0.0]),
+        ([1.0, 0.0, 0.0], [1.0, 0.0]),
+        ([1.0, 1.0, 0.0], [1.0, 1.0]),
+        ([0.0, 1.0, 0.0], [0.0, 1.0]),
+    ]
+    .map(|(pos, uv)| QuadVertex {
+        pos: Vec3::from_array(pos),
+        uv: Vec2::from_array(uv),
+    });
+    let mut vertex_array = VertexArray::new().unwrap();
+    vertex_array.add_vertex_buffer(VertexBuffer::from(&vertices), &QuadVertex::layout());
+    vertex_array.set_index_buffer(IndexBuffer::from(&[0, 1, 2, 0, 2, 3]));
+
+    let mut program = ShaderProgram::from_stage_sources(&[
+        (ShaderType::Vertex, QUAD_VERTEX_SHADER),
+        (ShaderType::Fragment, QUAD_FRAGMENT_SHADER),
+    ])
+    .unwrap();
+    program
+        .bind_uniform_block("Camera", CAMERA_BINDING)
+        .unwrap();
+    let _camera = camera(
+        Mat4::from_translation(Vec3::new(0.0, 0.0, -3.0))
+            * Mat4::from_axis_angle(Vec3::new(-1.0, 1.0, -1.0).normalize(), 30f32.to_radians()),
+    );
+
+    render(WIDTH, HEIGHT, || {
+        bind_textures();
+        program.bind();
+        program
+            .set("u_model", &Mat4::from_translation(Vec3::splat(-0.5)))
+            .unwrap();
+        program.set("color_map0", &0).unwrap();
+        program.set("color_map1", &1).unwrap();
+        unsafe {
+            gl::ClearColor(0.2, 0.3, 0.3, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        draw_indexed(&vertex_array, 0, 6);
+    })
+}
+
+/// Two textures blended on a quad.
+#[test]
+fn textured_quad() {
+    let _context = TestContext::new();
+    let container = Texture2D::from_file(&asset("image/container.jpg")).unwrap();
+    let awesome = Texture2D::from_file(&asset("image/awesomeface.png")).unwrap();
+
+    let image = render_textured_quad(|| {
+        container.bind(0);
+        awesome.bind(1);
+    });
+    assert_golden("textured_quad", &image, Tolerance::default());
+}
+
+/// The same quad with textures decoded on worker threads and streamed through a pixel
+/// buffer, which must match the synchronous upload.
+#[test]
+fn async_textured_quad() {
+    let _context = TestContext::new();
+    let mut loader = TextureLoader::new(2).pixel_buffer(1 << 20, 2);
+    let spec = TextureSpec::default();
+    let container = loader.load(&asset("image/container.jpg"), &spec);
+    let awesome = loader.load(&asset("image/awesomeface.png"), &spec);
+    let missing = loader.load(&asset("image/missing.png"), &spec);
+    assert_eq!(container.get_size(), [1, 1, 1]);
+
+    loader.finish();
+    assert_eq!(loader.get_pending(), 0);
+    assert_eq!(container.get_state(), LoadState::Ready);
+    assert_eq!(missing.get_state(), LoadState::Failed);
+    assert_eq!(missing.get_size(), [1, 1, 1]);
+
+    let image = render_textured_quad(|| {
+        container.bind(0);
+        awesome.bind(1);
+    });
+    assert_golden("textured_quad", &image, Tolerance::default());
+}
+
+/// The same quad with textures shared through an [`AssetManager`], which loads each file
+/// once and frees it with its last handle.
+#[test]
+fn managed_textured_quad() {
+    let _context = TestContext::new();
+    let mut assets = AssetManager::new()
+        .root("does/not/exist")
+        .root(asset(""));
+    let spec = TextureSpec::default();
+    let container = assets.load_texture("image/container.jpg", &spec).unwrap();
+    let awesome = assets.load_texture("image/awesomeface.png", &spec).unwrap();
+    let same = assets
+        .load_texture("./image/../image/container.jpg", &spec)
+        .unwrap();
+    assert!(same == container);
+    assert_eq!(container.get_count(), 2);
+    assert_eq!(assets.get_loaded(), 2);
+
+    let stages = [
+        (ShaderType::Vertex, "shader.vert"),
+        (ShaderType::Fragment, "shader.frag"),
+    ];
+    let program = assets.load_program(&stages).unwrap();
+    assert!(assets.load_program(&stages).unwrap() == program);
+    assert!(assets.poll().is_empty());
+
+    let image = render_textured_quad(|| {
+        container.bind(0);
+        awesome.bind(1);
+    });
+    assert_golden("textured_quad", &image, Tolerance::default());
+
+    let id = container.get_id();
+    drop((container, same, program));
+    assert_eq!(assets.get_loaded(), 1);
+    assert_eq!(unsafe { gl::IsTexture(id) }, gl::FALSE);
+    let reloaded = assets.load_texture("image/container.jpg", &spec).unwrap();
+    assert_eq!(reloaded.get_count(), 1);
+}
+
+/// `assets/cube.obj` with its material, drawn with the shaders of `fenix-app`.
+#[test]
+fn obj_cube() {
+    let _context = TestContext::new();
+    let mesh = Mesh::from_file(&asset("cube.obj")).unwrap();
+    let mut program = ShaderProgram::from_stage_files(
+        &Preprocessor::new(),
+        &[
+            (ShaderType::Vertex, &asset("shader.vert")),
+            (ShaderType::Fragment, &asset("shader.frag")),
+        ],
+    )
+    .unwrap();
+    program
+        .bind_uniform_block("Camera", CAMERA_BINDING)
+        .unwrap();
+    let _camera = camera(Mat4::look_at_rh(
+        Vec3::new(1.5, 1.2, 2.0),
+        Vec3::ZERO,
+        Vec3::Y,
+    ));
+
+    let image = render(WIDTH, HEIGHT, || {
+        program.bind();
+        program
+            .set("u_model", &Mat4::from_rotation_y(20f32.to_radians()))
+            .unwrap();
+        unsafe {
+            gl::Enable(gl::DEPTH_TEST);
+            gl::ClearColor(0.1, 0.1, 0.1, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+        for submesh in mesh.get_submeshes() {
+            if let Some(material) = submesh.material {
+                mesh.get_materials()[material].apply(&mut program).unwrap();
+            }
+            draw_indexed(mesh.get_vertex_array(), submesh.offset, submesh.count);
+        }
+        unsafe { gl::Disable(gl::DEPTH_TEST) };
+    });
+    assert_golden("obj_cube", &image, Tolerance::default());
+}
+
+/// Textures read back with `Texture2D::read_pixels` match the images they were loaded from.
+#[test]
+fn texture_readback() {
+    let _context = TestContext::new();
+    for name in ["image/container.jpg", "image/awesomeface.png"] {
+        let texture = Texture2D::from_file(&asset(name)).unwrap();
+        let image = image::open(asset(name)).unwrap();
+        assert_eq!(texture.read_pixels(), image, "{}", name);
+    }
+
+    let texture = Texture2D::from_file_as(&asset("image/awesomeface.png"), Conversion::Rgba32F)
+        .unwrap();
+    let image = image::open(asset("image/awesomeface.png")).unwrap();
+    assert_eq!(texture.read_pixels().into_rgba32f(), image.into_rgba32f());
+}
+
+/// A triangle rendered with 4x MSAA and resolved, which smooths its edges.
+#[test]
+fn msaa_resolve() {
+    let _context = TestContext::new();
+    let mut program = ShaderProgram::from_stage_sources(&[
+        (
+            ShaderType::Vertex,
+            r#"
+            #version 450 core
+            const vec2 positions[3] = vec2[](vec2(-0.8, -0.7), vec2(0.9, -0.3), vec2(-0.2, 0.8));
+            void main() { gl_Position = vec4(positions[gl_VertexID], 0.0, 1.0); }
+            "#,
+        ),
+        (
+            ShaderType::Fragment,
+            r#"
+            #version 450 core
+            out vec4 frag_color;
+            uniform vec3 u_color;
+            void main() { frag_color = vec4(u_color, 1.0); }
+            "#,
+        ),
+    ])
+    .unwrap();
+    let vertex_array = VertexArray::new().unwrap();
+    let multisampled = Framebuffer::new(
+        FramebufferSpec::new(WIDTH, HEIGHT)
+            .samples(4)
+            .color(AttachmentSpec::renderbuffer(AttachmentFormat::Rgba8)),
+    )
+    .unwrap();
+
+    let resolved = Framebuffer::new(
+        FramebufferSpec::new(WIDTH, HEIGHT).color(AttachmentSpec::texture(AttachmentFormat::Rgba8)),
+    )
+    .unwrap();
+
+    multisampled.bind();
+    program.bind();
+    program.set("u_color", &Vec3::new(1.0, 0.6, 0.1)).unwrap();
+    vertex_array.bind();
+    unsafe {
+        gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+    }
+    VertexArray::unbind();
+    Framebuffer::unbind();
+    multisampled.resolve_to(&resolved);
+
+    let image = resolved.read_pixels(0).unwrap();
+    assert_golden("msaa_resolve", &image, Tolerance::default());
+    // Some edge pixels must be a blend of the triangle and the background.
+    let blended = image
+        .pixels()
+        .filter(|pixel| pixel.0[0] > 0 && pixel.0[0] < 255)
+        .count();
+    assert!(blended > 0, "The triangle edges aren't antialiased");
+}
+
+#[test]
+fn comparison_counts_pixels_over_tolerance() {
+    let expected = RgbaImage::from_pixel(16, 16, Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, Rgba([102, 100, 100, 255]));
+    actual.put_pixel(1, 0, Rgba([100, 110, 100, 255]));
+
+    let comparison = common::compare(&expected, &actual, 2);
+    assert_eq!(comparison.mismatched, 1);
+    assert_eq!(comparison.max_difference, 10);
+    assert!(comparison.ssim > 0.99);
+    assert_eq!(common::compare(&expected, &expected, 0).ssim, 1.0);
+
+    let diff = common::diff_image(&expected, &actual, 2);
+    assert_eq!(diff.get_pixel(1, 0), &Rgba([128, 0, 0, 255]));
+    assert_eq!(diff.get_pixel(0, 0), &Rgba([33, 33, 33, 255]));
+}
+
+#[test]
+fn ssim_detects_structural_changes() {
+    let checkerboard = RgbaImage::from_fn(32, 32, |x, y| {
+        let value = if (x / 2 + y / 2) % 2 == 0 { 0 } else { 255 };
+        Rgba([value, value, value, 255])
+    });
+    let flat = RgbaImage::from_pixel(32, 32, Rgba([128, 128, 128, 255]));
+    let brighter = RgbaImage::from_fn(32, 32, |x, y| {
+        let [value, ..] = checkerboard.get_pixel(x, y).0;
+        let value = value.saturating_add(4);
+        Rgba([value, value, value, 255])
+    });
+
+    assert!(common::ssim(&checkerboard, &flat) < 0.1);
+    assert!(common::ssim(&checkerboard, &brighter) > 0.99);
+}